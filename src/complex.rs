@@ -0,0 +1,135 @@
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use sparse::dok::{One, Zero};
+
+/// A complex number in rectangular form.
+///
+/// The layout is that of a two-element array, so that complex values can be
+/// read in place from memory-mapped files.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[repr(C)]
+pub struct Complex<T> {
+    pub re: T,
+    pub im: T,
+}
+
+impl<T> Complex<T> {
+    /// Create a complex number from its real and imaginary parts.
+    pub fn new(re: T, im: T) -> Self {
+        Complex { re, im }
+    }
+}
+
+impl<T> Complex<T>
+    where T: Copy + Neg<Output = T>
+{
+    /// Get the complex conjugate of this number.
+    pub fn conj(&self) -> Self {
+        Complex::new(self.re, -self.im)
+    }
+}
+
+impl<T> Complex<T>
+    where T: Copy + Add<Output = T> + Mul<Output = T>
+{
+    /// Get the squared magnitude of this number.
+    pub fn norm_sqr(&self) -> T {
+        self.re * self.re + self.im * self.im
+    }
+}
+
+impl<T> Add for Complex<T>
+    where T: Add<Output = T>
+{
+    type Output = Complex<T>;
+
+    fn add(self, other: Complex<T>) -> Complex<T> {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl<T> Sub for Complex<T>
+    where T: Sub<Output = T>
+{
+    type Output = Complex<T>;
+
+    fn sub(self, other: Complex<T>) -> Complex<T> {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl<T> Mul for Complex<T>
+    where T: Copy + Add<Output = T> + Sub<Output = T> + Mul<Output = T>
+{
+    type Output = Complex<T>;
+
+    fn mul(self, other: Complex<T>) -> Complex<T> {
+        Complex::new(self.re * other.re - self.im * other.im,
+                     self.re * other.im + self.im * other.re)
+    }
+}
+
+impl<T> Div for Complex<T>
+    where T: Copy + Add<Output = T> + Sub<Output = T> + Mul<Output = T> + Div<Output = T>
+{
+    type Output = Complex<T>;
+
+    fn div(self, other: Complex<T>) -> Complex<T> {
+        let denom = other.norm_sqr();
+        Complex::new((self.re * other.re + self.im * other.im) / denom,
+                     (self.im * other.re - self.re * other.im) / denom)
+    }
+}
+
+impl<T> Neg for Complex<T>
+    where T: Neg<Output = T>
+{
+    type Output = Complex<T>;
+
+    fn neg(self) -> Complex<T> {
+        Complex::new(-self.re, -self.im)
+    }
+}
+
+impl<T> Zero for Complex<T>
+    where T: Zero
+{
+    fn zero() -> Complex<T> {
+        Complex::new(T::zero(), T::zero())
+    }
+
+    fn is_zero(&self) -> bool {
+        self.re.is_zero() && self.im.is_zero()
+    }
+}
+
+impl<T> One for Complex<T>
+    where T: Zero + One
+{
+    fn one() -> Complex<T> {
+        Complex::new(T::one(), T::zero())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Complex;
+
+    #[test]
+    fn test_arithmetic() {
+        let a = Complex::new(1.0, 2.0);
+        let b = Complex::new(3.0, -1.0);
+        assert_eq!(a + b, Complex::new(4.0, 1.0));
+        assert_eq!(a - b, Complex::new(-2.0, 3.0));
+        assert_eq!(a * b, Complex::new(5.0, 5.0));
+        assert_eq!((a * b) / b, a);
+        assert_eq!(-a, Complex::new(-1.0, -2.0));
+    }
+
+    #[test]
+    fn test_conj() {
+        let a = Complex::new(1, 2);
+        assert_eq!(a.conj(), Complex::new(1, -2));
+        assert_eq!(a * a.conj(), Complex::new(a.norm_sqr(), 0));
+    }
+}
@@ -0,0 +1,94 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::result;
+
+/// Errors produced by fallible sparse matrix operations.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    /// An index lay outside of a matrix of the given shape.
+    OutOfBounds {
+        index: (u64, u64),
+        shape: (u64, u64),
+    },
+    /// The operands of `op` had incompatible shapes.
+    ShapeMismatch {
+        op: &'static str,
+        left: (u64, u64),
+        right: (u64, u64),
+    },
+    /// A matrix that needed to be inverted was singular.
+    Singular,
+    /// Raw storage arrays did not describe a valid matrix.
+    InvalidStructure(String),
+    /// An operation would have stored every element of a matrix of the given
+    /// shape, which has more than `limit` elements.
+    TooDense {
+        shape: (u64, u64),
+        limit: u64,
+    },
+    /// Input could not be parsed. `line` is 1-based, when known.
+    Parse {
+        line: Option<usize>,
+        message: String,
+    },
+    /// Reading or writing failed.
+    Io {
+        kind: io::ErrorKind,
+        message: String,
+    },
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::OutOfBounds { index, shape } => {
+                write!(f,
+                       "Out of bounds index ({row}, {col}) for sparse matrix of \
+                        shape ({nrows}, {ncols})",
+                       row = index.0,
+                       col = index.1,
+                       nrows = shape.0,
+                       ncols = shape.1)
+            }
+            Error::ShapeMismatch { op, left, right } => {
+                write!(f,
+                       "Shape mismatch: cannot {op} operands of shape \
+                        ({lrows}, {lcols}) and ({rrows}, {rcols})",
+                       op = op,
+                       lrows = left.0,
+                       lcols = left.1,
+                       rrows = right.0,
+                       rcols = right.1)
+            }
+            Error::Singular => write!(f, "Matrix is singular"),
+            Error::InvalidStructure(ref message) => write!(f, "{}", message),
+            Error::TooDense { shape, limit } => {
+                write!(f,
+                       "Refusing to densify sparse matrix of shape ({nrows}, {ncols}) \
+                        with more than {limit} elements",
+                       nrows = shape.0,
+                       ncols = shape.1,
+                       limit = limit)
+            }
+            Error::Parse { line: Some(line), ref message } => {
+                write!(f, "Parse error on line {}: {}", line, message)
+            }
+            Error::Parse { line: None, ref message } => write!(f, "Parse error: {}", message),
+            Error::Io { ref message, .. } => write!(f, "I/O error: {}", message),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io {
+            kind: e.kind(),
+            message: e.to_string(),
+        }
+    }
+}
@@ -1,8 +1,15 @@
 #![allow(dead_code)]
 
+pub mod complex;
+pub mod error;
+pub mod rational;
 pub mod sparse;
 pub mod util;
 
+pub use complex::Complex;
+pub use error::{Error, Result};
+pub use rational::Rational;
+
 #[cfg(test)]
 mod tests {
     #[test]
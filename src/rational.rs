@@ -0,0 +1,286 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use sparse::dok::{One, Zero};
+
+/// An exact rational number with `i128` numerator and denominator.
+///
+/// Values are always kept in lowest terms with a positive denominator, so
+/// structurally equal values are numerically equal. Arithmetic panics if an
+/// intermediate result overflows `i128`, just like integer arithmetic in
+/// debug builds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Rational {
+    num: i128,
+    den: i128,
+}
+
+/// Greatest common divisor of the magnitudes of `a` and `b`.
+///
+/// This is computed unsigned, since the magnitude of `i128::MIN` does not
+/// fit in an `i128`.
+fn gcd(a: i128, b: i128) -> u128 {
+    let (mut a, mut b) = (a.unsigned_abs(), b.unsigned_abs());
+    while b != 0 {
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+    a
+}
+
+fn checked(value: Option<i128>) -> i128 {
+    value.expect("Rational arithmetic overflowed i128")
+}
+
+impl Rational {
+    /// Create the rational number `num / den`, reduced to lowest terms.
+    ///
+    /// Panics if `den` is zero, or if the reduced numerator or denominator
+    /// does not fit in an `i128`, as for `Rational::new(i128::MIN, -1)`.
+    pub fn new(num: i128, den: i128) -> Self {
+        if den == 0 {
+            panic!("Rational with zero denominator")
+        }
+        let g = gcd(num, den);
+        let (num_abs, den_abs) = (num.unsigned_abs() / g, den.unsigned_abs() / g);
+        let num = if (num < 0) != (den < 0) {
+            0i128.checked_sub_unsigned(num_abs)
+        } else {
+            i128::try_from(num_abs).ok()
+        };
+        Rational {
+            num: checked(num),
+            den: checked(i128::try_from(den_abs).ok()),
+        }
+    }
+
+    /// Create the rational number equal to the integer `n`.
+    pub fn from_integer(n: i128) -> Self {
+        Rational { num: n, den: 1 }
+    }
+
+    /// Get the numerator, in lowest terms.
+    pub fn numer(&self) -> i128 {
+        self.num
+    }
+
+    /// Get the (positive) denominator, in lowest terms.
+    pub fn denom(&self) -> i128 {
+        self.den
+    }
+
+    /// Whether this number is an integer.
+    pub fn is_integer(&self) -> bool {
+        self.den == 1
+    }
+
+    /// Get the reciprocal of this number.
+    ///
+    /// Panics if this number is zero.
+    pub fn recip(&self) -> Self {
+        Rational::new(self.den, self.num)
+    }
+
+    /// Get the absolute value of this number.
+    pub fn abs(&self) -> Self {
+        Rational {
+            num: checked(self.num.checked_abs()),
+            den: self.den,
+        }
+    }
+
+    /// Convert to the nearest `f64`.
+    pub fn to_f64(&self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+}
+
+impl From<i64> for Rational {
+    fn from(n: i64) -> Rational {
+        Rational::from_integer(n as i128)
+    }
+}
+
+impl From<i128> for Rational {
+    fn from(n: i128) -> Rational {
+        Rational::from_integer(n)
+    }
+}
+
+impl Zero for Rational {
+    fn zero() -> Rational {
+        Rational::from_integer(0)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.num == 0
+    }
+}
+
+impl One for Rational {
+    fn one() -> Rational {
+        Rational::from_integer(1)
+    }
+}
+
+impl Add for Rational {
+    type Output = Rational;
+
+    fn add(self, other: Rational) -> Rational {
+        // Scale both operands to the least common denominator rather than to
+        // the product of denominators to delay overflow. Denominators are
+        // positive, so their gcd fits in an i128.
+        let g = gcd(self.den, other.den) as i128;
+        let left = checked(self.num.checked_mul(other.den / g));
+        let right = checked(other.num.checked_mul(self.den / g));
+        Rational::new(checked(left.checked_add(right)),
+                      checked((self.den / g).checked_mul(other.den)))
+    }
+}
+
+impl Sub for Rational {
+    type Output = Rational;
+
+    fn sub(self, other: Rational) -> Rational {
+        self + -other
+    }
+}
+
+impl Mul for Rational {
+    type Output = Rational;
+
+    fn mul(self, other: Rational) -> Rational {
+        // Cancel common factors before multiplying to delay overflow. Each
+        // gcd divides a positive denominator, so it fits in an i128.
+        let g1 = gcd(self.num, other.den).max(1) as i128;
+        let g2 = gcd(other.num, self.den).max(1) as i128;
+        Rational::new(checked((self.num / g1).checked_mul(other.num / g2)),
+                      checked((self.den / g2).checked_mul(other.den / g1)))
+    }
+}
+
+impl Div for Rational {
+    type Output = Rational;
+
+    /// Divide `self` by `other`.
+    ///
+    /// Panics if `other` is zero.
+    fn div(self, other: Rational) -> Rational {
+        Mul::mul(self, other.recip())
+    }
+}
+
+impl Neg for Rational {
+    type Output = Rational;
+
+    fn neg(self) -> Rational {
+        Rational {
+            num: checked(self.num.checked_neg()),
+            den: self.den,
+        }
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Rational) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, other: &Rational) -> Ordering {
+        // Denominators are positive, so cross-multiplying preserves order.
+        let g = gcd(self.den, other.den) as i128;
+        let left = checked(self.num.checked_mul(other.den / g));
+        let right = checked(other.num.checked_mul(self.den / g));
+        left.cmp(&right)
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.den == 1 {
+            write!(f, "{}", self.num)
+        } else {
+            write!(f, "{}/{}", self.num, self.den)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rational;
+    use sparse::dok::Zero;
+
+    #[test]
+    fn test_normalization() {
+        let r = Rational::new(6, -4);
+        assert_eq!((r.numer(), r.denom()), (-3, 2));
+        assert_eq!(Rational::new(0, -7), Rational::zero());
+        assert_eq!(Rational::new(10, 5), Rational::from(2i64));
+        assert!(Rational::new(10, 5).is_integer());
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let third = Rational::new(1, 3);
+        let half = Rational::new(1, 2);
+        assert_eq!(third + half, Rational::new(5, 6));
+        assert_eq!(third - half, Rational::new(-1, 6));
+        assert_eq!(third * half, Rational::new(1, 6));
+        assert_eq!(third / half, Rational::new(2, 3));
+        assert_eq!(-third, Rational::new(-1, 3));
+        assert_eq!(third.recip(), Rational::from(3i64));
+        assert_eq!((-third).abs(), third);
+        assert!(third < half);
+        assert!(-half < third);
+    }
+
+    #[test]
+    fn test_exact_sum() {
+        let tenth = Rational::new(1, 10);
+        let mut total = Rational::zero();
+        for _ in 0..10 {
+            total = total + tenth;
+        }
+        assert_eq!(total, Rational::from(1i64));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Rational::new(3, -6).to_string(), "-1/2");
+        assert_eq!(Rational::from(4i64).to_string(), "4");
+    }
+
+    #[test]
+    #[should_panic(expected = "zero denominator")]
+    fn test_zero_denominator() {
+        Rational::new(1, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "overflow")]
+    fn test_overflow() {
+        let big = Rational::from_integer(i128::MAX);
+        let _ = big + big;
+    }
+
+    #[test]
+    fn test_min() {
+        assert_eq!(Rational::new(i128::MIN, 2).numer(), i128::MIN / 2);
+        assert_eq!(Rational::new(i128::MIN, i128::MIN), Rational::from(1i64));
+        assert_eq!(Rational::new(i128::MIN, 1).numer(), i128::MIN);
+        assert_eq!(Rational::new(4, i128::MIN), Rational::new(-1, 1 << 125));
+        assert_eq!(Rational::from_integer(i128::MIN) * Rational::new(1, 2),
+                   Rational::from_integer(i128::MIN / 2));
+    }
+
+    #[test]
+    #[should_panic(expected = "overflow")]
+    fn test_min_overflow() {
+        Rational::new(i128::MIN, -1);
+    }
+}
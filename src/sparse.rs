@@ -1,125 +1,26 @@
-mod dok {
-    use std::collections::HashMap;
-    use std::ops::Index;
-    // use std::ops::{Add, Sub, Mul, Index, IndexMut};
-
-    type Coords = (u64, u64);
-    type CoordMap<T> = HashMap<Coords, T>;
-
-    const ZERO_F64: f64 = 0.0;
-    const ONE_F64: f64 = 1.0;
-
-    static ZERO_F64_REF: &'static f64 = &ZERO_F64;
-    static ONE_F64_REF: &'static f64 = &ONE_F64;
-
-    pub trait Zero {
-        fn zero() -> &'static Self;
-    }
-
-    impl Zero for f64 {
-        fn zero() -> &'static f64 {
-            ZERO_F64_REF
-        }
-    }
-
-    pub trait One {
-        fn one() -> &'static Self;
-    }
-
-    impl One for f64 {
-        fn one() -> &'static f64 {
-            &ONE_F64_REF
-        }
-    }
-
-    pub trait MatrixElem: Zero + One + Copy {}
-    impl<T: Zero + One + Copy> MatrixElem for T {}
-
-    /// A Dictionary-of-Keys Sparse Matrix
-    pub struct DOKMatrix<T: 'static>
-        where T: MatrixElem
-    {
-        pub nrows: u64,
-        pub ncols: u64,
-        elems: CoordMap<T>,
-    }
-
-    impl<T> DOKMatrix<T>
-        where T: MatrixElem + Copy
-    {
-        /// Create a DOKMatrix.
-        ///
-        /// # Arguments
-        ///
-        /// * `nrows` - Number of rows in the new matrix.
-        /// * `ncols` - Number of columns in the returned matrix.
-        /// * `elems` - Map from indices of non-zero elements to values.
-        pub fn new(nrows: u64, ncols: u64, elems: CoordMap<T>) -> Self {
-            DOKMatrix {
-                nrows: nrows,
-                ncols: ncols,
-                elems: elems,
-            }
-        }
-
-        /// Create a DOKMatrix with all zero elements.
-        ///
-        /// # Arguments
-        ///
-        /// * `nrows` - Number of rows in the new matrix.
-        /// * `ncols` - Number of columns in the returned matrix.
-        pub fn zeros(nrows: u64, ncols: u64) -> Self {
-            Self::new(nrows, ncols, CoordMap::<T>::new())
-        }
-
-        pub fn identity(size: u64) -> Self {
-            let mut map = HashMap::<Coords, T>::new();
-            for i in 0..size {
-                map.insert((i, i), *T::one());
-            }
-            Self::new(size, size, map)
-        }
-
-        pub fn transposed(&self) -> Self {
-            let mut map = HashMap::<Coords, T>::new();
-            for (&(i, j), v) in (&self.elems).into_iter() {
-                map.insert((j, i), *v);
-            }
-            return Self::new(self.ncols, self.nrows, map);
-        }
-    }
-
-    impl<T> Index<(u64, u64)> for DOKMatrix<T>
-        where T: MatrixElem
-    {
-        type Output = T;
-
-        /// Get the element at coordinate (row, col).
-        fn index<'a>(&'a self, (row, col): (u64, u64)) -> &'a T {
-            if row >= self.nrows || col >= self.ncols {
-                panic!("Out of bounds index ({row}, {col}) for sparse matrix \
-                        of shape ({nrows}, {ncols})",
-                       row = row,
-                       col = col,
-                       nrows = self.nrows,
-                       ncols = self.ncols)
-            }
-            let elem = self.elems.get(&(row, col));
-            match elem {
-                None => T::zero(),
-                Some(elem) => elem,
-            }
-        }
-    }
-}
-
+pub mod dok;
+pub mod csr;
+pub mod csc;
+pub mod coo;
+pub mod semiring;
+pub mod masked;
+pub mod io;
+mod compressed;
+mod display;
+mod norm;
 
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
+    use complex::Complex;
+    use error::Error;
+    use rational::Rational;
     use util::itertools::cartesian_product;
 
-    use super::dok::DOKMatrix;
+    use std::ops::{Add, Mul};
+
+    use super::dok::{Axis, DOKMatrix, One, Order, Zero};
+    use super::semiring::{MaxMin, MaxPlus, MinPlus, OrAnd};
     type FloatMatrix = DOKMatrix<f64>;
 
     #[test]
@@ -184,4 +85,892 @@ mod tests {
         assert_eq!(transposed[(3, 0)], 3.0);
         assert_eq!(transposed[(2, 2)], -4.0);
     }
+
+    fn make_matrix(nrows: u64, ncols: u64, entries: &[((u64, u64), f64)]) -> FloatMatrix {
+        let mut elems = HashMap::new();
+        for &(k, v) in entries {
+            elems.insert(k, v);
+        }
+        FloatMatrix::new(nrows, ncols, elems)
+    }
+
+    #[test]
+    fn test_add() {
+        let a = make_matrix(3, 4, &[((0, 0), 1.0), ((1, 2), 2.0), ((2, 3), 3.0)]);
+        let b = make_matrix(3, 4, &[((0, 0), 4.0), ((1, 2), -2.0), ((2, 0), 5.0)]);
+
+        let expected = [((0, 0), 5.0), ((2, 3), 3.0), ((2, 0), 5.0)];
+        for result in [&a + &b, a.clone() + &b, a.clone() + b.clone()] {
+            assert_eq!((result.nrows, result.ncols), (3, 4));
+            for &((i, j), v) in expected.iter() {
+                assert_eq!(result[(i, j)], v);
+            }
+            assert_eq!(result[(1, 2)], 0.0);
+        }
+
+        let mut c = a.clone();
+        c += &b;
+        assert_eq!(c[(0, 0)], 5.0);
+        assert_eq!(c[(1, 2)], 0.0);
+        c += b;
+        assert_eq!(c[(0, 0)], 9.0);
+        assert_eq!(c[(1, 2)], -2.0);
+    }
+
+    #[test]
+    fn test_sub() {
+        let a = make_matrix(3, 4, &[((0, 0), 1.0), ((1, 2), 2.0), ((2, 3), 3.0)]);
+        let b = make_matrix(3, 4, &[((0, 0), 4.0), ((1, 2), 2.0), ((2, 0), 5.0)]);
+
+        let expected = [((0, 0), -3.0), ((1, 2), 0.0), ((2, 3), 3.0), ((2, 0), -5.0)];
+        for result in [&a - &b, a.clone() - &b, a.clone() - b.clone()] {
+            for &((i, j), v) in expected.iter() {
+                assert_eq!(result[(i, j)], v);
+            }
+        }
+
+        let mut c = a.clone();
+        c -= &a;
+        for (i, j) in cartesian_product(0..3, 0..4) {
+            assert_eq!(c[(i, j)], 0.0);
+        }
+    }
+
+    #[test]
+    fn test_neg() {
+        let a = make_matrix(2, 2, &[((0, 1), 1.5), ((1, 0), -2.0)]);
+        for result in [-&a, -a.clone()] {
+            assert_eq!(result[(0, 0)], 0.0);
+            assert_eq!(result[(0, 1)], -1.5);
+            assert_eq!(result[(1, 0)], 2.0);
+            assert_eq!(result[(1, 1)], 0.0);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Shape mismatch")]
+    fn test_add_shape_mismatch() {
+        let _ = FloatMatrix::zeros(2, 3) + FloatMatrix::zeros(3, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Shape mismatch")]
+    fn test_sub_shape_mismatch() {
+        let _ = FloatMatrix::zeros(2, 3) - FloatMatrix::zeros(2, 4);
+    }
+
+    #[test]
+    fn test_mul() {
+        // [[1, 0, 2],      [[0, 3],
+        //  [0, 0, 0],  *    [4, 0],
+        //  [0, 5, 0]]       [1, 0]]
+        let a = make_matrix(3, 3, &[((0, 0), 1.0), ((0, 2), 2.0), ((2, 1), 5.0)]);
+        let b = make_matrix(3, 2, &[((0, 1), 3.0), ((1, 0), 4.0), ((2, 0), 1.0)]);
+
+        let expected = [[2.0, 3.0], [0.0, 0.0], [20.0, 0.0]];
+        for result in [&a * &b, a.clone() * &b, a.clone() * b.clone()] {
+            assert_eq!((result.nrows, result.ncols), (3, 2));
+            for (i, j) in cartesian_product(0..3, 0..2) {
+                assert_eq!(result[(i, j)], expected[i as usize][j as usize]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_mul_identity() {
+        let a = make_matrix(3, 4, &[((0, 1), 1.0), ((1, 3), -2.0), ((2, 2), 7.0)]);
+        let left = &FloatMatrix::identity(3) * &a;
+        let right = &a * &FloatMatrix::identity(4);
+        for (i, j) in cartesian_product(0..3, 0..4) {
+            assert_eq!(left[(i, j)], a[(i, j)]);
+            assert_eq!(right[(i, j)], a[(i, j)]);
+        }
+    }
+
+    #[test]
+    fn test_mul_cancellation() {
+        // The single output entry is 1 * 1 + 1 * -1 = 0 and must not be stored.
+        let a = make_matrix(1, 2, &[((0, 0), 1.0), ((0, 1), 1.0)]);
+        let b = make_matrix(2, 1, &[((0, 0), 1.0), ((1, 0), -1.0)]);
+        let product = &a * &b;
+        assert_eq!(product[(0, 0)], 0.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Shape mismatch")]
+    fn test_mul_shape_mismatch() {
+        let _ = FloatMatrix::zeros(2, 3) * FloatMatrix::zeros(2, 3);
+    }
+
+    #[test]
+    fn test_matvec() {
+        // [[1, 0, 2],
+        //  [0, 0, 0],
+        //  [0, 5, 0],
+        //  [3, 0, 0]]
+        let a = make_matrix(4, 3, &[((0, 0), 1.0), ((0, 2), 2.0), ((2, 1), 5.0), ((3, 0), 3.0)]);
+        assert_eq!(a.matvec(&[1.0, 2.0, 3.0]), vec![7.0, 0.0, 10.0, 3.0]);
+        assert_eq!(a.rmatvec(&[1.0, 2.0, 3.0, 4.0]), vec![13.0, 15.0, 2.0]);
+        assert_eq!(a.rmatvec(&[1.0, 2.0, 3.0, 4.0]),
+                   a.transposed().matvec(&[1.0, 2.0, 3.0, 4.0]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Shape mismatch")]
+    fn test_matvec_shape_mismatch() {
+        FloatMatrix::zeros(2, 3).matvec(&[1.0, 2.0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Shape mismatch")]
+    fn test_rmatvec_shape_mismatch() {
+        FloatMatrix::zeros(2, 3).rmatvec(&[1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn test_matmat() {
+        let a = make_matrix(3, 3, &[((0, 0), 1.0), ((0, 2), 2.0), ((2, 1), 5.0)]);
+        // [[0, 3],
+        //  [4, 0],
+        //  [1, 0]]
+        let b = [0.0, 3.0, 4.0, 0.0, 1.0, 0.0];
+        assert_eq!(a.matmat(&b, 2), vec![2.0, 3.0, 0.0, 0.0, 20.0, 0.0]);
+        assert_eq!(a.matmat(&[], 0), Vec::<f64>::new());
+    }
+
+    #[test]
+    #[should_panic(expected = "Shape mismatch")]
+    fn test_matmat_shape_mismatch() {
+        FloatMatrix::zeros(2, 3).matmat(&[1.0; 4], 2);
+    }
+
+    #[test]
+    fn test_set_and_remove() {
+        let mut m = FloatMatrix::zeros(3, 3);
+        m.set(0, 1, 2.0);
+        m.set(2, 2, -1.0);
+        assert_eq!(m[(0, 1)], 2.0);
+        assert_eq!(m[(2, 2)], -1.0);
+
+        m.set(0, 1, 0.0);
+        assert_eq!(m[(0, 1)], 0.0);
+        assert!(!m.entry((0, 1)).is_stored());
+
+        assert_eq!(m.remove(2, 2), Some(-1.0));
+        assert_eq!(m.remove(2, 2), None);
+        assert_eq!(m[(2, 2)], 0.0);
+    }
+
+    #[test]
+    fn test_write_zero() {
+        // Writing zero never stores an element, whatever the write path.
+        let mut m = FloatMatrix::zeros(2, 2);
+        m.set(1, 0, 3.0);
+        m.entry((1, 0)).update(|v| v + 1.5);
+        assert_eq!(m[(1, 0)], 4.5);
+
+        m.set(0, 0, 0.0);
+        m.entry((0, 1)).insert(0.0);
+        m.entry((1, 1)).update(|v| v * 2.0);
+        *m.get_mut(1, 1).unwrap() *= 2.0;
+        assert!(!m.entry((0, 0)).is_stored());
+        assert!(!m.entry((0, 1)).is_stored());
+        assert!(!m.entry((1, 1)).is_stored());
+        assert!(m.entry((1, 0)).is_stored());
+        m.apply(|v| v - 4.5);
+        assert_eq!(m.nnz(), 0);
+    }
+
+    #[test]
+    fn test_get_mut() {
+        let mut m = FloatMatrix::zeros(2, 2);
+        *m.get_mut(1, 0).unwrap() = 3.0;
+        *m.get_mut(1, 0).unwrap() += 1.5;
+        assert_eq!(m[(1, 0)], 4.5);
+        assert_eq!(*m.get_mut(0, 0).unwrap(), 0.0);
+        assert!(!m.entry((0, 0)).is_stored());
+
+        *m.get_mut(1, 0).unwrap() -= 4.5;
+        assert!(!m.entry((1, 0)).is_stored());
+        assert!(m.get_mut(2, 0).is_none());
+        assert!(m.get_mut(0, 2).is_none());
+    }
+
+    #[test]
+    fn test_entry() {
+        let mut m = FloatMatrix::zeros(2, 2);
+        assert_eq!(m.entry((0, 0)).key(), (0, 0));
+        assert_eq!(*m.entry((0, 0)).get(), 0.0);
+
+        assert_eq!(m.entry((0, 0)).update(|v| v + 2.0), 2.0);
+        assert_eq!(m.entry((0, 0)).update(|v| v + 2.0), 4.0);
+        assert_eq!(m.entry((0, 0)).insert(1.0), 4.0);
+        assert_eq!(m[(0, 0)], 1.0);
+
+        // and_modify only applies to stored values.
+        m.entry((1, 1)).and_modify(|v| *v = 5.0);
+        assert!(!m.entry((1, 1)).is_stored());
+        m.entry((0, 0)).and_modify(|v| *v *= 3.0);
+        assert_eq!(m[(0, 0)], 3.0);
+
+        // Writing zero through any entry method removes the element.
+        m.entry((0, 0)).and_modify(|v| *v = 0.0);
+        assert!(!m.entry((0, 0)).is_stored());
+        m.entry((1, 0)).insert(7.0);
+        m.entry((1, 0)).update(|v| v - 7.0);
+        assert!(!m.entry((1, 0)).is_stored());
+        m.entry((0, 1)).insert(7.0);
+        assert_eq!(m.entry((0, 1)).remove(), 7.0);
+        assert!(!m.entry((0, 1)).is_stored());
+    }
+
+    #[test]
+    #[should_panic(expected = "Out of bounds")]
+    fn test_set_out_of_bounds() {
+        FloatMatrix::zeros(2, 2).set(2, 0, 1.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Out of bounds")]
+    fn test_entry_out_of_bounds() {
+        FloatMatrix::zeros(2, 2).entry((0, 2));
+    }
+
+    #[test]
+    fn test_try_new() {
+        let mut elems = HashMap::new();
+        elems.insert((1, 1), 1.0);
+        assert!(FloatMatrix::try_new(2, 2, elems.clone()).is_ok());
+        assert_eq!(FloatMatrix::try_new(1, 2, elems).err(),
+                   Some(Error::OutOfBounds {
+                       index: (1, 1),
+                       shape: (1, 2),
+                   }));
+    }
+
+    #[test]
+    #[should_panic(expected = "Out of bounds")]
+    fn test_new_out_of_bounds() {
+        let mut elems = HashMap::new();
+        elems.insert((0, 3), 1.0);
+        FloatMatrix::new(3, 3, elems);
+    }
+
+    #[test]
+    fn test_get() {
+        let m = make_matrix(2, 3, &[((1, 2), 4.0)]);
+        assert_eq!(m.get(1, 2), Some(&4.0));
+        assert_eq!(m.get(0, 0), Some(&0.0));
+        assert_eq!(m.get(2, 0), None);
+        assert_eq!(m.get(0, 3), None);
+    }
+
+    #[test]
+    fn test_try_set() {
+        let mut m = FloatMatrix::zeros(2, 2);
+        assert_eq!(m.try_set(1, 1, 3.0), Ok(()));
+        assert_eq!(m[(1, 1)], 3.0);
+        assert_eq!(m.try_set(2, 1, 3.0),
+                   Err(Error::OutOfBounds {
+                       index: (2, 1),
+                       shape: (2, 2),
+                   }));
+        assert!(m.try_entry((1, 2)).is_err());
+
+        assert_eq!(m.try_remove(1, 1), Ok(Some(3.0)));
+        assert_eq!(m.try_remove(1, 1), Ok(None));
+        assert_eq!(m.try_remove(1, 2),
+                   Err(Error::OutOfBounds {
+                       index: (1, 2),
+                       shape: (2, 2),
+                   }));
+    }
+
+    #[test]
+    fn test_try_ops() {
+        let a = make_matrix(2, 3, &[((0, 0), 1.0), ((1, 2), 2.0)]);
+        let b = make_matrix(3, 2, &[((2, 1), 3.0)]);
+
+        assert_eq!(a.try_add(&b).err(),
+                   Some(Error::ShapeMismatch {
+                       op: "add",
+                       left: (2, 3),
+                       right: (3, 2),
+                   }));
+        assert!(a.try_sub(&b).is_err());
+        assert!(a.try_add(&a).is_ok());
+        assert_eq!(a.try_sub(&a).unwrap()[(1, 2)], 0.0);
+
+        let product = a.try_mul(&b).unwrap();
+        assert_eq!(product[(1, 1)], 6.0);
+        assert_eq!(a.try_mul(&a).err(),
+                   Some(Error::ShapeMismatch {
+                       op: "multiply",
+                       left: (2, 3),
+                       right: (2, 3),
+                   }));
+
+        assert_eq!(a.try_matvec(&[1.0, 1.0, 1.0]), Ok(vec![1.0, 2.0]));
+        assert!(a.try_matvec(&[1.0, 1.0]).is_err());
+        assert_eq!(a.try_rmatvec(&[1.0, 1.0]), Ok(vec![1.0, 0.0, 2.0]));
+        assert!(a.try_rmatvec(&[1.0, 1.0, 1.0]).is_err());
+        assert!(a.try_matmat(&[1.0; 6], 2).is_ok());
+        assert!(a.try_matmat(&[1.0; 4], 2).is_err());
+        assert!(a.try_matmat(&[1.0; 5], 2).is_err());
+    }
+
+    #[test]
+    fn test_nnz_and_density() {
+        let m = make_matrix(4, 5, &[((0, 0), 1.0), ((3, 4), 2.0)]);
+        assert_eq!(m.nnz(), 2);
+        assert_eq!(m.density(), 0.1);
+        assert_eq!(FloatMatrix::zeros(0, 5).density(), 0.0);
+    }
+
+    #[test]
+    fn test_iter() {
+        let entries = [((0, 1), 1.0), ((2, 0), 2.0), ((1, 1), 3.0)];
+        let mut m = make_matrix(3, 2, &entries);
+
+        let mut seen: Vec<((u64, u64), f64)> = m.iter().map(|(k, &v)| (k, v)).collect();
+        seen.sort_by_key(|&(k, _)| k);
+        assert_eq!(seen, vec![((0, 1), 1.0), ((1, 1), 3.0), ((2, 0), 2.0)]);
+        assert_eq!(m.iter().len(), 3);
+        assert_eq!((&m).into_iter().count(), 3);
+
+        for (_, v) in &mut m.iter_mut() {
+            *v *= 2.0;
+        }
+        let mut elems = m.iter_mut();
+        assert_eq!((&mut elems).into_iter().len(), 3);
+        for (_, v) in &mut elems {
+            *v += 1.0;
+        }
+        drop(elems);
+        assert_eq!(m[(0, 1)], 3.0);
+        assert_eq!(m[(2, 0)], 5.0);
+        assert_eq!(m[(1, 1)], 7.0);
+
+        let mut owned: Vec<((u64, u64), f64)> = m.clone().into_iter().collect();
+        owned.sort_by_key(|&(k, _)| k);
+        assert_eq!(owned, vec![((0, 1), 3.0), ((1, 1), 7.0), ((2, 0), 5.0)]);
+
+        // Values set to zero through `iter_mut` are removed.
+        for ((i, _), v) in &mut m.iter_mut() {
+            if i == 1 {
+                *v = 0.0;
+            }
+        }
+        assert_eq!(m.nnz(), 2);
+        assert!(!m.entry((1, 1)).is_stored());
+    }
+
+    #[test]
+    fn test_iter_sorted() {
+        let m = make_matrix(3, 3, &[((2, 0), 1.0), ((0, 2), 2.0), ((0, 1), 3.0), ((1, 0), 4.0)]);
+
+        let rows: Vec<(u64, u64)> = m.iter_sorted(Order::RowMajor).map(|(k, _)| k).collect();
+        assert_eq!(rows, vec![(0, 1), (0, 2), (1, 0), (2, 0)]);
+
+        let cols: Vec<(u64, u64)> = m.iter_sorted(Order::ColMajor).map(|(k, _)| k).collect();
+        assert_eq!(cols, vec![(1, 0), (2, 0), (0, 1), (0, 2)]);
+    }
+
+    #[test]
+    fn test_row_and_col_iter() {
+        let m = make_matrix(3, 3, &[((2, 0), 1.0), ((0, 2), 2.0), ((0, 1), 3.0), ((1, 0), 4.0)]);
+        assert_eq!(m.row_iter(0).collect::<Vec<_>>(), vec![(1, &3.0), (2, &2.0)]);
+        assert_eq!(m.row_iter(1).collect::<Vec<_>>(), vec![(0, &4.0)]);
+        assert_eq!(m.col_iter(0).collect::<Vec<_>>(), vec![(1, &4.0), (2, &1.0)]);
+        assert_eq!(m.col_iter(2).collect::<Vec<_>>(), vec![(0, &2.0)]);
+
+        // Only the iterated dimension is checked.
+        assert_eq!(FloatMatrix::zeros(2, 0).row_iter(1).count(), 0);
+        assert_eq!(FloatMatrix::zeros(0, 2).col_iter(1).count(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Out of bounds")]
+    fn test_row_iter_out_of_bounds() {
+        FloatMatrix::zeros(2, 3).row_iter(2);
+    }
+
+    #[test]
+    fn test_integer_elements() {
+        let mut a = DOKMatrix::<i64>::zeros(2, 2);
+        a.set(0, 0, 2);
+        a.set(1, 0, -3);
+        let b = DOKMatrix::<i64>::identity(2);
+        let product = &a * &b;
+        assert_eq!(product[(0, 0)], 2);
+        assert_eq!(product[(1, 0)], -3);
+        assert_eq!(product[(1, 1)], 0);
+        assert_eq!((&a - &a).nnz(), 0);
+
+        let mut counts = DOKMatrix::<u8>::zeros(3, 3);
+        counts.entry((1, 2)).update(|v| v + 1);
+        counts.entry((1, 2)).update(|v| v + 1);
+        assert_eq!(counts[(1, 2)], 2);
+        assert_eq!(counts.matvec(&[1, 1, 1]), vec![0, 2, 0]);
+    }
+
+    #[test]
+    fn test_f32_elements() {
+        let m = DOKMatrix::<f32>::identity(3);
+        assert_eq!(m.matvec(&[1.5, 2.5, 3.5]), vec![1.5, 2.5, 3.5]);
+    }
+
+    #[test]
+    fn test_complex_elements() {
+        let i = Complex::new(0.0, 1.0);
+        let mut m = DOKMatrix::<Complex<f64>>::zeros(2, 2);
+        m.set(0, 1, i);
+        m.set(1, 0, i.conj());
+
+        let squared = &m * &m;
+        assert_eq!(squared[(0, 0)], Complex::new(1.0, 0.0));
+        assert_eq!(squared[(1, 1)], Complex::new(1.0, 0.0));
+        assert_eq!(squared[(0, 1)], Complex::new(0.0, 0.0));
+        assert_eq!(squared.nnz(), 2);
+    }
+
+    /// A heap-allocated element type, which can't be Copy or live in a
+    /// `static`.
+    #[derive(Clone, Debug, PartialEq)]
+    struct Boxed(Box<i64>);
+
+    impl Zero for Boxed {
+        fn zero() -> Boxed {
+            Boxed(Box::new(0))
+        }
+
+        fn is_zero(&self) -> bool {
+            *self.0 == 0
+        }
+    }
+
+    impl One for Boxed {
+        fn one() -> Boxed {
+            Boxed(Box::new(1))
+        }
+    }
+
+    impl Add for Boxed {
+        type Output = Boxed;
+
+        fn add(self, other: Boxed) -> Boxed {
+            Boxed(Box::new(*self.0 + *other.0))
+        }
+    }
+
+    impl Mul for Boxed {
+        type Output = Boxed;
+
+        fn mul(self, other: Boxed) -> Boxed {
+            Boxed(Box::new(*self.0 * *other.0))
+        }
+    }
+
+    #[test]
+    fn test_non_copy_elements() {
+        let mut m = DOKMatrix::<Boxed>::identity(2);
+        m.set(0, 1, Boxed(Box::new(3)));
+        assert_eq!(m[(1, 0)], Boxed::zero());
+        assert_eq!(m.get(0, 1), Some(&Boxed(Box::new(3))));
+
+        let squared = &m * &m;
+        assert_eq!(squared[(0, 1)], Boxed(Box::new(6)));
+        assert_eq!(squared[(1, 1)], Boxed::one());
+
+        let sum = &m + &m;
+        assert_eq!(sum[(0, 0)], Boxed(Box::new(2)));
+        assert_eq!(m.matvec(&[Boxed::one(), Boxed::one()]),
+                   vec![Boxed(Box::new(4)), Boxed::one()]);
+
+        m.set(0, 1, Boxed::zero());
+        assert_eq!(m.nnz(), 2);
+    }
+
+    #[test]
+    fn test_rational_elements() {
+        // The 2x2 Hilbert matrix and its exact inverse.
+        let r = |n, d| Rational::new(n, d);
+        let mut h = DOKMatrix::<Rational>::zeros(2, 2);
+        h.set(0, 0, r(1, 1));
+        h.set(0, 1, r(1, 2));
+        h.set(1, 0, r(1, 2));
+        h.set(1, 1, r(1, 3));
+        let mut inv = DOKMatrix::<Rational>::zeros(2, 2);
+        inv.set(0, 0, r(4, 1));
+        inv.set(0, 1, r(-6, 1));
+        inv.set(1, 0, r(-6, 1));
+        inv.set(1, 1, r(12, 1));
+
+        let product = &h * &inv;
+        assert_eq!(product.nnz(), 2);
+        for (i, j) in cartesian_product(0..2, 0..2) {
+            let expected = if i == j { r(1, 1) } else { r(0, 1) };
+            assert_eq!(product[(i, j)], expected);
+        }
+
+        // Eliminate the (1, 0) entry of h exactly.
+        let factor = h[(1, 0)] / h[(0, 0)];
+        let pivot = h[(1, 1)] - factor * h[(0, 1)];
+        assert_eq!(pivot, r(1, 12));
+    }
+
+    fn example_graph() -> FloatMatrix {
+        // Weighted edges 0->1, 0->2, 1->2, 1->3 and 2->3.
+        make_matrix(4,
+                    4,
+                    &[((0, 1), 1.0), ((0, 2), 5.0), ((1, 2), 2.0), ((1, 3), 4.0), ((2, 3), 1.0)])
+    }
+
+    #[test]
+    fn test_min_plus_shortest_paths() {
+        let a = example_graph();
+        let two_hops = a.mxm(&a, MinPlus);
+        assert_eq!(two_hops.nnz(), 3);
+        assert_eq!(two_hops[(0, 2)], 3.0);
+        assert_eq!(two_hops[(0, 3)], 5.0);
+        assert_eq!(two_hops[(1, 3)], 3.0);
+
+        let three_hops = two_hops.mxm(&a, MinPlus);
+        assert_eq!(three_hops.nnz(), 1);
+        assert_eq!(three_hops[(0, 3)], 4.0);
+
+        // Relax distances to node 3 along one edge.
+        let inf = f64::INFINITY;
+        assert_eq!(a.mxv(&[inf, inf, inf, 0.0], MinPlus), vec![inf, 4.0, 1.0, inf]);
+    }
+
+    #[test]
+    fn test_max_plus_longest_paths() {
+        let a = example_graph();
+        let two_hops = a.mxm(&a, MaxPlus);
+        assert_eq!(two_hops[(0, 3)], 6.0);
+        assert_eq!(two_hops[(0, 2)], 3.0);
+    }
+
+    #[test]
+    fn test_or_and_reachability() {
+        let mut a = DOKMatrix::<bool>::zeros(4, 4);
+        for &(i, j) in &[(0, 1), (0, 2), (1, 2), (1, 3), (2, 3)] {
+            a.set(i, j, true);
+        }
+        let two_hops = a.mxm(&a, OrAnd);
+        let mut reached: Vec<_> = two_hops.iter().map(|(coords, _)| coords).collect();
+        reached.sort();
+        assert_eq!(reached, vec![(0, 2), (0, 3), (1, 3)]);
+
+        // One step of breadth-first search from node 0.
+        let frontier = [true, false, false, false];
+        assert_eq!(a.vxm(&frontier, OrAnd), vec![false, true, true, false]);
+    }
+
+    #[test]
+    fn test_max_min_bottleneck_paths() {
+        let mut a = DOKMatrix::<u32>::zeros(3, 3);
+        a.set(0, 1, 5);
+        a.set(1, 2, 3);
+        a.set(0, 2, 2);
+        let two_hops = a.mxm(&a, MaxMin);
+        assert_eq!(two_hops.nnz(), 1);
+        assert_eq!(two_hops[(0, 2)], 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "Shape mismatch")]
+    fn test_mxm_shape_mismatch() {
+        make_matrix(2, 3, &[]).mxm(&make_matrix(2, 3, &[]), MinPlus);
+    }
+
+    fn sorted_entries(m: &FloatMatrix) -> Vec<((u64, u64), f64)> {
+        m.iter_sorted(Order::RowMajor).map(|(coords, &v)| (coords, v)).collect()
+    }
+
+    #[test]
+    fn test_hadamard_and_ewise_add() {
+        let a = make_matrix(2, 3, &[((0, 0), 2.0), ((0, 2), 3.0), ((1, 1), 4.0)]);
+        let b = make_matrix(2, 3, &[((0, 0), 5.0), ((1, 1), 0.5), ((1, 2), 1.0)]);
+
+        assert_eq!(sorted_entries(&a.hadamard(&b)), vec![((0, 0), 10.0), ((1, 1), 2.0)]);
+        assert_eq!(sorted_entries(&a.ewise_add(&b)),
+                   vec![((0, 0), 7.0), ((0, 2), 3.0), ((1, 1), 4.5), ((1, 2), 1.0)]);
+        assert_eq!(sorted_entries(&a.ewise_div(&b)), vec![((0, 0), 0.4), ((1, 1), 8.0)]);
+    }
+
+    #[test]
+    fn test_ewise_min_max() {
+        let a = make_matrix(2, 2, &[((0, 0), 2.0), ((0, 1), -3.0), ((1, 0), 1.0)]);
+        let b = make_matrix(2, 2, &[((0, 0), 5.0), ((1, 1), -1.0)]);
+
+        assert_eq!(sorted_entries(&a.ewise_min(&b)),
+                   vec![((0, 0), 2.0), ((0, 1), -3.0), ((1, 1), -1.0)]);
+        assert_eq!(sorted_entries(&a.ewise_max(&b)), vec![((0, 0), 5.0), ((1, 0), 1.0)]);
+    }
+
+    #[test]
+    fn test_ewise_with() {
+        let a = make_matrix(2, 2, &[((0, 0), 2.0), ((0, 1), 3.0)]);
+        let b = make_matrix(2, 2, &[((0, 0), 2.0), ((1, 1), 4.0)]);
+
+        // Absent elements are passed to the closure as zero, and results that
+        // cancel are dropped.
+        let difference = a.ewise_union_with(&b, |x, y| x - y);
+        assert_eq!(sorted_entries(&difference), vec![((0, 1), 3.0), ((1, 1), -4.0)]);
+
+        let mean = a.ewise_intersect_with(&b, |x, y| (x + y) / 2.0);
+        assert_eq!(sorted_entries(&mean), vec![((0, 0), 2.0)]);
+    }
+
+    #[test]
+    fn test_ewise_shape_mismatch() {
+        let a = make_matrix(2, 2, &[]);
+        let b = make_matrix(2, 3, &[]);
+        let expected = Err(Error::ShapeMismatch {
+            op: "combine",
+            left: (2, 2),
+            right: (2, 3),
+        });
+        assert_eq!(a.try_hadamard(&b).map(|m| m.nnz()), expected);
+        assert_eq!(a.try_ewise_add(&b).map(|m| m.nnz()), expected);
+        assert_eq!(a.try_ewise_max(&b).map(|m| m.nnz()), expected);
+    }
+
+    #[test]
+    fn test_scalar_mul_div() {
+        let a = make_matrix(2, 2, &[((0, 0), 1.0), ((1, 0), -4.0)]);
+        assert_eq!(sorted_entries(&(&a * 2.0)), vec![((0, 0), 2.0), ((1, 0), -8.0)]);
+        assert_eq!(sorted_entries(&(&a / 4.0)), vec![((0, 0), 0.25), ((1, 0), -1.0)]);
+        assert_eq!((a.clone() * 0.0).nnz(), 0);
+
+        let mut b = a.clone();
+        b *= 3.0;
+        b /= 2.0;
+        assert_eq!(sorted_entries(&b), vec![((0, 0), 1.5), ((1, 0), -6.0)]);
+
+        // Integer division can round elements down to zero.
+        let mut c = DOKMatrix::<i32>::zeros(1, 2);
+        c.set(0, 0, 1);
+        c.set(0, 1, 7);
+        c /= 2;
+        assert_eq!(c.nnz(), 1);
+        assert_eq!(c[(0, 1)], 3);
+    }
+
+    #[test]
+    fn test_map() {
+        let a = make_matrix(2, 3, &[((0, 0), 1.0), ((0, 2), 100.0), ((1, 1), -5.0)]);
+
+        let logs = a.map(|v| v.abs().log10());
+        assert_eq!(sorted_entries(&logs), vec![((0, 2), 2.0), ((1, 1), 5f64.log10())]);
+
+        let clipped = a.map(|&v| v.clamp(-1.0, 10.0));
+        assert_eq!(sorted_entries(&clipped),
+                   vec![((0, 0), 1.0), ((0, 2), 10.0), ((1, 1), -1.0)]);
+
+        let pattern: DOKMatrix<bool> = a.map(|_| true);
+        assert_eq!(pattern.nnz(), 3);
+
+        let upper = a.map_with_index(|(i, j), &v| if j > i { v } else { 0.0 });
+        assert_eq!(sorted_entries(&upper), vec![((0, 2), 100.0)]);
+
+        let mut b = a.clone();
+        b.apply(|v| v - 1.0);
+        assert_eq!(sorted_entries(&b), vec![((0, 2), 99.0), ((1, 1), -6.0)]);
+    }
+
+    #[test]
+    fn test_map_dense() {
+        let a = make_matrix(2, 2, &[((0, 1), 1.0)]);
+
+        let shifted = a.map_dense(|v| v + 1.0);
+        assert_eq!(sorted_entries(&shifted),
+                   vec![((0, 0), 1.0), ((0, 1), 2.0), ((1, 0), 1.0), ((1, 1), 1.0)]);
+
+        // Functions that keep zero at zero do not densify, whatever the
+        // shape.
+        let huge = make_matrix(1 << 20, 1 << 20, &[((5, 7), 3.0)]);
+        assert_eq!(huge.map_dense(|v| v * 2.0).nnz(), 1);
+
+        assert_eq!(huge.try_map_dense(|v| v + 1.0).map(|m| m.nnz()),
+                   Err(Error::TooDense {
+                       shape: (1 << 20, 1 << 20),
+                       limit: super::dok::MAX_DENSE_ELEMS,
+                   }));
+    }
+
+    fn example_reductions() -> FloatMatrix {
+        // [[1, 0, 2, 0],
+        //  [0, 0, 0, 0],
+        //  [-3, 5, 0, -6]]
+        make_matrix(3,
+                    4,
+                    &[((0, 0), 1.0), ((0, 2), 2.0), ((2, 0), -3.0), ((2, 1), 5.0), ((2, 3), -6.0)])
+    }
+
+    #[test]
+    fn test_sum() {
+        let m = example_reductions();
+        assert_eq!(m.sum(), -1.0);
+        assert_eq!(m.sum_axis(Axis::Rows), vec![3.0, 0.0, -4.0]);
+        assert_eq!(m.sum_axis(Axis::Cols), vec![-2.0, 5.0, 2.0, -6.0]);
+        assert_eq!(m.mean_axis(Axis::Rows), vec![0.75, 0.0, -1.0]);
+        assert_eq!(m.mean_axis(Axis::Cols), vec![-2.0 / 3.0, 5.0 / 3.0, 2.0 / 3.0, -2.0]);
+
+        // Line lengths near the top of a narrow integer type.
+        let mut narrow = DOKMatrix::<u8>::zeros(2, 200);
+        narrow.set(0, 199, 200);
+        assert_eq!(narrow.mean_axis(Axis::Rows), vec![1, 0]);
+        let mut narrow = DOKMatrix::<i8>::zeros(64, 127);
+        narrow.set(3, 0, -64);
+        assert_eq!(narrow.mean_axis(Axis::Rows)[3], 0);
+        assert_eq!(narrow.mean_axis(Axis::Cols)[0], -1);
+    }
+
+    #[test]
+    fn test_nnz_axis() {
+        let m = example_reductions();
+        assert_eq!(m.nnz_axis(Axis::Rows), vec![2, 0, 3]);
+        assert_eq!(m.nnz_axis(Axis::Cols), vec![2, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_min_max_axis() {
+        let m = example_reductions();
+        assert_eq!(m.max_axis(Axis::Rows), vec![2.0, 0.0, 5.0]);
+        assert_eq!(m.min_axis(Axis::Rows), vec![0.0, 0.0, -6.0]);
+        assert_eq!(m.max_axis(Axis::Cols), vec![1.0, 5.0, 2.0, 0.0]);
+        assert_eq!(m.min_axis(Axis::Cols), vec![-3.0, 0.0, 0.0, -6.0]);
+
+        // A full line has no implicit zero to compete with.
+        let full = make_matrix(1, 2, &[((0, 0), -1.0), ((0, 1), -2.0)]);
+        assert_eq!(full.max_axis(Axis::Rows), vec![-1.0]);
+    }
+
+    #[test]
+    fn test_argmax_axis() {
+        let m = example_reductions();
+        assert_eq!(m.argmax_axis(Axis::Rows), vec![2, 0, 1]);
+        assert_eq!(m.argmax_axis(Axis::Cols), vec![0, 2, 0, 0]);
+
+        // The maximum is an implicit zero at column 1.
+        let negative = make_matrix(1, 3, &[((0, 0), -1.0), ((0, 2), -2.0)]);
+        assert_eq!(negative.argmax_axis(Axis::Rows), vec![1]);
+        let full = make_matrix(1, 2, &[((0, 0), -1.0), ((0, 1), -2.0)]);
+        assert_eq!(full.argmax_axis(Axis::Rows), vec![0]);
+    }
+
+    #[test]
+    fn test_row_normalize() {
+        // Normalize the rows of a transition matrix.
+        let mut counts = DOKMatrix::<Rational>::zeros(2, 3);
+        counts.set(0, 0, Rational::from(1i64));
+        counts.set(0, 2, Rational::from(3i64));
+        counts.set(1, 1, Rational::from(2i64));
+        let totals = counts.sum_axis(Axis::Rows);
+        let transition = counts.map_with_index(|(i, _), &v| v / totals[i as usize]);
+        assert_eq!(transition.sum_axis(Axis::Rows), vec![Rational::one(); 2]);
+        assert_eq!(transition[(0, 2)], Rational::new(3, 4));
+        assert_eq!(counts.mean_axis(Axis::Cols)[2], Rational::new(3, 2));
+    }
+
+    #[test]
+    fn test_eq() {
+        let a = make_matrix(2, 3, &[((0, 0), 1.0), ((1, 2), 2.0)]);
+        let mut b = make_matrix(2, 3, &[((1, 2), 2.0), ((0, 0), 1.0)]);
+        assert!(a == b);
+
+        // Explicit zeros compare equal to absent elements.
+        b = make_matrix(2, 3, &[((1, 2), 2.0), ((0, 0), 1.0), ((1, 1), 0.0)]);
+        assert_eq!(b.nnz(), 3);
+        assert!(a == b);
+        assert!(b == a);
+
+        b.set(1, 1, 3.0);
+        assert!(a != b);
+        assert!(b != a);
+        assert!(a != make_matrix(3, 2, &[((0, 0), 1.0)]));
+        assert!(FloatMatrix::zeros(2, 2) != FloatMatrix::zeros(2, 3));
+
+        // Comparing whole matrices replaces elementwise loops.
+        assert!(a.transposed().transposed() == a);
+        assert!(&(&a + &b) - &b == a);
+    }
+
+    #[test]
+    fn test_approx_eq() {
+        let a = make_matrix(2, 2, &[((0, 0), 1.0), ((1, 1), 1e6)]);
+        let b = make_matrix(2, 2, &[((0, 0), 1.0 + 1e-10), ((1, 1), 1e6 + 1e-3), ((0, 1), 1e-12)]);
+        assert!(a.approx_eq(&b, 1e-8, 1e-9));
+        assert!(b.approx_eq(&a, 1e-8, 1e-9));
+        assert!(!a.approx_eq(&b, 0.0, 0.0));
+        assert!(!a.approx_eq(&b, 1e-8, 0.0));
+        assert!(!a.approx_eq(&make_matrix(2, 3, &[]), 1.0, 1.0));
+
+        let nan = make_matrix(2, 2, &[((0, 0), f64::NAN)]);
+        assert!(!nan.approx_eq(&nan, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_same_pattern() {
+        let a = make_matrix(2, 2, &[((0, 0), 1.0), ((1, 0), 2.0)]);
+        let b = make_matrix(2, 2, &[((0, 0), -5.0), ((1, 0), 7.0)]);
+        assert!(a.same_pattern(&b));
+        assert!(a.same_pattern(&a.map(|_| true)));
+        assert!(!a.same_pattern(&make_matrix(2, 2, &[((0, 0), 1.0)])));
+        assert!(!a.same_pattern(&make_matrix(2, 3, &[((0, 0), 1.0), ((1, 0), 2.0)])));
+
+        // Unlike equality, the pattern includes explicit zeros.
+        let c = make_matrix(2, 2, &[((0, 0), -5.0), ((1, 0), 7.0), ((1, 1), 0.0)]);
+        assert!(!b.same_pattern(&c));
+    }
+
+    #[test]
+    fn test_debug() {
+        let m = make_matrix(2, 3, &[((1, 1), 10.0), ((0, 2), -2.5), ((0, 0), 1.0)]);
+        assert_eq!(format!("{:?}", m),
+                   "DOKMatrix { shape: (2, 3), nnz: 3, elems: [((0, 0), 1.0), ((0, 2), -2.5), \
+                    ((1, 1), 10.0)] }");
+
+        let entries: Vec<_> = (0..10).map(|j| ((0, 9 - j), 1.0)).collect();
+        let long = make_matrix(1, 10, &entries);
+        assert_eq!(format!("{:?}", long),
+                   "DOKMatrix { shape: (1, 10), nnz: 10, elems: [((0, 0), 1.0), ((0, 1), 1.0), \
+                    ((0, 2), 1.0), ((0, 3), 1.0), ((0, 4), 1.0), ((0, 5), 1.0), ((0, 6), 1.0), \
+                    ((0, 7), 1.0), ... 2 more] }");
+
+        // With Debug available, matrices can be compared with assert_eq.
+        assert_eq!(m.transposed().transposed(), m);
+    }
+
+    #[test]
+    fn test_display() {
+        let m = make_matrix(2, 3, &[((0, 0), 1.0), ((0, 2), -2.5), ((1, 1), 10.0)]);
+        assert_eq!(m.to_string(), "[[1,  0, -2.5],\n [0, 10,    0]]");
+        assert_eq!(format!("{:.1}", m), "[[1.0,  0.0, -2.5],\n [0.0, 10.0,  0.0]]");
+        assert_eq!(FloatMatrix::zeros(0, 0).to_string(), "[]");
+
+        let large = make_matrix(1000, 20, &[((3, 4), 1.0)]);
+        assert_eq!(large.to_string(), "1000x20 sparse matrix with 1 stored elements");
+    }
+
+    #[test]
+    fn test_spy() {
+        let m = make_matrix(3, 5, &[((0, 0), 1.0), ((1, 3), 2.0), ((2, 4), 3.0)]);
+        assert_eq!(m.spy(), "*....\n...*.\n....*\n");
+
+        // Explicit zeros are not drawn.
+        let big = make_matrix(100,
+                              100,
+                              &[((0, 0), 1.0), ((99, 99), 1.0), ((45, 5), 1.0), ((50, 50), 0.0)]);
+        let spy = big.spy_with_size(10, 10);
+        let lines: Vec<&str> = spy.lines().collect();
+        assert_eq!(lines.len(), 10);
+        assert_eq!(lines[0], "*.........");
+        assert_eq!(lines[4], "*.........");
+        assert_eq!(lines[5], "..........");
+        assert_eq!(lines[9], ".........*");
+    }
 }
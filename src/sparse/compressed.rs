@@ -0,0 +1,95 @@
+//! Helpers shared by the compressed (CSR and CSC) storage formats.
+//!
+//! Both formats store a "major" axis (rows for CSR, columns for CSC) as a
+//! list of offsets into parallel arrays of "minor" indices and values.
+
+use error::{Error, Result};
+
+/// Return an error unless the given arrays describe a valid compressed
+/// matrix.
+///
+/// # Arguments
+///
+/// * `format` - Name of the format, used in error messages.
+/// * `major` - Name of the major axis, used in error messages.
+/// * `nmajor` - Length of the major axis.
+/// * `nminor` - Length of the minor axis.
+/// * `indptr` - Offsets of the start of each major slice.
+/// * `indices` - Minor index of each stored element.
+/// * `nnz` - Number of stored values.
+pub fn check_compressed(format: &str,
+                        major: &str,
+                        nmajor: u64,
+                        nminor: u64,
+                        indptr: &[usize],
+                        indices: &[u64],
+                        nnz: usize)
+                        -> Result<()> {
+    let invalid = |message: String| {
+        Err(Error::InvalidStructure(format!("Invalid {} matrix: {}", format, message)))
+    };
+
+    if indptr.len() as u128 != u128::from(nmajor) + 1 {
+        return invalid(format!("indptr has length {}, expected {}",
+                               indptr.len(),
+                               u128::from(nmajor) + 1));
+    }
+    if indices.len() != nnz {
+        return invalid(format!("indices has length {} but data has length {}",
+                               indices.len(),
+                               nnz));
+    }
+    if indptr[0] != 0 || indptr[nmajor as usize] != nnz {
+        return invalid(format!("indptr must start at 0 and end at {}", nnz));
+    }
+    for k in 0..nmajor as usize {
+        let (start, end) = (indptr[k], indptr[k + 1]);
+        if start > end {
+            return invalid(format!("indptr decreases at {} {}", major, k));
+        }
+        if end > nnz {
+            return invalid(format!("indptr exceeds {} at {} {}", nnz, major, k));
+        }
+        let minor = &indices[start..end];
+        if minor.iter().any(|&idx| idx >= nminor) {
+            return invalid(format!("index out of bounds in {} {}", major, k));
+        }
+        if minor.windows(2).any(|w| w[0] >= w[1]) {
+            return invalid(format!("indices of {} {} are not strictly increasing", major, k));
+        }
+    }
+    Ok(())
+}
+
+/// Build compressed arrays from `(major, minor, value)` triplets.
+///
+/// Each `(major, minor)` pair must appear at most once. The returned minor
+/// indices are sorted within each major slice.
+///
+/// # Arguments
+///
+/// * `nmajor` - Length of the major axis.
+/// * `entries` - Triplets to compress.
+pub fn compress<T, I>(nmajor: u64, entries: I) -> (Vec<usize>, Vec<u64>, Vec<T>)
+    where T: Clone,
+          I: Iterator<Item = (u64, u64, T)>
+{
+    let mut slices = vec![Vec::<(u64, T)>::new(); nmajor as usize];
+    for (major, minor, v) in entries {
+        slices[major as usize].push((minor, v));
+    }
+
+    let mut indptr = Vec::with_capacity(nmajor as usize + 1);
+    let mut indices = Vec::new();
+    let mut data = Vec::new();
+    indptr.push(0);
+    for mut slice in slices {
+        slice.sort_by_key(|&(minor, _)| minor);
+        for (minor, v) in slice {
+            indices.push(minor);
+            data.push(v);
+        }
+        indptr.push(indices.len());
+    }
+    (indptr, indices, data)
+}
@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::ops::Add;
+
+use error::{Error, Result};
+use sparse::compressed::compress;
+use sparse::csc::CscMatrix;
+use sparse::csr::CsrMatrix;
+use sparse::dok::{DOKMatrix, MatrixElem};
+
+/// A Coordinate (triplet) Sparse Matrix
+///
+/// Element `k` has value `values[k]` at coordinate `(rows[k], cols[k])`.
+/// The same coordinate may appear more than once, in which case the values
+/// are summed when the matrix is converted to another format.
+#[derive(Clone)]
+pub struct CooMatrix<T>
+    where T: MatrixElem
+{
+    pub nrows: u64,
+    pub ncols: u64,
+    rows: Vec<u64>,
+    cols: Vec<u64>,
+    values: Vec<T>,
+}
+
+impl<T> CooMatrix<T>
+    where T: MatrixElem
+{
+    /// Create an empty CooMatrix.
+    ///
+    /// # Arguments
+    ///
+    /// * `nrows` - Number of rows in the new matrix.
+    /// * `ncols` - Number of columns in the new matrix.
+    pub fn new(nrows: u64, ncols: u64) -> Self {
+        Self::with_capacity(nrows, ncols, 0)
+    }
+
+    /// Create an empty CooMatrix with room for `capacity` triplets.
+    ///
+    /// # Arguments
+    ///
+    /// * `nrows` - Number of rows in the new matrix.
+    /// * `ncols` - Number of columns in the new matrix.
+    /// * `capacity` - Number of triplets to preallocate.
+    pub fn with_capacity(nrows: u64, ncols: u64, capacity: usize) -> Self {
+        CooMatrix {
+            nrows,
+            ncols,
+            rows: Vec::with_capacity(capacity),
+            cols: Vec::with_capacity(capacity),
+            values: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Append the triplet `(row, col, value)`.
+    ///
+    /// Pushing a coordinate that is already present does not overwrite it;
+    /// both values are kept and later summed.
+    pub fn push(&mut self, row: u64, col: u64, value: T) {
+        self.try_push(row, col, value).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Append the triplet `(row, col, value)`, returning an error if
+    /// (row, col) lies outside of the matrix.
+    pub fn try_push(&mut self, row: u64, col: u64, value: T) -> Result<()> {
+        if row >= self.nrows || col >= self.ncols {
+            return Err(Error::OutOfBounds {
+                index: (row, col),
+                shape: (self.nrows, self.ncols),
+            });
+        }
+        self.rows.push(row);
+        self.cols.push(col);
+        self.values.push(value);
+        Ok(())
+    }
+
+    /// Number of stored triplets, counting duplicates separately.
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Row index of each stored triplet.
+    pub fn rows(&self) -> &[u64] {
+        &self.rows
+    }
+
+    /// Column index of each stored triplet.
+    pub fn cols(&self) -> &[u64] {
+        &self.cols
+    }
+
+    /// Value of each stored triplet.
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+}
+
+impl<T> CooMatrix<T>
+    where T: MatrixElem + Add<Output = T>
+{
+    /// Merge triplets that share a coordinate by summing their values.
+    ///
+    /// Afterwards the triplets are sorted in row-major order, each coordinate
+    /// appears at most once, and entries that summed to zero are removed.
+    pub fn sum_duplicates(&mut self) {
+        let summed = self.summed();
+        self.rows = summed.iter().map(|&(i, _, _)| i).collect();
+        self.cols = summed.iter().map(|&(_, j, _)| j).collect();
+        self.values = summed.into_iter().map(|(_, _, v)| v).collect();
+    }
+
+    /// Convert this matrix into a DOKMatrix, summing duplicate triplets.
+    pub fn to_dok(&self) -> DOKMatrix<T> {
+        let summed = self.summed();
+        let mut map = HashMap::with_capacity(summed.len());
+        for (i, j, v) in summed {
+            map.insert((i, j), v);
+        }
+        DOKMatrix::new(self.nrows, self.ncols, map)
+    }
+
+    /// Convert this matrix into a CsrMatrix, summing duplicate triplets.
+    pub fn to_csr(&self) -> CsrMatrix<T> {
+        let (indptr, indices, data) = compress(self.nrows, self.summed().into_iter());
+        CsrMatrix::new(self.nrows, self.ncols, indptr, indices, data)
+    }
+
+    /// Convert this matrix into a CscMatrix, summing duplicate triplets.
+    pub fn to_csc(&self) -> CscMatrix<T> {
+        let entries = self.summed().into_iter().map(|(i, j, v)| (j, i, v));
+        let (indptr, indices, data) = compress(self.ncols, entries);
+        CscMatrix::new(self.nrows, self.ncols, indptr, indices, data)
+    }
+
+    /// Get the stored triplets sorted in row-major order with duplicates
+    /// summed and zeros removed.
+    fn summed(&self) -> Vec<(u64, u64, T)> {
+        let mut order: Vec<usize> = (0..self.values.len()).collect();
+        order.sort_by_key(|&k| (self.rows[k], self.cols[k]));
+
+        let mut out = Vec::<(u64, u64, T)>::with_capacity(order.len());
+        for k in order {
+            let (i, j, v) = (self.rows[k], self.cols[k], self.values[k].clone());
+            match out.last_mut() {
+                Some(last) if (last.0, last.1) == (i, j) => last.2 = last.2.clone() + v,
+                _ => out.push((i, j, v)),
+            }
+        }
+        out.retain(|(_, _, v)| !v.is_zero());
+        out
+    }
+}
+
+impl<T> Extend<(u64, u64, T)> for CooMatrix<T>
+    where T: MatrixElem
+{
+    /// Append every `(row, col, value)` triplet yielded by `iter`.
+    fn extend<I>(&mut self, iter: I)
+        where I: IntoIterator<Item = (u64, u64, T)>
+    {
+        let iter = iter.into_iter();
+        let (lower, _) = iter.size_hint();
+        self.rows.reserve(lower);
+        self.cols.reserve(lower);
+        self.values.reserve(lower);
+        for (i, j, v) in iter {
+            self.push(i, j, v);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use util::itertools::cartesian_product;
+
+    use super::CooMatrix;
+
+    fn example() -> CooMatrix<f64> {
+        // Sums to
+        // [[3, 0, 0],
+        //  [0, 0, 4]]
+        let mut m = CooMatrix::new(2, 3);
+        m.push(1, 2, 4.0);
+        m.push(0, 0, 1.0);
+        m.extend(vec![(0, 1, 5.0), (0, 0, 2.0), (0, 1, -5.0)]);
+        m
+    }
+
+    #[test]
+    fn test_push_and_extend() {
+        let m = example();
+        assert_eq!(m.nnz(), 5);
+        assert_eq!(m.rows(), &[1, 0, 0, 0, 0]);
+        assert_eq!(m.cols(), &[2, 0, 1, 0, 1]);
+        assert_eq!(m.values(), &[4.0, 1.0, 5.0, 2.0, -5.0]);
+    }
+
+    #[test]
+    fn test_sum_duplicates() {
+        let mut m = example();
+        m.sum_duplicates();
+        assert_eq!(m.rows(), &[0, 1]);
+        assert_eq!(m.cols(), &[0, 2]);
+        assert_eq!(m.values(), &[3.0, 4.0]);
+    }
+
+    #[test]
+    fn test_conversions() {
+        let m = example();
+        let expected = [[3.0, 0.0, 0.0], [0.0, 0.0, 4.0]];
+
+        let dok = m.to_dok();
+        let csr = m.to_csr();
+        let csc = m.to_csc();
+        assert_eq!(csr.nnz(), 2);
+        assert_eq!(csc.nnz(), 2);
+        for (i, j) in cartesian_product(0..2, 0..3) {
+            let v = expected[i as usize][j as usize];
+            assert_eq!(dok[(i, j)], v);
+            assert_eq!(csr[(i, j)], v);
+            assert_eq!(csc[(i, j)], v);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Out of bounds")]
+    fn test_push_out_of_bounds() {
+        CooMatrix::new(2, 2).push(0, 2, 1.0);
+    }
+}
@@ -0,0 +1,380 @@
+use std::collections::HashMap;
+use std::ops::{Add, Index, Mul};
+
+use error::{Error, Result};
+use sparse::compressed::{check_compressed, compress};
+use sparse::csr::CsrMatrix;
+use sparse::dok::{DOKMatrix, MatrixElem};
+use sparse::semiring::{PlusTimes, Semiring};
+
+/// A Compressed Sparse Column Matrix
+///
+/// The row indices and values of column `j` are stored in
+/// `indices[indptr[j]..indptr[j + 1]]` and `data[indptr[j]..indptr[j + 1]]`.
+/// Row indices within each column are strictly increasing.
+#[derive(Clone)]
+pub struct CscMatrix<T>
+    where T: MatrixElem
+{
+    pub nrows: u64,
+    pub ncols: u64,
+    indptr: Vec<usize>,
+    indices: Vec<u64>,
+    data: Vec<T>,
+    // Absent elements are returned from `Index` as a reference to this value.
+    zero: T,
+}
+
+impl<T> CscMatrix<T>
+    where T: MatrixElem
+{
+    /// Create a CscMatrix from its raw arrays.
+    ///
+    /// Panics if the arrays do not describe a valid matrix: `indptr` must
+    /// have `ncols + 1` non-decreasing entries starting at zero and ending at
+    /// `data.len()`, and the row indices of each column must be strictly
+    /// increasing and less than `nrows`.
+    ///
+    /// # Arguments
+    ///
+    /// * `nrows` - Number of rows in the new matrix.
+    /// * `ncols` - Number of columns in the new matrix.
+    /// * `indptr` - Offsets of the start of each column in `indices` and
+    ///   `data`.
+    /// * `indices` - Row index of each stored element.
+    /// * `data` - Value of each stored element.
+    pub fn new(nrows: u64,
+               ncols: u64,
+               indptr: Vec<usize>,
+               indices: Vec<u64>,
+               data: Vec<T>)
+               -> Self {
+        Self::try_new(nrows, ncols, indptr, indices, data).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Create a CscMatrix from its raw arrays, returning an error if they do
+    /// not describe a valid matrix.
+    pub fn try_new(nrows: u64,
+                   ncols: u64,
+                   indptr: Vec<usize>,
+                   indices: Vec<u64>,
+                   data: Vec<T>)
+                   -> Result<Self> {
+        check_compressed("CSC", "column", ncols, nrows, &indptr, &indices, data.len())?;
+        Ok(CscMatrix {
+            nrows,
+            ncols,
+            indptr,
+            indices,
+            data,
+            zero: T::zero(),
+        })
+    }
+
+    /// Create a CscMatrix with all zero elements.
+    ///
+    /// # Arguments
+    ///
+    /// * `nrows` - Number of rows in the new matrix.
+    /// * `ncols` - Number of columns in the new matrix.
+    pub fn zeros(nrows: u64, ncols: u64) -> Self {
+        Self::new(nrows, ncols, vec![0; ncols as usize + 1], vec![], vec![])
+    }
+
+    /// Create a CscMatrix holding the same elements as a DOKMatrix.
+    pub fn from_dok(dok: &DOKMatrix<T>) -> Self {
+        let entries = dok.elems.iter().map(|(&(i, j), v)| (j, i, v.clone()));
+        let (indptr, indices, data) = compress(dok.ncols, entries);
+        Self::new(dok.nrows, dok.ncols, indptr, indices, data)
+    }
+
+    /// Convert this matrix into a DOKMatrix holding the same elements.
+    pub fn to_dok(&self) -> DOKMatrix<T> {
+        let mut map = HashMap::with_capacity(self.data.len());
+        for col in 0..self.ncols {
+            let (rows, values) = self.col(col);
+            for (&row, v) in rows.iter().zip(values) {
+                map.insert((row, col), v.clone());
+            }
+        }
+        DOKMatrix::new(self.nrows, self.ncols, map)
+    }
+
+    /// Get the row indices and values stored in column `col`.
+    pub fn col(&self, col: u64) -> (&[u64], &[T]) {
+        if col >= self.ncols {
+            panic!("Out of bounds column {col} for sparse matrix of shape \
+                    ({nrows}, {ncols})",
+                   col = col,
+                   nrows = self.nrows,
+                   ncols = self.ncols)
+        }
+        let (start, end) = (self.indptr[col as usize], self.indptr[col as usize + 1]);
+        (&self.indices[start..end], &self.data[start..end])
+    }
+
+    /// Get the element at coordinate (row, col), or `None` if (row, col)
+    /// lies outside of the matrix.
+    pub fn get(&self, row: u64, col: u64) -> Option<&T> {
+        if row >= self.nrows || col >= self.ncols {
+            return None;
+        }
+        let (rows, values) = self.col(col);
+        match rows.binary_search(&row) {
+            Ok(pos) => Some(&values[pos]),
+            Err(_) => Some(&self.zero),
+        }
+    }
+
+    /// Get the transpose of this matrix as a CsrMatrix.
+    ///
+    /// The compressed arrays are reused as-is: the columns of `self` are the
+    /// rows of its transpose, so no sorting or regrouping is needed.
+    pub fn transposed(&self) -> CsrMatrix<T> {
+        self.clone().into_transposed()
+    }
+
+    /// Consume this matrix and reinterpret its storage as its transpose.
+    pub fn into_transposed(self) -> CsrMatrix<T> {
+        CsrMatrix::new(self.ncols, self.nrows, self.indptr, self.indices, self.data)
+    }
+
+    /// Number of explicitly stored elements.
+    pub fn nnz(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Column offsets into `indices` and `data`.
+    pub fn indptr(&self) -> &[usize] {
+        &self.indptr
+    }
+
+    /// Row index of each stored element.
+    pub fn indices(&self) -> &[u64] {
+        &self.indices
+    }
+
+    /// Value of each stored element.
+    pub fn data(&self) -> &[T] {
+        &self.data
+    }
+}
+
+impl<T> CscMatrix<T>
+    where T: MatrixElem
+{
+    /// Compute the matrix-vector product `self * x` over `semiring`.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Dense vector of length `self.ncols`.
+    /// * `semiring` - Operations used to combine elements.
+    pub fn mxv<S>(&self, x: &[T], semiring: S) -> Vec<T>
+        where S: Semiring<T>
+    {
+        self.try_mxv(x, semiring).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Compute the matrix-vector product `self * x` over `semiring`,
+    /// returning an error if `x` does not have length `self.ncols`.
+    pub fn try_mxv<S>(&self, x: &[T], semiring: S) -> Result<Vec<T>>
+        where S: Semiring<T>
+    {
+        self.check_vector_len(x, self.ncols, "matvec")?;
+        let mut out = vec![semiring.zero(); self.nrows as usize];
+        for col in 0..self.ncols {
+            let (rows, values) = self.col(col);
+            let scale = &x[col as usize];
+            for (&row, v) in rows.iter().zip(values) {
+                let row = row as usize;
+                let product = semiring.mul(v.clone(), scale.clone());
+                out[row] = semiring.add(out[row].clone(), product);
+            }
+        }
+        Ok(out)
+    }
+
+    /// Compute the vector-matrix product `x^T * self` over `semiring`.
+    ///
+    /// Each output element combines `x` with one stored column, which is the
+    /// natural access pattern for this format.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Dense vector of length `self.nrows`.
+    /// * `semiring` - Operations used to combine elements.
+    pub fn vxm<S>(&self, x: &[T], semiring: S) -> Vec<T>
+        where S: Semiring<T>
+    {
+        self.try_vxm(x, semiring).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Compute the vector-matrix product `x^T * self` over `semiring`,
+    /// returning an error if `x` does not have length `self.nrows`.
+    pub fn try_vxm<S>(&self, x: &[T], semiring: S) -> Result<Vec<T>>
+        where S: Semiring<T>
+    {
+        self.check_vector_len(x, self.nrows, "rmatvec")?;
+        Ok((0..self.ncols)
+            .map(|col| {
+                let (rows, values) = self.col(col);
+                rows.iter().zip(values).fold(semiring.zero(), |acc, (&row, v)| {
+                    semiring.add(acc, semiring.mul(x[row as usize].clone(), v.clone()))
+                })
+            })
+            .collect())
+    }
+
+    /// Return an error if `x` does not have length `expected`.
+    fn check_vector_len(&self, x: &[T], expected: u64, op: &'static str) -> Result<()> {
+        if x.len() as u64 != expected {
+            return Err(Error::ShapeMismatch {
+                op,
+                left: (self.nrows, self.ncols),
+                right: (x.len() as u64, 1),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl<T> CscMatrix<T>
+    where T: MatrixElem + Add<Output = T> + Mul<Output = T>
+{
+    /// Compute the matrix-vector product `self * x`.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Dense vector of length `self.ncols`.
+    pub fn matvec(&self, x: &[T]) -> Vec<T> {
+        self.mxv(x, PlusTimes)
+    }
+
+    /// Compute the matrix-vector product `self * x`, returning an error if
+    /// `x` does not have length `self.ncols`.
+    pub fn try_matvec(&self, x: &[T]) -> Result<Vec<T>> {
+        self.try_mxv(x, PlusTimes)
+    }
+
+    /// Compute the transposed matrix-vector product `self^T * x`.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Dense vector of length `self.nrows`.
+    pub fn rmatvec(&self, x: &[T]) -> Vec<T> {
+        self.vxm(x, PlusTimes)
+    }
+
+    /// Compute the transposed matrix-vector product `self^T * x`, returning
+    /// an error if `x` does not have length `self.nrows`.
+    pub fn try_rmatvec(&self, x: &[T]) -> Result<Vec<T>> {
+        self.try_vxm(x, PlusTimes)
+    }
+}
+
+impl<T> Index<(u64, u64)> for CscMatrix<T>
+    where T: MatrixElem
+{
+    type Output = T;
+
+    /// Get the element at coordinate (row, col).
+    fn index(&self, (row, col): (u64, u64)) -> &T {
+        self.get(row, col).unwrap_or_else(|| {
+            panic!("{}",
+                   Error::OutOfBounds {
+                       index: (row, col),
+                       shape: (self.nrows, self.ncols),
+                   })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use util::itertools::cartesian_product;
+
+    use super::CscMatrix;
+    use sparse::csr::CsrMatrix;
+    use sparse::dok::DOKMatrix;
+
+    fn example() -> CscMatrix<f64> {
+        // [[1, 0, 2, 0],
+        //  [0, 0, 0, 0],
+        //  [0, 5, 0, 6]]
+        CscMatrix::new(3,
+                       4,
+                       vec![0, 1, 2, 3, 4],
+                       vec![0, 2, 0, 2],
+                       vec![1.0, 5.0, 2.0, 6.0])
+    }
+
+    #[test]
+    fn test_index() {
+        let m = example();
+        let expected = [[1.0, 0.0, 2.0, 0.0], [0.0, 0.0, 0.0, 0.0], [0.0, 5.0, 0.0, 6.0]];
+        for (i, j) in cartesian_product(0..3, 0..4) {
+            assert_eq!(m[(i, j)], expected[i as usize][j as usize]);
+        }
+        assert_eq!(m.nnz(), 4);
+    }
+
+    #[test]
+    fn test_col() {
+        let m = example();
+        assert_eq!(m.col(0), (&[0][..], &[1.0][..]));
+        assert_eq!(m.col(3), (&[2][..], &[6.0][..]));
+    }
+
+    #[test]
+    fn test_matvec() {
+        let m = example();
+        assert_eq!(m.matvec(&[1.0, 2.0, 3.0, 4.0]), vec![7.0, 0.0, 34.0]);
+        assert_eq!(m.rmatvec(&[1.0, 2.0, 3.0]), vec![1.0, 15.0, 2.0, 18.0]);
+    }
+
+    #[test]
+    fn test_dok_roundtrip() {
+        let mut elems = HashMap::new();
+        elems.insert((2, 3), 6.0);
+        elems.insert((0, 2), 2.0);
+        elems.insert((2, 1), 5.0);
+        elems.insert((0, 0), 1.0);
+        let dok = DOKMatrix::new(3, 4, elems);
+
+        let csc = CscMatrix::from_dok(&dok);
+        assert_eq!(csc.indptr(), &[0, 1, 2, 3, 4]);
+        assert_eq!(csc.indices(), &[0, 2, 0, 2]);
+        assert_eq!(csc.data(), &[1.0, 5.0, 2.0, 6.0]);
+
+        let back = csc.to_dok();
+        assert_eq!((back.nrows, back.ncols), (3, 4));
+        for (i, j) in cartesian_product(0..3, 0..4) {
+            assert_eq!(back[(i, j)], dok[(i, j)]);
+        }
+    }
+
+    #[test]
+    fn test_transposed() {
+        let m = example();
+        let t: CsrMatrix<f64> = m.transposed();
+        assert_eq!((t.nrows, t.ncols), (4, 3));
+        for (i, j) in cartesian_product(0..3, 0..4) {
+            assert_eq!(t[(j, i)], m[(i, j)]);
+        }
+
+        // Transposing a DOK matrix and compressing it by rows gives the same
+        // arrays as compressing the original by columns.
+        let dok = m.to_dok();
+        let csr = CsrMatrix::from_dok(&dok.transposed());
+        assert_eq!(csr.indptr(), t.indptr());
+        assert_eq!(csr.indices(), t.indices());
+        assert_eq!(csr.data(), t.data());
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid CSC matrix")]
+    fn test_row_out_of_bounds() {
+        CscMatrix::new(3, 1, vec![0, 1], vec![3], vec![1.0]);
+    }
+}
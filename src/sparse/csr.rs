@@ -0,0 +1,358 @@
+use std::collections::HashMap;
+use std::ops::{Add, Index, Mul};
+
+use error::{Error, Result};
+use sparse::compressed::{check_compressed, compress};
+use sparse::csc::CscMatrix;
+use sparse::dok::{DOKMatrix, MatrixElem};
+use sparse::semiring::{PlusTimes, Semiring};
+
+/// A Compressed Sparse Row Matrix
+///
+/// The column indices and values of row `i` are stored in
+/// `indices[indptr[i]..indptr[i + 1]]` and `data[indptr[i]..indptr[i + 1]]`.
+/// Column indices within each row are strictly increasing.
+#[derive(Clone)]
+pub struct CsrMatrix<T>
+    where T: MatrixElem
+{
+    pub nrows: u64,
+    pub ncols: u64,
+    indptr: Vec<usize>,
+    indices: Vec<u64>,
+    data: Vec<T>,
+    // Absent elements are returned from `Index` as a reference to this value.
+    zero: T,
+}
+
+impl<T> CsrMatrix<T>
+    where T: MatrixElem
+{
+    /// Create a CsrMatrix from its raw arrays.
+    ///
+    /// Panics if the arrays do not describe a valid matrix: `indptr` must
+    /// have `nrows + 1` non-decreasing entries starting at zero and ending at
+    /// `data.len()`, and the column indices of each row must be strictly
+    /// increasing and less than `ncols`.
+    ///
+    /// # Arguments
+    ///
+    /// * `nrows` - Number of rows in the new matrix.
+    /// * `ncols` - Number of columns in the new matrix.
+    /// * `indptr` - Offsets of the start of each row in `indices` and `data`.
+    /// * `indices` - Column index of each stored element.
+    /// * `data` - Value of each stored element.
+    pub fn new(nrows: u64,
+               ncols: u64,
+               indptr: Vec<usize>,
+               indices: Vec<u64>,
+               data: Vec<T>)
+               -> Self {
+        Self::try_new(nrows, ncols, indptr, indices, data).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Create a CsrMatrix from its raw arrays, returning an error if they do
+    /// not describe a valid matrix.
+    pub fn try_new(nrows: u64,
+                   ncols: u64,
+                   indptr: Vec<usize>,
+                   indices: Vec<u64>,
+                   data: Vec<T>)
+                   -> Result<Self> {
+        check_compressed("CSR", "row", nrows, ncols, &indptr, &indices, data.len())?;
+        Ok(CsrMatrix {
+            nrows,
+            ncols,
+            indptr,
+            indices,
+            data,
+            zero: T::zero(),
+        })
+    }
+
+    /// Create a CsrMatrix with all zero elements.
+    ///
+    /// # Arguments
+    ///
+    /// * `nrows` - Number of rows in the new matrix.
+    /// * `ncols` - Number of columns in the new matrix.
+    pub fn zeros(nrows: u64, ncols: u64) -> Self {
+        Self::new(nrows, ncols, vec![0; nrows as usize + 1], vec![], vec![])
+    }
+
+    /// Create a CsrMatrix holding the same elements as a DOKMatrix.
+    pub fn from_dok(dok: &DOKMatrix<T>) -> Self {
+        let entries = dok.elems.iter().map(|(&(i, j), v)| (i, j, v.clone()));
+        let (indptr, indices, data) = compress(dok.nrows, entries);
+        Self::new(dok.nrows, dok.ncols, indptr, indices, data)
+    }
+
+    /// Convert this matrix into a DOKMatrix holding the same elements.
+    pub fn to_dok(&self) -> DOKMatrix<T> {
+        let mut map = HashMap::with_capacity(self.data.len());
+        for row in 0..self.nrows {
+            let (cols, values) = self.row(row);
+            for (&col, v) in cols.iter().zip(values) {
+                map.insert((row, col), v.clone());
+            }
+        }
+        DOKMatrix::new(self.nrows, self.ncols, map)
+    }
+
+    /// Get the column indices and values stored in row `row`.
+    pub fn row(&self, row: u64) -> (&[u64], &[T]) {
+        if row >= self.nrows {
+            panic!("Out of bounds row {row} for sparse matrix of shape \
+                    ({nrows}, {ncols})",
+                   row = row,
+                   nrows = self.nrows,
+                   ncols = self.ncols)
+        }
+        let (start, end) = (self.indptr[row as usize], self.indptr[row as usize + 1]);
+        (&self.indices[start..end], &self.data[start..end])
+    }
+
+    /// Get the element at coordinate (row, col), or `None` if (row, col)
+    /// lies outside of the matrix.
+    pub fn get(&self, row: u64, col: u64) -> Option<&T> {
+        if row >= self.nrows || col >= self.ncols {
+            return None;
+        }
+        let (cols, values) = self.row(row);
+        match cols.binary_search(&col) {
+            Ok(pos) => Some(&values[pos]),
+            Err(_) => Some(&self.zero),
+        }
+    }
+
+    /// Get the transpose of this matrix as a CscMatrix.
+    ///
+    /// The compressed arrays are reused as-is: the rows of `self` are the
+    /// columns of its transpose, so no sorting or regrouping is needed.
+    pub fn transposed(&self) -> CscMatrix<T> {
+        self.clone().into_transposed()
+    }
+
+    /// Consume this matrix and reinterpret its storage as its transpose.
+    pub fn into_transposed(self) -> CscMatrix<T> {
+        CscMatrix::new(self.ncols, self.nrows, self.indptr, self.indices, self.data)
+    }
+
+    /// Number of explicitly stored elements.
+    pub fn nnz(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Row offsets into `indices` and `data`.
+    pub fn indptr(&self) -> &[usize] {
+        &self.indptr
+    }
+
+    /// Column index of each stored element.
+    pub fn indices(&self) -> &[u64] {
+        &self.indices
+    }
+
+    /// Value of each stored element.
+    pub fn data(&self) -> &[T] {
+        &self.data
+    }
+}
+
+impl<T> CsrMatrix<T>
+    where T: MatrixElem
+{
+    /// Compute the matrix-vector product `self * x` over `semiring`.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Dense vector of length `self.ncols`.
+    /// * `semiring` - Operations used to combine elements.
+    pub fn mxv<S>(&self, x: &[T], semiring: S) -> Vec<T>
+        where S: Semiring<T>
+    {
+        self.try_mxv(x, semiring).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Compute the matrix-vector product `self * x` over `semiring`,
+    /// returning an error if `x` does not have length `self.ncols`.
+    pub fn try_mxv<S>(&self, x: &[T], semiring: S) -> Result<Vec<T>>
+        where S: Semiring<T>
+    {
+        if x.len() as u64 != self.ncols {
+            return Err(Error::ShapeMismatch {
+                op: "matvec",
+                left: (self.nrows, self.ncols),
+                right: (x.len() as u64, 1),
+            });
+        }
+        Ok((0..self.nrows)
+            .map(|row| {
+                let (cols, values) = self.row(row);
+                cols.iter().zip(values).fold(semiring.zero(), |acc, (&col, v)| {
+                    semiring.add(acc, semiring.mul(v.clone(), x[col as usize].clone()))
+                })
+            })
+            .collect())
+    }
+}
+
+impl<T> CsrMatrix<T>
+    where T: MatrixElem + Add<Output = T> + Mul<Output = T>
+{
+    /// Compute the matrix-vector product `self * x`.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Dense vector of length `self.ncols`.
+    pub fn matvec(&self, x: &[T]) -> Vec<T> {
+        self.mxv(x, PlusTimes)
+    }
+
+    /// Compute the matrix-vector product `self * x`, returning an error if
+    /// `x` does not have length `self.ncols`.
+    pub fn try_matvec(&self, x: &[T]) -> Result<Vec<T>> {
+        self.try_mxv(x, PlusTimes)
+    }
+}
+
+impl<T> Index<(u64, u64)> for CsrMatrix<T>
+    where T: MatrixElem
+{
+    type Output = T;
+
+    /// Get the element at coordinate (row, col).
+    fn index(&self, (row, col): (u64, u64)) -> &T {
+        self.get(row, col).unwrap_or_else(|| {
+            panic!("{}",
+                   Error::OutOfBounds {
+                       index: (row, col),
+                       shape: (self.nrows, self.ncols),
+                   })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use util::itertools::cartesian_product;
+
+    use super::CsrMatrix;
+    use sparse::dok::DOKMatrix;
+
+    fn example() -> CsrMatrix<f64> {
+        // [[1, 0, 2, 0],
+        //  [0, 0, 0, 0],
+        //  [0, 5, 0, 6]]
+        CsrMatrix::new(3,
+                       4,
+                       vec![0, 2, 2, 4],
+                       vec![0, 2, 1, 3],
+                       vec![1.0, 2.0, 5.0, 6.0])
+    }
+
+    #[test]
+    fn test_index() {
+        let m = example();
+        let expected = [[1.0, 0.0, 2.0, 0.0], [0.0, 0.0, 0.0, 0.0], [0.0, 5.0, 0.0, 6.0]];
+        for (i, j) in cartesian_product(0..3, 0..4) {
+            assert_eq!(m[(i, j)], expected[i as usize][j as usize]);
+        }
+        assert_eq!(m.nnz(), 4);
+    }
+
+    #[test]
+    fn test_row() {
+        let m = example();
+        assert_eq!(m.row(0), (&[0, 2][..], &[1.0, 2.0][..]));
+        assert_eq!(m.row(1), (&[][..], &[][..]));
+        assert_eq!(m.row(2), (&[1, 3][..], &[5.0, 6.0][..]));
+    }
+
+    #[test]
+    fn test_matvec() {
+        let m = example();
+        assert_eq!(m.matvec(&[1.0, 2.0, 3.0, 4.0]), vec![7.0, 0.0, 34.0]);
+    }
+
+    #[test]
+    fn test_dok_roundtrip() {
+        let mut elems = HashMap::new();
+        elems.insert((2, 3), 6.0);
+        elems.insert((0, 2), 2.0);
+        elems.insert((2, 1), 5.0);
+        elems.insert((0, 0), 1.0);
+        let dok = DOKMatrix::new(3, 4, elems);
+
+        let csr = CsrMatrix::from_dok(&dok);
+        assert_eq!(csr.indptr(), &[0, 2, 2, 4]);
+        assert_eq!(csr.indices(), &[0, 2, 1, 3]);
+        assert_eq!(csr.data(), &[1.0, 2.0, 5.0, 6.0]);
+
+        let back = csr.to_dok();
+        assert_eq!((back.nrows, back.ncols), (3, 4));
+        for (i, j) in cartesian_product(0..3, 0..4) {
+            assert_eq!(back[(i, j)], dok[(i, j)]);
+        }
+    }
+
+    #[test]
+    fn test_zeros() {
+        let m = CsrMatrix::<f64>::zeros(2, 3);
+        assert_eq!(m.nnz(), 0);
+        for (i, j) in cartesian_product(0..2, 0..3) {
+            assert_eq!(m[(i, j)], 0.0);
+        }
+    }
+
+    #[test]
+    fn test_transposed() {
+        let m = example();
+        let t = m.transposed();
+        assert_eq!((t.nrows, t.ncols), (4, 3));
+        for (i, j) in cartesian_product(0..3, 0..4) {
+            assert_eq!(t[(j, i)], m[(i, j)]);
+        }
+        let back = t.into_transposed();
+        assert_eq!(back.indptr(), m.indptr());
+        assert_eq!(back.indices(), m.indices());
+        assert_eq!(back.data(), m.data());
+    }
+
+    #[test]
+    fn test_get() {
+        let m = example();
+        assert_eq!(m.get(2, 3), Some(&6.0));
+        assert_eq!(m.get(1, 3), Some(&0.0));
+        assert_eq!(m.get(3, 0), None);
+    }
+
+    #[test]
+    fn test_try_new() {
+        assert!(CsrMatrix::try_new(1, 3, vec![0, 1], vec![2], vec![1.0]).is_ok());
+        assert!(CsrMatrix::try_new(1, 3, vec![0, 2], vec![2], vec![1.0]).is_err());
+        assert!(CsrMatrix::try_new(2, 3, vec![0, 1], vec![2], vec![1.0]).is_err());
+        assert!(CsrMatrix::try_new(2, 3, vec![0, 5, 1], vec![0], vec![1.0]).is_err());
+        assert!(CsrMatrix::<f64>::try_new(u64::MAX, 3, vec![0], vec![], vec![]).is_err());
+        assert!(example().try_matvec(&[1.0]).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid CSR matrix")]
+    fn test_unsorted_indices() {
+        CsrMatrix::new(1, 3, vec![0, 2], vec![2, 0], vec![1.0, 2.0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid CSR matrix")]
+    fn test_column_out_of_bounds() {
+        CsrMatrix::new(1, 3, vec![0, 1], vec![3], vec![1.0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Out of bounds")]
+    fn test_index_out_of_bounds() {
+        let _ = example()[(3, 0)];
+    }
+}
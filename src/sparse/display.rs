@@ -0,0 +1,155 @@
+use std::fmt;
+
+use sparse::dok::{DOKMatrix, MatrixElem};
+
+/// Number of triplets shown by the `Debug` impl before eliding the rest.
+const DEBUG_TRIPLETS: usize = 8;
+
+/// Largest number of rows or columns that `Display` renders densely.
+const DISPLAY_MAX_DIM: u64 = 16;
+
+/// Default size of the character grid drawn by `spy`.
+const SPY_MAX_ROWS: u64 = 32;
+const SPY_MAX_COLS: u64 = 64;
+
+/// Formats the first few triplets of a matrix as a list, followed by a count
+/// of the elided ones.
+struct Triplets<'a, T: 'a> {
+    shown: Vec<((u64, u64), &'a T)>,
+    elided: usize,
+}
+
+impl<'a, T> fmt::Debug for Triplets<'a, T>
+    where T: fmt::Debug
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut list = f.debug_list();
+        for &(coords, v) in &self.shown {
+            list.entry(&(coords, v));
+        }
+        if self.elided > 0 {
+            list.entry(&format_args!("... {} more", self.elided));
+        }
+        list.finish()
+    }
+}
+
+impl<T> fmt::Debug for DOKMatrix<T>
+    where T: MatrixElem + fmt::Debug
+{
+    /// Show the shape, the number of stored elements and the first few
+    /// stored elements in row-major order.
+    ///
+    /// Only the elements that are shown get sorted, so this costs O(nnz)
+    /// however large the matrix is.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut elems: Vec<((u64, u64), &T)> = self.iter().collect();
+        let shown = elems.len().min(DEBUG_TRIPLETS);
+        if shown < elems.len() {
+            elems.select_nth_unstable_by_key(shown, |&(coords, _)| coords);
+        }
+        let elided = elems.len() - shown;
+        elems.truncate(shown);
+        elems.sort_unstable_by_key(|&(coords, _)| coords);
+
+        f.debug_struct("DOKMatrix")
+            .field("shape", &(self.nrows, self.ncols))
+            .field("nnz", &self.nnz())
+            .field("elems",
+                   &Triplets {
+                       shown: elems,
+                       elided,
+                   })
+            .finish()
+    }
+}
+
+impl<T> fmt::Display for DOKMatrix<T>
+    where T: MatrixElem + fmt::Display
+{
+    /// Render the matrix densely, with each column right-aligned, if it has
+    /// at most 16 rows and columns. Larger matrices are summarized by their
+    /// shape and number of stored elements.
+    ///
+    /// A precision given in the format string, as in `{:.2}`, is applied to
+    /// every element.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.nrows > DISPLAY_MAX_DIM || self.ncols > DISPLAY_MAX_DIM {
+            return write!(f,
+                          "{}x{} sparse matrix with {} stored elements",
+                          self.nrows,
+                          self.ncols,
+                          self.nnz());
+        }
+
+        let render = |v: &T| match f.precision() {
+            Some(precision) => format!("{:.*}", precision, v),
+            None => v.to_string(),
+        };
+        let cells: Vec<Vec<String>> = (0..self.nrows)
+            .map(|i| (0..self.ncols).map(|j| render(&self[(i, j)])).collect())
+            .collect();
+        let widths: Vec<usize> = (0..self.ncols as usize)
+            .map(|j| cells.iter().map(|row| row[j].len()).max().unwrap_or(0))
+            .collect();
+
+        write!(f, "[")?;
+        for (i, row) in cells.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",\n ")?;
+            }
+            write!(f, "[")?;
+            for (j, cell) in row.iter().enumerate() {
+                if j > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{:>width$}", cell, width = widths[j])?;
+            }
+            write!(f, "]")?;
+        }
+        write!(f, "]")
+    }
+}
+
+impl<T> DOKMatrix<T>
+    where T: MatrixElem
+{
+    /// Draw the sparsity pattern of the matrix as a grid of characters, at
+    /// most 32 rows by 64 columns.
+    ///
+    /// See `spy_with_size`.
+    pub fn spy(&self) -> String {
+        self.spy_with_size(SPY_MAX_ROWS, SPY_MAX_COLS)
+    }
+
+    /// Draw the sparsity pattern of the matrix as a grid of characters.
+    ///
+    /// Each character stands for a block of elements, and is `*` if any of
+    /// them is a stored non-zero and `.` otherwise. Matrices larger than the
+    /// grid are downsampled by using blocks of more than one element. Every
+    /// line of the grid, including the last, ends with a newline.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_rows` - Largest number of lines in the grid.
+    /// * `max_cols` - Largest number of characters in each line.
+    pub fn spy_with_size(&self, max_rows: u64, max_cols: u64) -> String {
+        let block = |len: u64, max: u64| if len <= max { 1 } else { len.div_ceil(max.max(1)) };
+        let (block_rows, block_cols) = (block(self.nrows, max_rows), block(self.ncols, max_cols));
+        let (rows, cols) = (self.nrows.div_ceil(block_rows) as usize,
+                            self.ncols.div_ceil(block_cols) as usize);
+
+        let mut grid = vec![vec![b'.'; cols]; rows];
+        for ((i, j), v) in self.iter() {
+            if !v.is_zero() {
+                grid[(i / block_rows) as usize][(j / block_cols) as usize] = b'*';
+            }
+        }
+        let mut out = String::with_capacity(rows * (cols + 1));
+        for line in grid {
+            out.extend(line.into_iter().map(char::from));
+            out.push('\n');
+        }
+        out
+    }
+}
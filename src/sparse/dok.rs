@@ -0,0 +1,1597 @@
+use std::collections::{hash_map, HashMap};
+use std::mem;
+use std::ops::{Add, AddAssign, Deref, DerefMut, Div, DivAssign, Index, Mul, MulAssign, Neg,
+               Sub, SubAssign};
+
+use error::{Error, Result};
+use sparse::semiring::{self, PlusTimes, Semiring};
+
+type Coords = (u64, u64);
+type CoordMap<T> = HashMap<Coords, T>;
+
+/// Types with an additive identity.
+pub trait Zero: Sized {
+    fn zero() -> Self;
+    fn is_zero(&self) -> bool;
+}
+
+/// Types with a multiplicative identity.
+pub trait One: Sized {
+    fn one() -> Self;
+}
+
+macro_rules! impl_zero_one {
+    ($zero:expr, $one:expr; $($t:ty)*) => {
+        $(
+            impl Zero for $t {
+                fn zero() -> $t {
+                    $zero
+                }
+
+                fn is_zero(&self) -> bool {
+                    *self == $zero
+                }
+            }
+
+            impl One for $t {
+                fn one() -> $t {
+                    $one
+                }
+            }
+        )*
+    }
+}
+
+impl_zero_one!(0, 1; i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize);
+impl_zero_one!(0.0, 1.0; f32 f64);
+impl_zero_one!(false, true; bool);
+
+pub trait MatrixElem: Zero + One + Clone {}
+impl<T: Zero + One + Clone> MatrixElem for T {}
+
+/// Largest number of elements `map_dense` will store.
+pub const MAX_DENSE_ELEMS: u64 = 1 << 26;
+
+/// A Dictionary-of-Keys Sparse Matrix
+///
+/// Writing zero to an element removes it instead of storing it. For this
+/// reason `IndexMut` is not implemented: it would have to hand out a bare
+/// `&mut T` that could be set to zero. Use `set`, `entry` or `get_mut` to
+/// change elements in place.
+#[derive(Clone)]
+pub struct DOKMatrix<T>
+    where T: MatrixElem
+{
+    pub nrows: u64,
+    pub ncols: u64,
+    pub(crate) elems: CoordMap<T>,
+    // Absent elements are returned from `Index` as a reference to this value.
+    zero: T,
+}
+
+impl<T> DOKMatrix<T>
+    where T: MatrixElem
+{
+    /// Create a DOKMatrix.
+    ///
+    /// Panics if any key of `elems` lies outside of the matrix.
+    ///
+    /// # Arguments
+    ///
+    /// * `nrows` - Number of rows in the new matrix.
+    /// * `ncols` - Number of columns in the returned matrix.
+    /// * `elems` - Map from indices of non-zero elements to values.
+    pub fn new(nrows: u64, ncols: u64, elems: CoordMap<T>) -> Self {
+        Self::try_new(nrows, ncols, elems).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Create a DOKMatrix, returning an error if any key of `elems` lies
+    /// outside of the matrix.
+    ///
+    /// # Arguments
+    ///
+    /// * `nrows` - Number of rows in the new matrix.
+    /// * `ncols` - Number of columns in the returned matrix.
+    /// * `elems` - Map from indices of non-zero elements to values.
+    pub fn try_new(nrows: u64, ncols: u64, elems: CoordMap<T>) -> Result<Self> {
+        let matrix = DOKMatrix {
+            nrows,
+            ncols,
+            elems,
+            zero: T::zero(),
+        };
+        for &(row, col) in matrix.elems.keys() {
+            matrix.check_in_bounds(row, col)?;
+        }
+        Ok(matrix)
+    }
+
+    /// Create a DOKMatrix with all zero elements.
+    ///
+    /// # Arguments
+    ///
+    /// * `nrows` - Number of rows in the new matrix.
+    /// * `ncols` - Number of columns in the returned matrix.
+    pub fn zeros(nrows: u64, ncols: u64) -> Self {
+        Self::new(nrows, ncols, CoordMap::<T>::new())
+    }
+
+    pub fn identity(size: u64) -> Self {
+        let mut map = HashMap::<Coords, T>::new();
+        for i in 0..size {
+            map.insert((i, i), T::one());
+        }
+        Self::new(size, size, map)
+    }
+
+    pub fn transposed(&self) -> Self {
+        let mut map = HashMap::<Coords, T>::new();
+        for (&(i, j), v) in &self.elems {
+            map.insert((j, i), v.clone());
+        }
+        Self::new(self.ncols, self.nrows, map)
+    }
+
+    /// Number of explicitly stored elements.
+    pub fn nnz(&self) -> usize {
+        self.elems.len()
+    }
+
+    /// Fraction of the matrix's elements that are explicitly stored.
+    ///
+    /// Matrices with no elements at all have a density of zero.
+    pub fn density(&self) -> f64 {
+        let size = self.nrows as f64 * self.ncols as f64;
+        if size == 0.0 {
+            return 0.0;
+        }
+        self.elems.len() as f64 / size
+    }
+
+    /// Iterate over the coordinates and values of the stored elements, in
+    /// arbitrary order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { inner: self.elems.iter() }
+    }
+
+    /// Iterate over the coordinates and mutable values of the stored
+    /// elements, in arbitrary order.
+    ///
+    /// This returns a guard, which is iterated by mutable reference, as in
+    /// `for (coords, v) in &mut m.iter_mut()`. Elements set to zero are
+    /// removed when the guard is dropped. For the same reason `&mut
+    /// DOKMatrix` does not implement `IntoIterator`.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut { elems: &mut self.elems }
+    }
+
+    /// Iterate over the coordinates and values of the stored elements,
+    /// sorted by coordinate in the given order.
+    ///
+    /// This sorts the stored elements, so it costs O(nnz log nnz).
+    pub fn iter_sorted(&self, order: Order) -> ::std::vec::IntoIter<(Coords, &T)> {
+        let mut elems: Vec<(Coords, &T)> = self.iter().collect();
+        match order {
+            Order::RowMajor => elems.sort_unstable_by_key(|&(coords, _)| coords),
+            Order::ColMajor => elems.sort_unstable_by_key(|&((i, j), _)| (j, i)),
+        }
+        elems.into_iter()
+    }
+
+    /// Iterate over the stored elements of row `row`, sorted by column.
+    ///
+    /// This scans every stored element, so it costs O(nnz). Convert to a
+    /// CsrMatrix for repeated row access. Panics if `row` lies outside of
+    /// the matrix.
+    pub fn row_iter(&self, row: u64) -> ::std::vec::IntoIter<(u64, &T)> {
+        if row >= self.nrows {
+            panic!("{}",
+                   Error::OutOfBounds {
+                       index: (row, 0),
+                       shape: (self.nrows, self.ncols),
+                   })
+        }
+        let mut elems: Vec<(u64, &T)> = self.iter()
+            .filter(|&((i, _), _)| i == row)
+            .map(|((_, j), v)| (j, v))
+            .collect();
+        elems.sort_unstable_by_key(|&(j, _)| j);
+        elems.into_iter()
+    }
+
+    /// Iterate over the stored elements of column `col`, sorted by row.
+    ///
+    /// This scans every stored element, so it costs O(nnz). Convert to a
+    /// CscMatrix for repeated column access. Panics if `col` lies outside
+    /// of the matrix.
+    pub fn col_iter(&self, col: u64) -> ::std::vec::IntoIter<(u64, &T)> {
+        if col >= self.ncols {
+            panic!("{}",
+                   Error::OutOfBounds {
+                       index: (0, col),
+                       shape: (self.nrows, self.ncols),
+                   })
+        }
+        let mut elems: Vec<(u64, &T)> = self.iter()
+            .filter(|&((_, j), _)| j == col)
+            .map(|((i, _), v)| (i, v))
+            .collect();
+        elems.sort_unstable_by_key(|&(i, _)| i);
+        elems.into_iter()
+    }
+
+    /// Get the element at coordinate (row, col), or `None` if (row, col)
+    /// lies outside of the matrix.
+    pub fn get(&self, row: u64, col: u64) -> Option<&T> {
+        if self.check_in_bounds(row, col).is_err() {
+            return None;
+        }
+        Some(self.elems.get(&(row, col)).unwrap_or(&self.zero))
+    }
+
+    /// Return an error if `(row, col)` lies outside of the matrix.
+    fn check_in_bounds(&self, row: u64, col: u64) -> Result<()> {
+        if row >= self.nrows || col >= self.ncols {
+            return Err(Error::OutOfBounds {
+                index: (row, col),
+                shape: (self.nrows, self.ncols),
+            });
+        }
+        Ok(())
+    }
+
+    /// Panic if `(row, col)` lies outside of the matrix.
+    fn assert_in_bounds(&self, row: u64, col: u64) {
+        if let Err(e) = self.check_in_bounds(row, col) {
+            panic!("{}", e)
+        }
+    }
+
+    /// Return an error if `other` does not have the same shape as `self`.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - Matrix to compare against.
+    /// * `op` - Name of the operation being performed, used in the error
+    ///   message.
+    fn check_same_shape(&self, other: &Self, op: &'static str) -> Result<()> {
+        if self.nrows != other.nrows || self.ncols != other.ncols {
+            return Err(Error::ShapeMismatch {
+                op,
+                left: (self.nrows, self.ncols),
+                right: (other.nrows, other.ncols),
+            });
+        }
+        Ok(())
+    }
+
+    /// Panic if `other` does not have the same shape as `self`.
+    fn assert_same_shape(&self, other: &Self, op: &'static str) {
+        if let Err(e) = self.check_same_shape(other, op) {
+            panic!("{}", e)
+        }
+    }
+}
+
+impl<T> DOKMatrix<T>
+    where T: MatrixElem
+{
+    /// Compute the matrix-vector product `self * x` over `semiring`.
+    ///
+    /// Panics if `x` does not have length `self.ncols`.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Dense vector of length `self.ncols`.
+    /// * `semiring` - Operations used to combine elements. Rows of `self`
+    ///   with no stored elements produce `semiring.zero()`.
+    pub fn mxv<S>(&self, x: &[T], semiring: S) -> Vec<T>
+        where S: Semiring<T>
+    {
+        self.try_mxv(x, semiring).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Compute the matrix-vector product `self * x` over `semiring`,
+    /// returning an error if `x` does not have length `self.ncols`.
+    pub fn try_mxv<S>(&self, x: &[T], semiring: S) -> Result<Vec<T>>
+        where S: Semiring<T>
+    {
+        self.check_vector_len(x, self.ncols, "matvec")?;
+        let mut out = vec![semiring.zero(); self.nrows as usize];
+        for (&(i, j), v) in &self.elems {
+            let (i, j) = (i as usize, j as usize);
+            let product = semiring.mul(v.clone(), x[j].clone());
+            out[i] = semiring.add(out[i].clone(), product);
+        }
+        Ok(out)
+    }
+
+    /// Compute the vector-matrix product `x^T * self` over `semiring`.
+    ///
+    /// Panics if `x` does not have length `self.nrows`.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Dense vector of length `self.nrows`.
+    /// * `semiring` - Operations used to combine elements. Columns of `self`
+    ///   with no stored elements produce `semiring.zero()`.
+    pub fn vxm<S>(&self, x: &[T], semiring: S) -> Vec<T>
+        where S: Semiring<T>
+    {
+        self.try_vxm(x, semiring).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Compute the vector-matrix product `x^T * self` over `semiring`,
+    /// returning an error if `x` does not have length `self.nrows`.
+    pub fn try_vxm<S>(&self, x: &[T], semiring: S) -> Result<Vec<T>>
+        where S: Semiring<T>
+    {
+        self.check_vector_len(x, self.nrows, "rmatvec")?;
+        let mut out = vec![semiring.zero(); self.ncols as usize];
+        for (&(i, j), v) in &self.elems {
+            let (i, j) = (i as usize, j as usize);
+            let product = semiring.mul(x[i].clone(), v.clone());
+            out[j] = semiring.add(out[j].clone(), product);
+        }
+        Ok(out)
+    }
+
+    /// Compute the matrix product `self * other` over `semiring`.
+    ///
+    /// Panics if `self.ncols != other.nrows`.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - Right-hand operand.
+    /// * `semiring` - Operations used to combine elements. Result elements
+    ///   equal to `semiring.zero()` are not stored.
+    pub fn mxm<S>(&self, other: &DOKMatrix<T>, semiring: S) -> DOKMatrix<T>
+        where S: Semiring<T>
+    {
+        self.try_mxm(other, semiring).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Compute the matrix product `self * other` over `semiring`, returning
+    /// an error if `self.ncols != other.nrows`.
+    ///
+    /// Only stored entries are visited: each stored `self[(i, k)]` is
+    /// combined with the stored entries in row `k` of `other`, so the cost is
+    /// proportional to the number of non-trivial partial products rather
+    /// than to the dense shape of either operand.
+    pub fn try_mxm<S>(&self, other: &DOKMatrix<T>, semiring: S) -> Result<DOKMatrix<T>>
+        where S: Semiring<T>
+    {
+        if self.ncols != other.nrows {
+            return Err(Error::ShapeMismatch {
+                op: "multiply",
+                left: (self.nrows, self.ncols),
+                right: (other.nrows, other.ncols),
+            });
+        }
+
+        // Group the entries of `other` by row so that each entry of
+        // `self` can find its partners without scanning all of `other`.
+        let mut other_rows = HashMap::<u64, Vec<(u64, &T)>>::new();
+        for (&(k, j), v) in &other.elems {
+            other_rows.entry(k).or_default().push((j, v));
+        }
+
+        let mut map = CoordMap::<T>::new();
+        for (&(i, k), left) in &self.elems {
+            if let Some(row) = other_rows.get(&k) {
+                for &(j, right) in row {
+                    let product = semiring.mul(left.clone(), right.clone());
+                    let acc = map.entry((i, j)).or_insert_with(|| semiring.zero());
+                    *acc = semiring.add(acc.clone(), product);
+                }
+            }
+        }
+        map.retain(|_, v| !semiring.is_zero(v));
+        Ok(DOKMatrix::new(self.nrows, other.ncols, map))
+    }
+
+    /// Return an error if `x` does not have length `expected`.
+    fn check_vector_len(&self, x: &[T], expected: u64, op: &'static str) -> Result<()> {
+        if x.len() as u64 != expected {
+            return Err(Error::ShapeMismatch {
+                op,
+                left: (self.nrows, self.ncols),
+                right: (x.len() as u64, 1),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl<T> DOKMatrix<T>
+    where T: MatrixElem + Add<Output = T> + Mul<Output = T>
+{
+    /// Compute the matrix-vector product `self * x`.
+    ///
+    /// Panics if `x` does not have length `self.ncols`.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Dense vector of length `self.ncols`.
+    pub fn matvec(&self, x: &[T]) -> Vec<T> {
+        self.mxv(x, PlusTimes)
+    }
+
+    /// Compute the matrix-vector product `self * x`, returning an error if
+    /// `x` does not have length `self.ncols`.
+    pub fn try_matvec(&self, x: &[T]) -> Result<Vec<T>> {
+        self.try_mxv(x, PlusTimes)
+    }
+
+    /// Compute the transposed matrix-vector product `self^T * x`.
+    ///
+    /// Panics if `x` does not have length `self.nrows`.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Dense vector of length `self.nrows`.
+    pub fn rmatvec(&self, x: &[T]) -> Vec<T> {
+        self.vxm(x, PlusTimes)
+    }
+
+    /// Compute the transposed matrix-vector product `self^T * x`, returning
+    /// an error if `x` does not have length `self.nrows`.
+    pub fn try_rmatvec(&self, x: &[T]) -> Result<Vec<T>> {
+        self.try_vxm(x, PlusTimes)
+    }
+
+    /// Compute the product of `self` with a dense matrix.
+    ///
+    /// Both the input and the output are stored as row-major buffers.
+    ///
+    /// # Arguments
+    ///
+    /// * `b` - Row-major buffer holding a dense matrix of shape
+    ///   `(self.ncols, b_ncols)`.
+    /// * `b_ncols` - Number of columns in `b`.
+    ///
+    /// Returns a row-major buffer of shape `(self.nrows, b_ncols)`.
+    pub fn matmat(&self, b: &[T], b_ncols: usize) -> Vec<T> {
+        self.try_matmat(b, b_ncols).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Compute the product of `self` with a dense matrix, returning an error
+    /// if `b` does not hold a matrix of shape `(self.ncols, b_ncols)`.
+    pub fn try_matmat(&self, b: &[T], b_ncols: usize) -> Result<Vec<T>> {
+        if !b.len().is_multiple_of(b_ncols) {
+            let message = format!("Dense buffer of length {} does not hold a whole number \
+                                   of rows of length {}",
+                                  b.len(),
+                                  b_ncols);
+            return Err(Error::InvalidStructure(message));
+        }
+        if b.len() != self.ncols as usize * b_ncols {
+            return Err(Error::ShapeMismatch {
+                op: "multiply",
+                left: (self.nrows, self.ncols),
+                right: ((b.len() / b_ncols) as u64, b_ncols as u64),
+            });
+        }
+        let mut out = vec![T::zero(); self.nrows as usize * b_ncols];
+        for (&(i, k), v) in &self.elems {
+            let src = &b[k as usize * b_ncols..(k as usize + 1) * b_ncols];
+            let dst = &mut out[i as usize * b_ncols..(i as usize + 1) * b_ncols];
+            for (d, s) in dst.iter_mut().zip(src) {
+                *d = d.clone() + v.clone() * s.clone();
+            }
+        }
+        Ok(out)
+    }
+}
+
+impl<T> DOKMatrix<T>
+    where T: MatrixElem
+{
+    /// Whether `self` and `other` have the same shape and store elements at
+    /// exactly the same coordinates, regardless of their values.
+    ///
+    /// Explicitly stored zeros count as part of the pattern.
+    pub fn same_pattern<U>(&self, other: &DOKMatrix<U>) -> bool
+        where U: MatrixElem
+    {
+        self.nrows == other.nrows && self.ncols == other.ncols &&
+        self.elems.len() == other.elems.len() &&
+        self.elems.keys().all(|coords| other.elems.contains_key(coords))
+    }
+}
+
+impl<T> PartialEq for DOKMatrix<T>
+    where T: MatrixElem + PartialEq
+{
+    /// Two matrices are equal if they have the same shape and equal elements
+    /// at every coordinate, treating absent elements as zero.
+    ///
+    /// Explicitly stored zeros are therefore equal to absent elements. This
+    /// only visits stored elements, so it costs O(nnz).
+    fn eq(&self, other: &DOKMatrix<T>) -> bool {
+        if self.nrows != other.nrows || self.ncols != other.ncols {
+            return false;
+        }
+        self.elems.iter().all(|(coords, v)| match other.elems.get(coords) {
+            Some(w) => v == w,
+            None => v.is_zero(),
+        }) &&
+        other.elems
+            .iter()
+            .filter(|&(coords, _)| !self.elems.contains_key(coords))
+            .all(|(_, w)| w.is_zero())
+    }
+}
+
+impl<T> DOKMatrix<T>
+    where T: MatrixElem + Copy + Into<f64>
+{
+    /// Whether `self` and `other` have the same shape and every pair of
+    /// elements `a`, `b` satisfies `|a - b| <= atol + rtol * |b|`.
+    ///
+    /// Absent elements are treated as zero, and NaN is never close to
+    /// anything. This only visits stored elements, so it costs O(nnz).
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - Matrix to compare against.
+    /// * `rtol` - Relative tolerance, scaled by the magnitude of the element
+    ///   of `other`.
+    /// * `atol` - Absolute tolerance.
+    pub fn approx_eq(&self, other: &DOKMatrix<T>, rtol: f64, atol: f64) -> bool {
+        if self.nrows != other.nrows || self.ncols != other.ncols {
+            return false;
+        }
+        let close = |a: f64, b: f64| a == b || (a - b).abs() <= atol + rtol * b.abs();
+        let value = |m: &DOKMatrix<T>, coords| m.elems.get(coords).map_or(0.0, |&v| v.into());
+        self.elems.iter().all(|(coords, &v)| close(v.into(), value(other, coords))) &&
+        other.elems
+            .iter()
+            .filter(|&(coords, _)| !self.elems.contains_key(coords))
+            .all(|(_, &w)| close(0.0, w.into()))
+    }
+}
+
+impl<T> Index<(u64, u64)> for DOKMatrix<T>
+    where T: MatrixElem
+{
+    type Output = T;
+
+    /// Get the element at coordinate (row, col).
+    fn index(&self, (row, col): (u64, u64)) -> &T {
+        self.assert_in_bounds(row, col);
+        self.elems.get(&(row, col)).unwrap_or(&self.zero)
+    }
+}
+
+impl<T> DOKMatrix<T>
+    where T: MatrixElem
+{
+    /// Set the element at coordinate (row, col).
+    ///
+    /// Setting an element to zero removes it from the matrix.
+    pub fn set(&mut self, row: u64, col: u64, value: T) {
+        self.entry((row, col)).insert(value);
+    }
+
+    /// Set the element at coordinate (row, col), returning an error if
+    /// (row, col) lies outside of the matrix.
+    pub fn try_set(&mut self, row: u64, col: u64, value: T) -> Result<()> {
+        self.try_entry((row, col))?.insert(value);
+        Ok(())
+    }
+
+    /// Remove the element at coordinate (row, col), leaving a zero.
+    ///
+    /// Returns the previously stored value, if any.
+    pub fn remove(&mut self, row: u64, col: u64) -> Option<T> {
+        self.try_remove(row, col).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Remove the element at coordinate (row, col), returning an error if
+    /// (row, col) lies outside of the matrix.
+    pub fn try_remove(&mut self, row: u64, col: u64) -> Result<Option<T>> {
+        self.check_in_bounds(row, col)?;
+        Ok(self.elems.remove(&(row, col)))
+    }
+
+    /// Get a mutable reference to the element at coordinate (row, col), or
+    /// `None` if (row, col) lies outside of the matrix.
+    ///
+    /// Absent elements read as zero. The element is written back when the
+    /// returned guard is dropped, and removed if it is zero.
+    pub fn get_mut(&mut self, row: u64, col: u64) -> Option<ElemMut<'_, T>> {
+        if row >= self.nrows || col >= self.ncols {
+            return None;
+        }
+        let value = self.elems.remove(&(row, col)).unwrap_or_else(T::zero);
+        Some(ElemMut {
+            elems: &mut self.elems,
+            coords: (row, col),
+            value,
+        })
+    }
+
+    /// Get the entry at coordinate (row, col) for in-place manipulation.
+    pub fn entry(&mut self, coords: Coords) -> Entry<'_, T> {
+        self.try_entry(coords).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Get the entry at coordinate (row, col), returning an error if
+    /// (row, col) lies outside of the matrix.
+    pub fn try_entry(&mut self, (row, col): Coords) -> Result<Entry<'_, T>> {
+        self.check_in_bounds(row, col)?;
+        Ok(Entry {
+            elems: &mut self.elems,
+            zero: &self.zero,
+            coords: (row, col),
+        })
+    }
+}
+
+/// Order in which to visit the elements of a matrix.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Order {
+    /// Sort by row, then by column.
+    RowMajor,
+    /// Sort by column, then by row.
+    ColMajor,
+}
+
+/// Direction in which to reduce a matrix.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Axis {
+    /// Reduce along each row, producing one result per row.
+    Rows,
+    /// Reduce along each column, producing one result per column.
+    Cols,
+}
+
+/// Iterator over the stored elements of a DOKMatrix.
+pub struct Iter<'a, T: 'a> {
+    inner: hash_map::Iter<'a, Coords, T>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = (Coords, &'a T);
+
+    fn next(&mut self) -> Option<(Coords, &'a T)> {
+        self.inner.next().map(|(&coords, v)| (coords, v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+/// Guard giving mutable access to the stored elements of a DOKMatrix,
+/// returned by `DOKMatrix::iter_mut`.
+///
+/// Iterating over `&mut IterMut` yields the coordinates and mutable values
+/// of the stored elements. Any that are zero when the guard is dropped are
+/// removed from the matrix.
+pub struct IterMut<'a, T: 'a>
+    where T: MatrixElem
+{
+    elems: &'a mut CoordMap<T>,
+}
+
+impl<'a, T> Drop for IterMut<'a, T>
+    where T: MatrixElem
+{
+    fn drop(&mut self) {
+        self.elems.retain(|_, v| !v.is_zero());
+    }
+}
+
+impl<'a, 'b, T> IntoIterator for &'b mut IterMut<'a, T>
+    where T: MatrixElem
+{
+    type Item = (Coords, &'b mut T);
+    type IntoIter = ElemsMut<'b, T>;
+
+    fn into_iter(self) -> ElemsMut<'b, T> {
+        ElemsMut { inner: self.elems.iter_mut() }
+    }
+}
+
+/// Mutable iterator over the stored elements of a DOKMatrix, borrowed from
+/// an `IterMut` guard.
+pub struct ElemsMut<'a, T: 'a> {
+    inner: hash_map::IterMut<'a, Coords, T>,
+}
+
+impl<'a, T> Iterator for ElemsMut<'a, T> {
+    type Item = (Coords, &'a mut T);
+
+    fn next(&mut self) -> Option<(Coords, &'a mut T)> {
+        self.inner.next().map(|(&coords, v)| (coords, v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T> ExactSizeIterator for ElemsMut<'a, T> {}
+
+/// Owning iterator over the stored elements of a DOKMatrix.
+pub struct IntoIter<T> {
+    inner: hash_map::IntoIter<Coords, T>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = (Coords, T);
+
+    fn next(&mut self) -> Option<(Coords, T)> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+impl<T> IntoIterator for DOKMatrix<T>
+    where T: MatrixElem
+{
+    type Item = (Coords, T);
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter { inner: self.elems.into_iter() }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a DOKMatrix<T>
+    where T: MatrixElem
+{
+    type Item = (Coords, &'a T);
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+/// A view into a single element of a DOKMatrix.
+///
+/// This is similar to `std::collections::hash_map::Entry`, except that
+/// absent elements read as zero and any write of zero removes the element
+/// from the matrix instead of storing it.
+pub struct Entry<'a, T: 'a>
+    where T: MatrixElem
+{
+    elems: &'a mut CoordMap<T>,
+    zero: &'a T,
+    coords: Coords,
+}
+
+impl<'a, T> Entry<'a, T>
+    where T: MatrixElem
+{
+    /// Get the coordinate of this entry.
+    pub fn key(&self) -> Coords {
+        self.coords
+    }
+
+    /// Get the current value of this entry.
+    pub fn get(&self) -> &T {
+        self.elems.get(&self.coords).unwrap_or(self.zero)
+    }
+
+    /// Whether a value is explicitly stored for this entry.
+    pub fn is_stored(&self) -> bool {
+        self.elems.contains_key(&self.coords)
+    }
+
+    /// Set the value of this entry, returning the previous value.
+    pub fn insert(self, value: T) -> T {
+        let previous = if value.is_zero() {
+            self.elems.remove(&self.coords)
+        } else {
+            self.elems.insert(self.coords, value)
+        };
+        previous.unwrap_or_else(T::zero)
+    }
+
+    /// Remove this entry, returning the previous value.
+    pub fn remove(self) -> T {
+        self.elems.remove(&self.coords).unwrap_or_else(T::zero)
+    }
+
+    /// Modify the stored value of this entry in place, if there is one.
+    ///
+    /// If `f` sets the value to zero, the entry is removed.
+    pub fn and_modify<F>(self, f: F) -> Self
+        where F: FnOnce(&mut T)
+    {
+        let mut removed = false;
+        if let Some(value) = self.elems.get_mut(&self.coords) {
+            f(value);
+            removed = value.is_zero();
+        }
+        if removed {
+            self.elems.remove(&self.coords);
+        }
+        self
+    }
+
+    /// Replace the value of this entry with `f` applied to its current
+    /// value, treating an absent value as zero.
+    ///
+    /// Returns the new value. If it is zero, the entry is removed.
+    pub fn update<F>(self, f: F) -> T
+        where F: FnOnce(T) -> T
+    {
+        let value = f(self.get().clone());
+        self.insert(value.clone());
+        value
+    }
+}
+
+/// A mutable reference to a single element of a DOKMatrix, returned by
+/// `DOKMatrix::get_mut`.
+///
+/// The value is held by the guard and stored back into the matrix when the
+/// guard is dropped, unless it is zero.
+pub struct ElemMut<'a, T: 'a>
+    where T: MatrixElem
+{
+    elems: &'a mut CoordMap<T>,
+    coords: Coords,
+    value: T,
+}
+
+impl<'a, T> Deref for ElemMut<'a, T>
+    where T: MatrixElem
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<'a, T> DerefMut for ElemMut<'a, T>
+    where T: MatrixElem
+{
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<'a, T> Drop for ElemMut<'a, T>
+    where T: MatrixElem
+{
+    fn drop(&mut self) {
+        let value = mem::replace(&mut self.value, T::zero());
+        if !value.is_zero() {
+            self.elems.insert(self.coords, value);
+        }
+    }
+}
+
+impl<T> DOKMatrix<T>
+    where T: MatrixElem
+{
+    /// Combine `self` and `other` elementwise over the union of their
+    /// patterns.
+    ///
+    /// `f` is applied at every coordinate stored in either matrix, with an
+    /// absent element passed as zero. Results equal to zero are not stored.
+    /// Panics if the shapes differ.
+    pub fn ewise_union_with<F>(&self, other: &DOKMatrix<T>, f: F) -> DOKMatrix<T>
+        where F: Fn(T, T) -> T
+    {
+        self.try_ewise_union_with(other, f).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Combine `self` and `other` elementwise over the union of their
+    /// patterns, returning an error if the shapes differ.
+    pub fn try_ewise_union_with<F>(&self, other: &DOKMatrix<T>, f: F) -> Result<DOKMatrix<T>>
+        where F: Fn(T, T) -> T
+    {
+        self.check_same_shape(other, "combine")?;
+        let mut map = CoordMap::<T>::with_capacity(self.elems.len().max(other.elems.len()));
+        for (&coords, v) in &self.elems {
+            let w = other.elems.get(&coords).cloned().unwrap_or_else(T::zero);
+            map.insert(coords, f(v.clone(), w));
+        }
+        for (&coords, w) in &other.elems {
+            if !self.elems.contains_key(&coords) {
+                map.insert(coords, f(T::zero(), w.clone()));
+            }
+        }
+        map.retain(|_, v| !v.is_zero());
+        Ok(DOKMatrix::new(self.nrows, self.ncols, map))
+    }
+
+    /// Combine `self` and `other` elementwise over the intersection of their
+    /// patterns.
+    ///
+    /// `f` is applied only at coordinates stored in both matrices; all other
+    /// elements of the result are zero. Results equal to zero are not
+    /// stored. Panics if the shapes differ.
+    pub fn ewise_intersect_with<F>(&self, other: &DOKMatrix<T>, f: F) -> DOKMatrix<T>
+        where F: Fn(T, T) -> T
+    {
+        self.try_ewise_intersect_with(other, f).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Combine `self` and `other` elementwise over the intersection of their
+    /// patterns, returning an error if the shapes differ.
+    pub fn try_ewise_intersect_with<F>(&self,
+                                       other: &DOKMatrix<T>,
+                                       f: F)
+                                       -> Result<DOKMatrix<T>>
+        where F: Fn(T, T) -> T
+    {
+        self.check_same_shape(other, "combine")?;
+        // Probe the matrix with more elements using those of the other.
+        let swapped = self.elems.len() > other.elems.len();
+        let (small, large) = if swapped { (other, self) } else { (self, other) };
+        let mut map = CoordMap::<T>::with_capacity(small.elems.len());
+        for (&coords, v) in &small.elems {
+            if let Some(w) = large.elems.get(&coords) {
+                let (left, right) = if swapped { (w, v) } else { (v, w) };
+                let value = f(left.clone(), right.clone());
+                if !value.is_zero() {
+                    map.insert(coords, value);
+                }
+            }
+        }
+        Ok(DOKMatrix::new(self.nrows, self.ncols, map))
+    }
+}
+
+impl<T> DOKMatrix<T>
+    where T: MatrixElem
+{
+    /// Apply `f` to every stored element, leaving absent elements as zero.
+    ///
+    /// Results equal to zero are not stored. Use `map_dense` if `f` does not
+    /// map zero to zero.
+    pub fn map<U, F>(&self, mut f: F) -> DOKMatrix<U>
+        where U: MatrixElem,
+              F: FnMut(&T) -> U
+    {
+        self.map_with_index(|_, v| f(v))
+    }
+
+    /// Apply `f` to the coordinates and value of every stored element,
+    /// leaving absent elements as zero.
+    ///
+    /// Results equal to zero are not stored.
+    pub fn map_with_index<U, F>(&self, mut f: F) -> DOKMatrix<U>
+        where U: MatrixElem,
+              F: FnMut(Coords, &T) -> U
+    {
+        let mut map = HashMap::<Coords, U>::with_capacity(self.elems.len());
+        for (&coords, v) in &self.elems {
+            let value = f(coords, v);
+            if !value.is_zero() {
+                map.insert(coords, value);
+            }
+        }
+        DOKMatrix::new(self.nrows, self.ncols, map)
+    }
+
+    /// Replace every stored element with `f` applied to it, in place.
+    ///
+    /// Elements that become zero are removed.
+    pub fn apply<F>(&mut self, mut f: F)
+        where F: FnMut(&T) -> T
+    {
+        for v in self.elems.values_mut() {
+            *v = f(v);
+        }
+        self.elems.retain(|_, v| !v.is_zero());
+    }
+
+    /// Apply `f` to every element of the matrix, including absent ones.
+    ///
+    /// If `f` maps zero to zero this is the same as `map`. Otherwise every
+    /// element of the result is stored, so this panics if the matrix has
+    /// more than `MAX_DENSE_ELEMS` elements.
+    pub fn map_dense<U, F>(&self, f: F) -> DOKMatrix<U>
+        where U: MatrixElem,
+              F: FnMut(&T) -> U
+    {
+        self.try_map_dense(f).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Apply `f` to every element of the matrix, including absent ones,
+    /// returning an error if that would store more than `MAX_DENSE_ELEMS`
+    /// elements.
+    pub fn try_map_dense<U, F>(&self, mut f: F) -> Result<DOKMatrix<U>>
+        where U: MatrixElem,
+              F: FnMut(&T) -> U
+    {
+        let fill = f(&self.zero);
+        if fill.is_zero() {
+            return Ok(self.map(f));
+        }
+        let size = self.nrows.checked_mul(self.ncols);
+        if size.is_none_or(|size| size > MAX_DENSE_ELEMS) {
+            return Err(Error::TooDense {
+                shape: (self.nrows, self.ncols),
+                limit: MAX_DENSE_ELEMS,
+            });
+        }
+        let mut map = HashMap::<Coords, U>::with_capacity(size.unwrap_or(0) as usize);
+        for i in 0..self.nrows {
+            for j in 0..self.ncols {
+                let value = match self.elems.get(&(i, j)) {
+                    Some(v) => f(v),
+                    None => fill.clone(),
+                };
+                if !value.is_zero() {
+                    map.insert((i, j), value);
+                }
+            }
+        }
+        Ok(DOKMatrix::new(self.nrows, self.ncols, map))
+    }
+}
+
+impl<T> DOKMatrix<T>
+    where T: MatrixElem
+{
+    /// Number of results produced when reducing along `axis`, and the length
+    /// of each reduced line.
+    fn axis_lens(&self, axis: Axis) -> (u64, u64) {
+        match axis {
+            Axis::Rows => (self.nrows, self.ncols),
+            Axis::Cols => (self.ncols, self.nrows),
+        }
+    }
+
+    /// Split `coords` into the index of its line along `axis` and its
+    /// position within that line.
+    fn axis_split(axis: Axis, (i, j): Coords) -> (u64, u64) {
+        match axis {
+            Axis::Rows => (i, j),
+            Axis::Cols => (j, i),
+        }
+    }
+
+    /// Count the stored elements of each row or column.
+    pub fn nnz_axis(&self, axis: Axis) -> Vec<usize> {
+        let (nlines, _) = self.axis_lens(axis);
+        let mut out = vec![0; nlines as usize];
+        for &coords in self.elems.keys() {
+            let (line, _) = Self::axis_split(axis, coords);
+            out[line as usize] += 1;
+        }
+        out
+    }
+
+    /// Fold the stored elements of each row or column with `f`, starting
+    /// from `init` in every line.
+    fn fold_axis<F>(&self, axis: Axis, init: T, mut f: F) -> Vec<T>
+        where F: FnMut(T, &T) -> T
+    {
+        let (nlines, _) = self.axis_lens(axis);
+        let mut out = vec![init; nlines as usize];
+        for (&coords, v) in &self.elems {
+            let (line, _) = Self::axis_split(axis, coords);
+            let slot = &mut out[line as usize];
+            *slot = f(slot.clone(), v);
+        }
+        out
+    }
+}
+
+impl<T> DOKMatrix<T>
+    where T: MatrixElem + Add<Output = T>
+{
+    /// Sum all elements of the matrix.
+    pub fn sum(&self) -> T {
+        self.elems.values().fold(T::zero(), |acc, v| acc + v.clone())
+    }
+
+    /// Sum each row or column of the matrix.
+    ///
+    /// `Axis::Rows` gives a vector of length `self.nrows` holding the sum of
+    /// each row, for example the out-degrees of a graph's adjacency matrix.
+    pub fn sum_axis(&self, axis: Axis) -> Vec<T> {
+        self.fold_axis(axis, T::zero(), |acc, v| acc + v.clone())
+    }
+}
+
+impl<T> DOKMatrix<T>
+    where T: MatrixElem + Add<Output = T> + Div<Output = T>
+{
+    /// Average each row or column of the matrix, counting absent elements
+    /// as zero.
+    ///
+    /// Lines of length zero have a mean of zero. The line length must fit in
+    /// `T`; for a narrow integer type such as `u8`, a longer line overflows
+    /// when the length is converted, which panics in debug builds.
+    pub fn mean_axis(&self, axis: Axis) -> Vec<T> {
+        let (_, len) = self.axis_lens(axis);
+        let mut sums = self.sum_axis(axis);
+        if len > 0 {
+            let len = from_count::<T>(len);
+            for v in &mut sums {
+                *v = v.clone() / len.clone();
+            }
+        }
+        sums
+    }
+}
+
+impl<T> DOKMatrix<T>
+    where T: MatrixElem + PartialOrd
+{
+    /// Find the largest element of each row or column, counting absent
+    /// elements as zero.
+    ///
+    /// Lines of length zero have a maximum of zero.
+    pub fn max_axis(&self, axis: Axis) -> Vec<T> {
+        self.extreme_axis(axis, |a, b| a > b)
+    }
+
+    /// Find the smallest element of each row or column, counting absent
+    /// elements as zero.
+    ///
+    /// Lines of length zero have a minimum of zero.
+    pub fn min_axis(&self, axis: Axis) -> Vec<T> {
+        self.extreme_axis(axis, |a, b| a < b)
+    }
+
+    /// Find the position of the largest element of each row or column,
+    /// counting absent elements as zero.
+    ///
+    /// `Axis::Rows` gives the column index of the maximum of each row. Ties
+    /// are broken in favor of the smallest index, and lines of length zero
+    /// report index zero.
+    pub fn argmax_axis(&self, axis: Axis) -> Vec<u64> {
+        let (nlines, len) = self.axis_lens(axis);
+        let mut lines = vec![Vec::<(u64, &T)>::new(); nlines as usize];
+        for (&coords, v) in &self.elems {
+            let (line, pos) = Self::axis_split(axis, coords);
+            lines[line as usize].push((pos, v));
+        }
+        lines.into_iter()
+            .map(|mut line| {
+                line.sort_unstable_by_key(|&(pos, _)| pos);
+                let mut best = line.first().cloned();
+                for &(pos, v) in line.iter().skip(1) {
+                    if best.is_some_and(|(_, b)| v > b) {
+                        best = Some((pos, v));
+                    }
+                }
+                if (line.len() as u64) < len {
+                    // The line holds an implicit zero; the first one is the
+                    // lowest gap in the sorted positions.
+                    let gap = line.iter()
+                        .enumerate()
+                        .find(|&(k, &(pos, _))| pos != k as u64)
+                        .map_or(line.len() as u64, |(k, _)| k as u64);
+                    best = match best {
+                        Some((pos, v)) if *v > self.zero || (*v == self.zero && pos < gap) => {
+                            Some((pos, v))
+                        }
+                        _ => Some((gap, &self.zero)),
+                    };
+                }
+                best.map_or(0, |(pos, _)| pos)
+            })
+            .collect()
+    }
+
+    /// Reduce each line to the element that `better` prefers, counting
+    /// absent elements as zero.
+    fn extreme_axis<F>(&self, axis: Axis, better: F) -> Vec<T>
+        where F: Fn(&T, &T) -> bool
+    {
+        let (nlines, len) = self.axis_lens(axis);
+        let mut best: Vec<Option<T>> = vec![None; nlines as usize];
+        for (&coords, v) in &self.elems {
+            let (line, _) = Self::axis_split(axis, coords);
+            let slot = &mut best[line as usize];
+            if slot.as_ref().is_none_or(|b| better(v, b)) {
+                *slot = Some(v.clone());
+            }
+        }
+        let counts = self.nnz_axis(axis);
+        best.into_iter()
+            .zip(counts)
+            .map(|(b, count)| match b {
+                Some(b) if count as u64 == len || better(&b, &self.zero) => b,
+                _ => T::zero(),
+            })
+            .collect()
+    }
+}
+
+/// Convert the count `n` to an element, by summing ones.
+///
+/// This takes O(log n) additions.
+fn from_count<T>(mut n: u64) -> T
+    where T: MatrixElem + Add<Output = T>
+{
+    let mut out = T::zero();
+    let mut power = T::one();
+    while n > 0 {
+        if n & 1 == 1 {
+            out = out + power.clone();
+        }
+        n >>= 1;
+        if n > 0 {
+            power = power.clone() + power;
+        }
+    }
+    out
+}
+
+impl<T> MulAssign<T> for DOKMatrix<T>
+    where T: MatrixElem + Mul<Output = T>
+{
+    /// Multiply every element by the scalar `other`.
+    ///
+    /// Elements that become zero are removed.
+    fn mul_assign(&mut self, other: T) {
+        self.apply(|v| v.clone() * other.clone());
+    }
+}
+
+impl<T> Mul<T> for DOKMatrix<T>
+    where T: MatrixElem + Mul<Output = T>
+{
+    type Output = DOKMatrix<T>;
+
+    fn mul(mut self, other: T) -> DOKMatrix<T> {
+        self *= other;
+        self
+    }
+}
+
+impl<T> Mul<T> for &DOKMatrix<T>
+    where T: MatrixElem + Mul<Output = T>
+{
+    type Output = DOKMatrix<T>;
+
+    fn mul(self, other: T) -> DOKMatrix<T> {
+        self.map(|v| v.clone() * other.clone())
+    }
+}
+
+impl<T> DivAssign<T> for DOKMatrix<T>
+    where T: MatrixElem + Div<Output = T>
+{
+    /// Divide every element by the scalar `other`.
+    ///
+    /// Only stored elements are divided, so dividing by zero does not fill
+    /// the matrix. Elements that become zero are removed.
+    fn div_assign(&mut self, other: T) {
+        self.apply(|v| v.clone() / other.clone());
+    }
+}
+
+impl<T> Div<T> for DOKMatrix<T>
+    where T: MatrixElem + Div<Output = T>
+{
+    type Output = DOKMatrix<T>;
+
+    fn div(mut self, other: T) -> DOKMatrix<T> {
+        self /= other;
+        self
+    }
+}
+
+impl<T> Div<T> for &DOKMatrix<T>
+    where T: MatrixElem + Div<Output = T>
+{
+    type Output = DOKMatrix<T>;
+
+    fn div(self, other: T) -> DOKMatrix<T> {
+        self.map(|v| v.clone() / other.clone())
+    }
+}
+
+impl<T> DOKMatrix<T>
+    where T: MatrixElem + Add<Output = T>
+{
+    /// Compute the elementwise sum of `self` and `other`.
+    ///
+    /// This is the same as `self + other`. Panics if the shapes differ.
+    pub fn ewise_add(&self, other: &DOKMatrix<T>) -> DOKMatrix<T> {
+        self.ewise_union_with(other, |a, b| a + b)
+    }
+
+    /// Compute the elementwise sum of `self` and `other`, returning an error
+    /// if the shapes differ.
+    pub fn try_ewise_add(&self, other: &DOKMatrix<T>) -> Result<DOKMatrix<T>> {
+        self.try_ewise_union_with(other, |a, b| a + b)
+    }
+}
+
+impl<T> DOKMatrix<T>
+    where T: MatrixElem + Mul<Output = T>
+{
+    /// Compute the elementwise (Hadamard) product of `self` and `other`.
+    ///
+    /// The result stores at most the coordinates stored in both operands.
+    /// Panics if the shapes differ.
+    pub fn hadamard(&self, other: &DOKMatrix<T>) -> DOKMatrix<T> {
+        self.ewise_intersect_with(other, |a, b| a * b)
+    }
+
+    /// Compute the elementwise (Hadamard) product of `self` and `other`,
+    /// returning an error if the shapes differ.
+    pub fn try_hadamard(&self, other: &DOKMatrix<T>) -> Result<DOKMatrix<T>> {
+        self.try_ewise_intersect_with(other, |a, b| a * b)
+    }
+}
+
+impl<T> DOKMatrix<T>
+    where T: MatrixElem + Div<Output = T>
+{
+    /// Divide `self` by `other` elementwise.
+    ///
+    /// Only coordinates stored in both operands are divided. Elements of
+    /// `self` where `other` is zero are dropped rather than divided by zero.
+    /// Panics if the shapes differ.
+    pub fn ewise_div(&self, other: &DOKMatrix<T>) -> DOKMatrix<T> {
+        self.ewise_intersect_with(other, |a, b| a / b)
+    }
+
+    /// Divide `self` by `other` elementwise, returning an error if the
+    /// shapes differ.
+    pub fn try_ewise_div(&self, other: &DOKMatrix<T>) -> Result<DOKMatrix<T>> {
+        self.try_ewise_intersect_with(other, |a, b| a / b)
+    }
+}
+
+impl<T> DOKMatrix<T>
+    where T: MatrixElem + PartialOrd
+{
+    /// Compute the elementwise minimum of `self` and `other`, treating
+    /// absent elements as zero.
+    ///
+    /// Panics if the shapes differ.
+    pub fn ewise_min(&self, other: &DOKMatrix<T>) -> DOKMatrix<T> {
+        self.ewise_union_with(other, semiring::min)
+    }
+
+    /// Compute the elementwise minimum of `self` and `other`, returning an
+    /// error if the shapes differ.
+    pub fn try_ewise_min(&self, other: &DOKMatrix<T>) -> Result<DOKMatrix<T>> {
+        self.try_ewise_union_with(other, semiring::min)
+    }
+
+    /// Compute the elementwise maximum of `self` and `other`, treating
+    /// absent elements as zero.
+    ///
+    /// Panics if the shapes differ.
+    pub fn ewise_max(&self, other: &DOKMatrix<T>) -> DOKMatrix<T> {
+        self.ewise_union_with(other, semiring::max)
+    }
+
+    /// Compute the elementwise maximum of `self` and `other`, returning an
+    /// error if the shapes differ.
+    pub fn try_ewise_max(&self, other: &DOKMatrix<T>) -> Result<DOKMatrix<T>> {
+        self.try_ewise_union_with(other, semiring::max)
+    }
+}
+
+impl<T> DOKMatrix<T>
+    where T: MatrixElem + Add<Output = T>
+{
+    /// Compute `self + other`, returning an error if the shapes differ.
+    pub fn try_add(&self, other: &DOKMatrix<T>) -> Result<DOKMatrix<T>> {
+        self.check_same_shape(other, "add")?;
+        Ok(self + other)
+    }
+}
+
+impl<'a, T> AddAssign<&'a DOKMatrix<T>> for DOKMatrix<T>
+    where T: MatrixElem + Add<Output = T>
+{
+    /// Add `other` into `self` elementwise.
+    ///
+    /// Entries that cancel to zero are removed from `self`.
+    fn add_assign(&mut self, other: &'a DOKMatrix<T>) {
+        self.assert_same_shape(other, "add");
+        for (&coords, v) in &other.elems {
+            let sum = match self.elems.remove(&coords) {
+                Some(existing) => existing + v.clone(),
+                None => v.clone(),
+            };
+            if sum.is_zero() {
+                self.elems.remove(&coords);
+            } else {
+                self.elems.insert(coords, sum);
+            }
+        }
+    }
+}
+
+impl<T> AddAssign<DOKMatrix<T>> for DOKMatrix<T>
+    where T: MatrixElem + Add<Output = T>
+{
+    fn add_assign(&mut self, other: DOKMatrix<T>) {
+        *self += &other;
+    }
+}
+
+impl<'a, T> Add<&'a DOKMatrix<T>> for DOKMatrix<T>
+    where T: MatrixElem + Add<Output = T>
+{
+    type Output = DOKMatrix<T>;
+
+    fn add(mut self, other: &'a DOKMatrix<T>) -> DOKMatrix<T> {
+        self += other;
+        self
+    }
+}
+
+impl<T> Add<DOKMatrix<T>> for DOKMatrix<T>
+    where T: MatrixElem + Add<Output = T>
+{
+    type Output = DOKMatrix<T>;
+
+    fn add(mut self, other: DOKMatrix<T>) -> DOKMatrix<T> {
+        self += &other;
+        self
+    }
+}
+
+impl<'b, T> Add<&'b DOKMatrix<T>> for &DOKMatrix<T>
+    where T: MatrixElem + Add<Output = T>
+{
+    type Output = DOKMatrix<T>;
+
+    fn add(self, other: &'b DOKMatrix<T>) -> DOKMatrix<T> {
+        let mut result = self.clone();
+        result += other;
+        result
+    }
+}
+
+impl<T> DOKMatrix<T>
+    where T: MatrixElem + Sub<Output = T>
+{
+    /// Compute `self - other`, returning an error if the shapes differ.
+    pub fn try_sub(&self, other: &DOKMatrix<T>) -> Result<DOKMatrix<T>> {
+        self.check_same_shape(other, "subtract")?;
+        Ok(self - other)
+    }
+}
+
+impl<'a, T> SubAssign<&'a DOKMatrix<T>> for DOKMatrix<T>
+    where T: MatrixElem + Sub<Output = T>
+{
+    /// Subtract `other` from `self` elementwise.
+    ///
+    /// Entries that cancel to zero are removed from `self`.
+    fn sub_assign(&mut self, other: &'a DOKMatrix<T>) {
+        self.assert_same_shape(other, "subtract");
+        for (&coords, v) in &other.elems {
+            let difference = match self.elems.remove(&coords) {
+                Some(existing) => existing - v.clone(),
+                None => T::zero() - v.clone(),
+            };
+            if difference.is_zero() {
+                self.elems.remove(&coords);
+            } else {
+                self.elems.insert(coords, difference);
+            }
+        }
+    }
+}
+
+impl<T> SubAssign<DOKMatrix<T>> for DOKMatrix<T>
+    where T: MatrixElem + Sub<Output = T>
+{
+    fn sub_assign(&mut self, other: DOKMatrix<T>) {
+        *self -= &other;
+    }
+}
+
+impl<'a, T> Sub<&'a DOKMatrix<T>> for DOKMatrix<T>
+    where T: MatrixElem + Sub<Output = T>
+{
+    type Output = DOKMatrix<T>;
+
+    fn sub(mut self, other: &'a DOKMatrix<T>) -> DOKMatrix<T> {
+        self -= other;
+        self
+    }
+}
+
+impl<T> Sub<DOKMatrix<T>> for DOKMatrix<T>
+    where T: MatrixElem + Sub<Output = T>
+{
+    type Output = DOKMatrix<T>;
+
+    fn sub(mut self, other: DOKMatrix<T>) -> DOKMatrix<T> {
+        self -= &other;
+        self
+    }
+}
+
+impl<'b, T> Sub<&'b DOKMatrix<T>> for &DOKMatrix<T>
+    where T: MatrixElem + Sub<Output = T>
+{
+    type Output = DOKMatrix<T>;
+
+    fn sub(self, other: &'b DOKMatrix<T>) -> DOKMatrix<T> {
+        let mut result = self.clone();
+        result -= other;
+        result
+    }
+}
+
+impl<T> Neg for DOKMatrix<T>
+    where T: MatrixElem + Neg<Output = T>
+{
+    type Output = DOKMatrix<T>;
+
+    fn neg(mut self) -> DOKMatrix<T> {
+        self.elems = self.elems.into_iter().map(|(k, v)| (k, -v)).collect();
+        self
+    }
+}
+
+impl<T> Neg for &DOKMatrix<T>
+    where T: MatrixElem + Neg<Output = T>
+{
+    type Output = DOKMatrix<T>;
+
+    fn neg(self) -> DOKMatrix<T> {
+        -self.clone()
+    }
+}
+
+impl<T> DOKMatrix<T>
+    where T: MatrixElem + Add<Output = T> + Mul<Output = T>
+{
+    /// Compute the matrix product `self * other`, returning an error if
+    /// `self.ncols != other.nrows`.
+    ///
+    /// This is `try_mxm` over the `PlusTimes` semiring.
+    pub fn try_mul(&self, other: &DOKMatrix<T>) -> Result<DOKMatrix<T>> {
+        self.try_mxm(other, PlusTimes)
+    }
+}
+
+impl<'b, T> Mul<&'b DOKMatrix<T>> for &DOKMatrix<T>
+    where T: MatrixElem + Add<Output = T> + Mul<Output = T>
+{
+    type Output = DOKMatrix<T>;
+
+    /// Compute the matrix product `self * other`.
+    ///
+    /// Panics if `self.ncols != other.nrows`.
+    fn mul(self, other: &'b DOKMatrix<T>) -> DOKMatrix<T> {
+        self.try_mul(other).unwrap_or_else(|e| panic!("{}", e))
+    }
+}
+
+impl<'a, T> Mul<&'a DOKMatrix<T>> for DOKMatrix<T>
+    where T: MatrixElem + Add<Output = T> + Mul<Output = T>
+{
+    type Output = DOKMatrix<T>;
+
+    fn mul(self, other: &'a DOKMatrix<T>) -> DOKMatrix<T> {
+        &self * other
+    }
+}
+
+impl<T> Mul<DOKMatrix<T>> for DOKMatrix<T>
+    where T: MatrixElem + Add<Output = T> + Mul<Output = T>
+{
+    type Output = DOKMatrix<T>;
+
+    fn mul(self, other: DOKMatrix<T>) -> DOKMatrix<T> {
+        &self * &other
+    }
+}
@@ -0,0 +1,9 @@
+//! Reading and writing sparse matrices in external file formats.
+
+pub mod binary;
+pub mod hb;
+pub mod mm;
+#[cfg(unix)]
+pub mod mmap;
+pub mod npz;
+mod crc;
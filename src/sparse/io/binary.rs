@@ -0,0 +1,691 @@
+//! A compact, versioned binary format for sparse matrices.
+//!
+//! Every integer is stored little-endian, whatever the platform. A file is a
+//! 40-byte header, followed by the index sections, the values and a
+//! checksum:
+//!
+//! | Offset | Size | Contents                                             |
+//! |--------|------|------------------------------------------------------|
+//! | 0      | 8    | The magic bytes `REVOLVER`                           |
+//! | 8      | 2    | Format version, currently 1                          |
+//! | 10     | 1    | Layout: 0 for triplets, 1 for CSR, 2 for CSC         |
+//! | 11     | 1    | Element type tag, see `ElemType`                     |
+//! | 12     | 4    | Reserved, zero                                       |
+//! | 16     | 8    | Number of rows                                       |
+//! | 24     | 8    | Number of columns                                    |
+//! | 32     | 8    | Number of stored elements, `nnz`                     |
+//!
+//! Triplet files, written from a DOKMatrix, then hold `nnz` row indices and
+//! `nnz` column indices in row-major order. CSR and CSC files hold the
+//! `indptr` array, with one more entry than there are rows or columns, and
+//! `nnz` column or row indices. Every index is a `u64`, so the values start
+//! at an offset that is a multiple of 8. The values take `nnz` times the
+//! size of the element type, and the file ends with the CRC-32 of all of the
+//! preceding bytes as a `u32`.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+use complex::Complex;
+use error::{Error, Result};
+use sparse::csc::CscMatrix;
+use sparse::csr::CsrMatrix;
+use sparse::dok::{DOKMatrix, MatrixElem, Order};
+use sparse::io::crc::Crc32;
+
+pub(crate) const MAGIC: &[u8; 8] = b"REVOLVER";
+pub(crate) const VERSION: u16 = 1;
+pub(crate) const HEADER_LEN: usize = 40;
+
+/// Number of bytes encoded or decoded at a time when streaming a section.
+const CHUNK_LEN: usize = 1 << 16;
+
+/// Type of the values stored in a file, identified by the tag in its
+/// header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ElemType {
+    Bool = 1,
+    I8 = 2,
+    I16 = 3,
+    I32 = 4,
+    I64 = 5,
+    U8 = 6,
+    U16 = 7,
+    U32 = 8,
+    U64 = 9,
+    F32 = 10,
+    F64 = 11,
+    Complex32 = 12,
+    Complex64 = 13,
+}
+
+impl ElemType {
+    /// Look up the type with the given tag.
+    pub fn from_tag(tag: u8) -> Option<ElemType> {
+        let types = [ElemType::Bool,
+                     ElemType::I8,
+                     ElemType::I16,
+                     ElemType::I32,
+                     ElemType::I64,
+                     ElemType::U8,
+                     ElemType::U16,
+                     ElemType::U32,
+                     ElemType::U64,
+                     ElemType::F32,
+                     ElemType::F64,
+                     ElemType::Complex32,
+                     ElemType::Complex64];
+        types.iter().cloned().find(|&t| t as u8 == tag)
+    }
+
+    /// Number of bytes taken by each value.
+    pub fn size(self) -> usize {
+        match self {
+            ElemType::Bool | ElemType::I8 | ElemType::U8 => 1,
+            ElemType::I16 | ElemType::U16 => 2,
+            ElemType::I32 | ElemType::U32 | ElemType::F32 => 4,
+            ElemType::I64 | ElemType::U64 | ElemType::F64 | ElemType::Complex32 => 8,
+            ElemType::Complex64 => 16,
+        }
+    }
+}
+
+/// Element types that can be stored in the binary format.
+pub trait BinaryElem: MatrixElem {
+    /// Tag written to the header of files holding this type.
+    const ELEM_TYPE: ElemType;
+
+    /// Append the little-endian bytes of this value to `out`.
+    fn write_le(&self, out: &mut Vec<u8>);
+
+    /// Read a value from its little-endian bytes, of which there are
+    /// `Self::ELEM_TYPE.size()`.
+    fn read_le(bytes: &[u8]) -> Self;
+}
+
+macro_rules! impl_binary_elem {
+    ($($t:ident $elem_type:ident),*) => {
+        $(
+            impl BinaryElem for $t {
+                const ELEM_TYPE: ElemType = ElemType::$elem_type;
+
+                fn write_le(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn read_le(bytes: &[u8]) -> $t {
+                    let mut b = [0; ::std::mem::size_of::<$t>()];
+                    b.copy_from_slice(bytes);
+                    $t::from_le_bytes(b)
+                }
+            }
+        )*
+    }
+}
+
+impl_binary_elem!(i8 I8, i16 I16, i32 I32, i64 I64, u8 U8, u16 U16, u32 U32, u64 U64, f32 F32,
+                  f64 F64);
+
+macro_rules! impl_binary_complex {
+    ($($t:ident $elem_type:ident),*) => {
+        $(
+            impl BinaryElem for Complex<$t> {
+                const ELEM_TYPE: ElemType = ElemType::$elem_type;
+
+                fn write_le(&self, out: &mut Vec<u8>) {
+                    self.re.write_le(out);
+                    self.im.write_le(out);
+                }
+
+                fn read_le(bytes: &[u8]) -> Complex<$t> {
+                    let (re, im) = bytes.split_at(bytes.len() / 2);
+                    Complex::new($t::read_le(re), $t::read_le(im))
+                }
+            }
+        )*
+    }
+}
+
+impl_binary_complex!(f32 Complex32, f64 Complex64);
+
+impl BinaryElem for bool {
+    const ELEM_TYPE: ElemType = ElemType::Bool;
+
+    fn write_le(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+
+    fn read_le(bytes: &[u8]) -> bool {
+        bytes[0] != 0
+    }
+}
+
+/// Arrangement of the index sections of a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Layout {
+    Triplets = 0,
+    Csr = 1,
+    Csc = 2,
+}
+
+/// The fixed-size header at the start of a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Header {
+    pub layout: Layout,
+    pub elem_type: ElemType,
+    pub nrows: u64,
+    pub ncols: u64,
+    pub nnz: u64,
+}
+
+impl Header {
+    pub fn to_bytes(self) -> [u8; HEADER_LEN] {
+        let mut bytes = [0; HEADER_LEN];
+        bytes[..8].copy_from_slice(MAGIC);
+        bytes[8..10].copy_from_slice(&VERSION.to_le_bytes());
+        bytes[10] = self.layout as u8;
+        bytes[11] = self.elem_type as u8;
+        bytes[16..24].copy_from_slice(&self.nrows.to_le_bytes());
+        bytes[24..32].copy_from_slice(&self.ncols.to_le_bytes());
+        bytes[32..40].copy_from_slice(&self.nnz.to_le_bytes());
+        bytes
+    }
+
+    /// Parse a header, checking that it describes a file of the expected
+    /// layout and element type.
+    pub fn parse(bytes: &[u8; HEADER_LEN], layout: Layout, elem_type: ElemType) -> Result<Header> {
+        if bytes[..8] != MAGIC[..] {
+            return Err(invalid("not a revolver sparse matrix file".into()));
+        }
+        let version = u16::from_le_bytes([bytes[8], bytes[9]]);
+        if version != VERSION {
+            return Err(invalid(format!("unsupported format version {}", version)));
+        }
+        let found = match bytes[10] {
+            0 => Layout::Triplets,
+            1 => Layout::Csr,
+            2 => Layout::Csc,
+            tag => return Err(invalid(format!("unknown layout tag {}", tag))),
+        };
+        if found != layout {
+            return Err(invalid(format!("expected {:?} layout, found {:?}", layout, found)));
+        }
+        match ElemType::from_tag(bytes[11]) {
+            Some(found) if found == elem_type => {}
+            Some(found) => {
+                return Err(invalid(format!("expected elements of type {:?}, found {:?}",
+                                           elem_type,
+                                           found)))
+            }
+            None => return Err(invalid(format!("unknown element type tag {}", bytes[11]))),
+        }
+        Ok(Header {
+            layout,
+            elem_type,
+            nrows: u64_le(&bytes[16..24]),
+            ncols: u64_le(&bytes[24..32]),
+            nnz: u64_le(&bytes[32..40]),
+        })
+    }
+
+    /// Length of the `indptr` section of a compressed file, or an error if
+    /// it does not fit in a `u64`.
+    pub fn indptr_len(&self) -> Result<u64> {
+        let nmajor = match self.layout {
+            Layout::Csr => self.nrows,
+            Layout::Csc => self.ncols,
+            Layout::Triplets => return Ok(0),
+        };
+        nmajor.checked_add(1)
+            .ok_or_else(|| invalid(format!("matrix of shape ({}, {}) is too large",
+                                           self.nrows,
+                                           self.ncols)))
+    }
+}
+
+/// Read a `u64` from 8 little-endian bytes.
+pub(crate) fn u64_le(bytes: &[u8]) -> u64 {
+    let mut array = [0; 8];
+    array.copy_from_slice(bytes);
+    u64::from_le_bytes(array)
+}
+
+/// Build an error for a malformed file.
+pub(crate) fn invalid(message: String) -> Error {
+    Error::Parse {
+        line: None,
+        message,
+    }
+}
+
+/// Writes sections of a file in chunks, keeping a running checksum.
+struct SectionWriter<W: Write> {
+    writer: W,
+    crc: Crc32,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> SectionWriter<W> {
+    fn new(writer: W) -> SectionWriter<W> {
+        SectionWriter {
+            writer,
+            crc: Crc32::new(),
+            buf: Vec::with_capacity(CHUNK_LEN),
+        }
+    }
+
+    fn flush_chunk(&mut self) -> Result<()> {
+        self.crc.update(&self.buf);
+        self.writer.write_all(&self.buf)?;
+        self.buf.clear();
+        Ok(())
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        self.buf.extend_from_slice(bytes);
+        if self.buf.len() >= CHUNK_LEN {
+            self.flush_chunk()?;
+        }
+        Ok(())
+    }
+
+    fn u64s<I: IntoIterator<Item = u64>>(&mut self, values: I) -> Result<()> {
+        for v in values {
+            self.bytes(&v.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn values<'a, T, I>(&mut self, values: I) -> Result<()>
+        where T: BinaryElem + 'a,
+              I: IntoIterator<Item = &'a T>
+    {
+        for v in values {
+            v.write_le(&mut self.buf);
+            if self.buf.len() >= CHUNK_LEN {
+                self.flush_chunk()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the checksum, completing the file.
+    fn finish(mut self) -> Result<()> {
+        self.flush_chunk()?;
+        self.writer.write_all(&self.crc.value().to_le_bytes())?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Reads sections of a file in chunks, keeping a running checksum.
+struct SectionReader<R: Read> {
+    reader: R,
+    crc: Crc32,
+}
+
+impl<R: Read> SectionReader<R> {
+    fn new(reader: R) -> SectionReader<R> {
+        SectionReader {
+            reader,
+            crc: Crc32::new(),
+        }
+    }
+
+    fn bytes(&mut self, buf: &mut [u8]) -> Result<()> {
+        self.reader.read_exact(buf)?;
+        self.crc.update(buf);
+        Ok(())
+    }
+
+    fn header(&mut self, layout: Layout, elem_type: ElemType) -> Result<Header> {
+        let mut bytes = [0; HEADER_LEN];
+        self.bytes(&mut bytes)?;
+        Header::parse(&bytes, layout, elem_type)
+    }
+
+    /// Read `len` elements of `size` bytes each, decoding them with `decode`.
+    ///
+    /// Memory is allocated as the data arrives, so that a corrupt length in
+    /// the header fails at the end of the file rather than with an enormous
+    /// allocation.
+    fn section<T, F>(&mut self, len: u64, size: usize, decode: F) -> Result<Vec<T>>
+        where F: Fn(&[u8]) -> T
+    {
+        let per_chunk = (CHUNK_LEN / size) as u64;
+        let mut out = Vec::with_capacity(len.min(per_chunk) as usize);
+        let mut buf = vec![0; CHUNK_LEN];
+        let mut left = len;
+        while left > 0 {
+            let n = left.min(per_chunk) as usize;
+            let chunk = &mut buf[..n * size];
+            self.bytes(chunk)?;
+            out.extend(chunk.chunks(size).map(&decode));
+            left -= n as u64;
+        }
+        Ok(out)
+    }
+
+    fn u64s(&mut self, len: u64) -> Result<Vec<u64>> {
+        self.section(len, 8, u64_le)
+    }
+
+    fn values<T: BinaryElem>(&mut self, len: u64) -> Result<Vec<T>> {
+        self.section(len, T::ELEM_TYPE.size(), T::read_le)
+    }
+
+    fn usizes(&mut self, len: u64) -> Result<Vec<usize>> {
+        self.u64s(len)?
+            .into_iter()
+            .map(|v| if v > usize::MAX as u64 {
+                Err(invalid(format!("offset {} does not fit in memory", v)))
+            } else {
+                Ok(v as usize)
+            })
+            .collect()
+    }
+
+    /// Read and check the checksum at the end of the file.
+    fn finish(mut self) -> Result<()> {
+        let expected = self.crc.value();
+        let mut bytes = [0; 4];
+        self.reader.read_exact(&mut bytes)?;
+        if u32::from_le_bytes(bytes) != expected {
+            return Err(invalid("checksum mismatch: the file is corrupt".into()));
+        }
+        Ok(())
+    }
+}
+
+impl<T> DOKMatrix<T>
+    where T: BinaryElem
+{
+    /// Write this matrix in the binary format described in
+    /// `sparse::io::binary`, with its elements in row-major order.
+    ///
+    /// The output is written in chunks; wrapping `writer` in a `BufWriter`
+    /// is not necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - Destination of the file contents.
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<()> {
+        let elems = self.iter_sorted(Order::RowMajor);
+        let header = Header {
+            layout: Layout::Triplets,
+            elem_type: T::ELEM_TYPE,
+            nrows: self.nrows,
+            ncols: self.ncols,
+            nnz: elems.len() as u64,
+        };
+        let mut out = SectionWriter::new(writer);
+        out.bytes(&header.to_bytes())?;
+        out.u64s(elems.as_slice().iter().map(|&((i, _), _)| i))?;
+        out.u64s(elems.as_slice().iter().map(|&((_, j), _)| j))?;
+        out.values(elems.map(|(_, v)| v))?;
+        out.finish()
+    }
+
+    /// Read a matrix written by `write_to`.
+    ///
+    /// Returns an error if the file is malformed or corrupt, or holds a
+    /// compressed matrix or elements of a different type. Exactly the bytes
+    /// of the file are consumed from `reader`.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - Source of the file contents.
+    pub fn read_from<R: Read>(reader: R) -> Result<DOKMatrix<T>> {
+        let mut input = SectionReader::new(reader);
+        let header = input.header(Layout::Triplets, T::ELEM_TYPE)?;
+        let rows = input.u64s(header.nnz)?;
+        let cols = input.u64s(header.nnz)?;
+        let values = input.values::<T>(header.nnz)?;
+        input.finish()?;
+
+        let mut elems = HashMap::with_capacity(values.len());
+        for ((i, j), v) in rows.into_iter().zip(cols).zip(values) {
+            if elems.insert((i, j), v).is_some() {
+                return Err(invalid(format!("duplicate element ({}, {})", i, j)));
+            }
+        }
+        DOKMatrix::try_new(header.nrows, header.ncols, elems)
+    }
+}
+
+impl<T> CsrMatrix<T>
+    where T: BinaryElem
+{
+    /// Write this matrix in the binary format described in
+    /// `sparse::io::binary`.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - Destination of the file contents.
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<()> {
+        write_compressed(writer,
+                         Layout::Csr,
+                         (self.nrows, self.ncols),
+                         self.indptr(),
+                         self.indices(),
+                         self.data())
+    }
+
+    /// Read a matrix written by `write_to`.
+    ///
+    /// Returns an error if the file is malformed or corrupt, or holds a
+    /// matrix of another layout or elements of a different type.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - Source of the file contents.
+    pub fn read_from<R: Read>(reader: R) -> Result<CsrMatrix<T>> {
+        read_compressed(reader, Layout::Csr, CsrMatrix::try_new)
+    }
+}
+
+impl<T> CscMatrix<T>
+    where T: BinaryElem
+{
+    /// Write this matrix in the binary format described in
+    /// `sparse::io::binary`.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - Destination of the file contents.
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<()> {
+        write_compressed(writer,
+                         Layout::Csc,
+                         (self.nrows, self.ncols),
+                         self.indptr(),
+                         self.indices(),
+                         self.data())
+    }
+
+    /// Read a matrix written by `write_to`.
+    ///
+    /// Returns an error if the file is malformed or corrupt, or holds a
+    /// matrix of another layout or elements of a different type.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - Source of the file contents.
+    pub fn read_from<R: Read>(reader: R) -> Result<CscMatrix<T>> {
+        read_compressed(reader, Layout::Csc, CscMatrix::try_new)
+    }
+}
+
+fn write_compressed<T, W>(writer: W,
+                          layout: Layout,
+                          (nrows, ncols): (u64, u64),
+                          indptr: &[usize],
+                          indices: &[u64],
+                          data: &[T])
+                          -> Result<()>
+    where T: BinaryElem,
+          W: Write
+{
+    let header = Header {
+        layout,
+        elem_type: T::ELEM_TYPE,
+        nrows,
+        ncols,
+        nnz: data.len() as u64,
+    };
+    let mut out = SectionWriter::new(writer);
+    out.bytes(&header.to_bytes())?;
+    out.u64s(indptr.iter().map(|&p| p as u64))?;
+    out.u64s(indices.iter().cloned())?;
+    out.values(data)?;
+    out.finish()
+}
+
+/// Read a compressed matrix, building it with `new`, which takes the same
+/// arguments as `CsrMatrix::try_new`.
+fn read_compressed<T, R, M, F>(reader: R, layout: Layout, new: F) -> Result<M>
+    where T: BinaryElem,
+          R: Read,
+          F: FnOnce(u64, u64, Vec<usize>, Vec<u64>, Vec<T>) -> Result<M>
+{
+    let mut input = SectionReader::new(reader);
+    let header = input.header(layout, T::ELEM_TYPE)?;
+    let indptr = input.usizes(header.indptr_len()?)?;
+    let indices = input.u64s(header.nnz)?;
+    let data = input.values(header.nnz)?;
+    input.finish()?;
+    new(header.nrows, header.ncols, indptr, indices, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use complex::Complex;
+    use error::Error;
+    use sparse::csc::CscMatrix;
+    use sparse::csr::CsrMatrix;
+    use sparse::dok::DOKMatrix;
+    use sparse::io::crc::crc32;
+    use super::{ElemType, HEADER_LEN};
+
+    fn example() -> DOKMatrix<f64> {
+        let mut m = DOKMatrix::zeros(3, 4);
+        m.set(2, 0, -2.0);
+        m.set(0, 1, 1.5);
+        m.set(2, 3, 4.0);
+        m
+    }
+
+    #[test]
+    fn test_layout() {
+        let mut out = vec![];
+        example().write_to(&mut out).unwrap();
+        assert_eq!(out.len(), HEADER_LEN + 3 * 8 * 3 + 4);
+        assert_eq!(&out[..8], b"REVOLVER");
+        assert_eq!(&out[8..16], &[1, 0, 0, ElemType::F64 as u8, 0, 0, 0, 0]);
+        let le = |values: &[u64]| -> Vec<u8> {
+            values.iter().flat_map(|v| v.to_le_bytes().to_vec()).collect()
+        };
+        assert_eq!(&out[16..40], &le(&[3, 4, 3])[..]);
+        // Row and column indices, in row-major order.
+        assert_eq!(&out[40..88], &le(&[0, 2, 2, 1, 0, 3])[..]);
+        assert_eq!(&out[88..96], &1.5f64.to_le_bytes());
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let dok = example();
+        let mut out = vec![];
+        dok.write_to(&mut out).unwrap();
+        let read = DOKMatrix::<f64>::read_from(&out[..]).unwrap();
+        assert_eq!(read.nnz(), 3);
+        assert_eq!(read, dok);
+
+        let csr = CsrMatrix::from_dok(&dok);
+        let mut out = vec![];
+        csr.write_to(&mut out).unwrap();
+        let read = CsrMatrix::<f64>::read_from(&out[..]).unwrap();
+        assert_eq!((read.indptr(), read.indices(), read.data()),
+                   (csr.indptr(), csr.indices(), csr.data()));
+
+        let mut complex = DOKMatrix::zeros(2, 2);
+        complex.set(1, 0, Complex::new(1.0f32, -0.5));
+        let csc = CscMatrix::from_dok(&complex);
+        let mut out = vec![];
+        csc.write_to(&mut out).unwrap();
+        assert_eq!(CscMatrix::<Complex<f32>>::read_from(&out[..]).unwrap().to_dok(), complex);
+
+        // Matrices large enough to be written in several chunks.
+        let mut big = DOKMatrix::<i16>::zeros(200, 300);
+        for k in 0..20000u64 {
+            big.set(k * 7 % 200, k * 13 % 300, k as i16);
+        }
+        let mut out = vec![];
+        big.write_to(&mut out).unwrap();
+        assert_eq!(DOKMatrix::<i16>::read_from(&out[..]).unwrap(), big);
+    }
+
+    #[test]
+    fn test_streaming() {
+        // Files can be concatenated and read back one at a time.
+        let mut out = vec![];
+        example().write_to(&mut out).unwrap();
+        DOKMatrix::<bool>::identity(2).write_to(&mut out).unwrap();
+        let mut reader = &out[..];
+        assert_eq!(DOKMatrix::<f64>::read_from(&mut reader).unwrap(), example());
+        assert_eq!(DOKMatrix::<bool>::read_from(&mut reader).unwrap(),
+                   DOKMatrix::identity(2));
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let invalid = |message: &str| {
+            Error::Parse {
+                line: None,
+                message: message.into(),
+            }
+        };
+        let mut out = vec![];
+        example().write_to(&mut out).unwrap();
+
+        assert_eq!(DOKMatrix::<f32>::read_from(&out[..]).unwrap_err(),
+                   invalid("expected elements of type F32, found F64"));
+        assert_eq!(CsrMatrix::<f64>::read_from(&out[..]).err().unwrap(),
+                   invalid("expected Csr layout, found Triplets"));
+
+        let mut corrupt = out.clone();
+        corrupt[90] ^= 0x10;
+        assert_eq!(DOKMatrix::<f64>::read_from(&corrupt[..]).unwrap_err(),
+                   invalid("checksum mismatch: the file is corrupt"));
+
+        let mut version = out.clone();
+        version[8] = 2;
+        assert_eq!(DOKMatrix::<f64>::read_from(&version[..]).unwrap_err(),
+                   invalid("unsupported format version 2"));
+
+        match DOKMatrix::<f64>::read_from(&out[..out.len() - 1]).unwrap_err() {
+            Error::Io { kind, .. } => assert_eq!(kind, ::std::io::ErrorKind::UnexpectedEof),
+            e => panic!("unexpected error {:?}", e),
+        }
+        assert!(DOKMatrix::<f64>::read_from(&b"REVOLVE"[..]).is_err());
+
+        // A header whose indptr length overflows.
+        let mut huge = vec![];
+        CsrMatrix::from_dok(&example()).write_to(&mut huge).unwrap();
+        huge[16..24].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(CsrMatrix::<f64>::read_from(&huge[..]).err().unwrap(),
+                   invalid(&format!("matrix of shape ({}, 4) is too large", u64::MAX)));
+
+        // A well-formed file holding an indptr past the end of the data.
+        let bad = CsrMatrix::new(2, 3, vec![0, 1, 1], vec![0], vec![1.0]);
+        let mut bytes = vec![];
+        bad.write_to(&mut bytes).unwrap();
+        bytes[48..56].copy_from_slice(&5u64.to_le_bytes());
+        let len = bytes.len();
+        let crc = crc32(&bytes[..len - 4]);
+        bytes[len - 4..].copy_from_slice(&crc.to_le_bytes());
+        match CsrMatrix::<f64>::read_from(&bytes[..]).err().unwrap() {
+            Error::InvalidStructure(_) => {}
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+}
@@ -0,0 +1,828 @@
+//! The Harwell-Boeing and Rutherford-Boeing exchange formats.
+//!
+//! Both formats store a matrix in compressed sparse column form as
+//! fixed-width, 80-column records. A header of four or five records gives a
+//! title, the number of records in each section, a three-letter matrix type
+//! such as `RUA` (real, unsymmetric, assembled), the shape, and the Fortran
+//! formats used for the column pointers, row indices and values. All indices
+//! are 1-based.
+//!
+//! The two formats differ only in their headers: Harwell-Boeing files also
+//! describe an optional section of right-hand sides, which is skipped when
+//! reading, and Rutherford-Boeing files may hold integer values.
+//!
+//! See <https://math.nist.gov/MatrixMarket/formats.html#hb> and
+//! <https://www.cise.ufl.edu/research/sparse/matrices/DOC/rb.pdf>.
+
+use std::convert::TryFrom;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::result;
+
+use error::{Error, Result};
+use sparse::compressed::compress;
+use sparse::csc::CscMatrix;
+use sparse::io::mm::{Field, MatrixMarketElem, Symmetry};
+
+/// Largest number of characters in a record.
+const RECORD_LEN: usize = 80;
+
+/// Width of the fields used to write real values, enough for the shortest
+/// representation of any `f64` that reads back exactly.
+const REAL_WIDTH: usize = 25;
+
+/// Flavor of file to write.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Variant {
+    HarwellBoeing,
+    RutherfordBoeing,
+}
+
+/// A single position in a Fortran format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Edit {
+    /// Skip this many characters, as in `3X`.
+    Skip(usize),
+    /// Read a value from a field of this width, as in `I8` or `E25.16`.
+    Value(usize),
+}
+
+/// A Fortran format string such as `(16I5)` or `(1P,3E25.16)`, used to split
+/// fixed-width records into fields.
+///
+/// Formats may contain `I`, `E`, `D`, `F`, `G`, `ES` and `EN` edit
+/// descriptors with repeat counts, `nX` skips, `kP` scale factors, and
+/// repeated groups in parentheses. Scale factors only affect values written
+/// without an exponent, and are ignored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FortranFormat {
+    edits: Vec<Edit>,
+    // Index into `edits` where each record after the first starts, following
+    // Fortran's rule of reverting to the last top-level group.
+    reversion: usize,
+}
+
+impl FortranFormat {
+    /// Parse a Fortran format string.
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - Format string, including the enclosing parentheses.
+    pub fn parse(format: &str) -> Result<FortranFormat> {
+        let chars: Vec<char> = format.chars()
+            .filter(|c| !c.is_whitespace())
+            .flat_map(char::to_uppercase)
+            .collect();
+        let mut parser = FormatParser {
+            chars: &chars,
+            pos: 0,
+        };
+        let mut reversion = 0;
+        let edits = parser.parse(&mut reversion)
+            .map_err(|message| {
+                Error::Parse {
+                    line: None,
+                    message: format!("invalid Fortran format {:?}: {}", format, message),
+                }
+            })?;
+        if !edits.iter().any(|e| matches!(e, Edit::Value(_))) {
+            return Err(Error::Parse {
+                line: None,
+                message: format!("Fortran format {:?} has no fields", format),
+            });
+        }
+        Ok(FortranFormat { edits, reversion })
+    }
+
+    /// Split records into the contents of their fields, as a single Fortran
+    /// `READ` statement would.
+    ///
+    /// Leading and trailing blanks are stripped from each field, and blank
+    /// fields are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `records` - Lines of text to split.
+    pub fn read_fields<'a, I>(&self, records: I) -> Result<Vec<&'a str>>
+        where I: IntoIterator<Item = &'a str>
+    {
+        let mut fields = vec![];
+        for (n, record) in records.into_iter().enumerate() {
+            if !record.is_ascii() {
+                return Err(Error::Parse {
+                    line: None,
+                    message: "fixed-width records must be ASCII".into(),
+                });
+            }
+            let edits = if n == 0 { &self.edits[..] } else { &self.edits[self.reversion..] };
+            let mut pos = 0;
+            for edit in edits {
+                match *edit {
+                    Edit::Skip(width) => pos += width,
+                    Edit::Value(width) => {
+                        if pos >= record.len() {
+                            break;
+                        }
+                        let field = record[pos..record.len().min(pos + width)].trim();
+                        if !field.is_empty() {
+                            fields.push(field);
+                        }
+                        pos += width;
+                    }
+                }
+            }
+        }
+        Ok(fields)
+    }
+}
+
+/// Recursive descent parser for Fortran format strings, working on
+/// uppercased characters with whitespace removed.
+struct FormatParser<'a> {
+    chars: &'a [char],
+    pos: usize,
+}
+
+impl<'a> FormatParser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn expect(&mut self, c: char) -> result::Result<(), String> {
+        match self.peek() {
+            Some(found) if found == c => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(found) => Err(format!("expected {:?}, found {:?}", c, found)),
+            None => Err(format!("expected {:?}", c)),
+        }
+    }
+
+    /// Parse an unsigned integer, or return `None` if there are no digits.
+    fn number(&mut self) -> result::Result<Option<usize>, String> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Ok(None);
+        }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        digits.parse().map(Some).map_err(|_| format!("number {} is too large", digits))
+    }
+
+    /// Parse a whole format, storing in `reversion` the index of the edit at
+    /// which the last top-level group starts.
+    fn parse(&mut self, reversion: &mut usize) -> result::Result<Vec<Edit>, String> {
+        self.expect('(')?;
+        let edits = self.list(Some(reversion))?;
+        self.expect(')')?;
+        if let Some(c) = self.peek() {
+            return Err(format!("unexpected {:?} after the closing parenthesis", c));
+        }
+        Ok(edits)
+    }
+
+    /// Parse a comma-separated list of items up to a closing parenthesis.
+    fn list(&mut self, mut reversion: Option<&mut usize>) -> result::Result<Vec<Edit>, String> {
+        let mut edits = vec![];
+        loop {
+            if self.peek() == Some(')') {
+                return Ok(edits);
+            }
+            if self.peek() == Some('-') {
+                self.pos += 1;
+            }
+            let count = self.number()?;
+            match self.peek() {
+                Some('P') => {
+                    // A scale factor may be followed directly by a descriptor,
+                    // as in `1PE15.8`.
+                    self.pos += 1;
+                    if count.is_none() {
+                        return Err("scale factor must have a count".into());
+                    }
+                    if self.peek() != Some(',') {
+                        continue;
+                    }
+                }
+                Some('X') => {
+                    self.pos += 1;
+                    repeat(&mut edits, &[Edit::Skip(count.unwrap_or(1))], 1)?;
+                }
+                Some('(') => {
+                    self.pos += 1;
+                    if let Some(ref mut reversion) = reversion {
+                        **reversion = edits.len();
+                    }
+                    let group = self.list(None)?;
+                    self.expect(')')?;
+                    repeat(&mut edits, &group, count.unwrap_or(1))?;
+                }
+                Some(c @ 'I') | Some(c @ 'E') | Some(c @ 'D') | Some(c @ 'F') |
+                Some(c @ 'G') => {
+                    self.pos += 1;
+                    if c == 'E' && (self.peek() == Some('S') || self.peek() == Some('N')) {
+                        self.pos += 1;
+                    }
+                    let width = match self.number()? {
+                        Some(width) if width > 0 => width,
+                        _ => return Err(format!("edit descriptor {:?} needs a width", c)),
+                    };
+                    if self.peek() == Some('.') {
+                        self.pos += 1;
+                        self.number()?.ok_or_else(|| format!("expected digits after {:?}", c))?;
+                    }
+                    if c != 'I' && self.peek() == Some('E') {
+                        self.pos += 1;
+                        self.number()?.ok_or("expected exponent width")?;
+                    }
+                    repeat(&mut edits, &[Edit::Value(width)], count.unwrap_or(1))?;
+                }
+                Some(c) => return Err(format!("unsupported edit descriptor {:?}", c)),
+                None => return Err("unexpected end of format".into()),
+            }
+            if self.peek() == Some(',') {
+                self.pos += 1;
+            }
+        }
+    }
+}
+
+/// Append `count` copies of `group` to `edits`.
+///
+/// Returns an error if this would give more edits than there are characters
+/// in a record, so that repeat counts cannot cause unbounded allocation.
+fn repeat(edits: &mut Vec<Edit>, group: &[Edit], count: usize) -> result::Result<(), String> {
+    let total = group.len().checked_mul(count).and_then(|n| n.checked_add(edits.len()));
+    if total.is_none_or(|n| n > RECORD_LEN) {
+        return Err(format!("more than {} edits per record", RECORD_LEN));
+    }
+    for _ in 0..count {
+        edits.extend_from_slice(group);
+    }
+    Ok(())
+}
+
+/// Rewrite a Fortran real constant in a form Rust can parse, replacing `D`
+/// exponents and adding the `E` that Fortran omits from three-digit
+/// exponents, as in `1.0-100`.
+fn fortran_real(token: &str) -> String {
+    let token = token.replace(['D', 'd'], "E");
+    if token.contains(['E', 'e']) {
+        return token;
+    }
+    match token.rfind(['+', '-']) {
+        Some(pos) if pos > 0 => format!("{}E{}", &token[..pos], &token[pos..]),
+        _ => token,
+    }
+}
+
+/// Build a parse error for the given 1-based line.
+fn parse_error(line: usize, message: String) -> Error {
+    Error::Parse {
+        line: Some(line),
+        message,
+    }
+}
+
+/// Attach a line number to an error from `FortranFormat`.
+fn at_line(line: usize, e: Error) -> Error {
+    match e {
+        Error::Parse { line: None, message } => parse_error(line, message),
+        e => e,
+    }
+}
+
+/// Parse the whitespace-separated integers of a header record.
+fn header_ints(line: usize, record: &str, min: usize, max: usize) -> Result<Vec<u64>> {
+    let ints = record.split_whitespace()
+        .map(|t| t.parse().map_err(|_| parse_error(line, format!("invalid integer {:?}", t))))
+        .collect::<Result<Vec<u64>>>()?;
+    if ints.len() < min || ints.len() > max {
+        return Err(parse_error(line, format!("expected {} to {} integers", min, max)));
+    }
+    Ok(ints)
+}
+
+/// Read `len` integers from a section of a file starting at `line`.
+fn read_ints<'a, I>(format: &FortranFormat, line: usize, records: I, len: usize, what: &str)
+                    -> Result<Vec<u64>>
+    where I: IntoIterator<Item = &'a str>
+{
+    let fields = format.read_fields(records).map_err(|e| at_line(line, e))?;
+    if fields.len() != len {
+        return Err(parse_error(line, format!("expected {} {}, found {}", len, what, fields.len())));
+    }
+    fields.iter()
+        .map(|f| f.parse().map_err(|_| parse_error(line, format!("invalid integer {:?}", f))))
+        .collect()
+}
+
+/// Convert a count read from header line `line` to a `usize`.
+fn header_count(line: usize, count: u64) -> Result<usize> {
+    usize::try_from(count).map_err(|_| parse_error(line, format!("count {} is too large", count)))
+}
+
+/// Characters `start..end` of a record, which may be shorter than `end`.
+fn columns(record: &str, start: usize, end: usize) -> &str {
+    record.get(start.min(record.len())..end.min(record.len())).unwrap_or("").trim()
+}
+
+/// Read a matrix in Harwell-Boeing or Rutherford-Boeing format.
+///
+/// Both variants are accepted. Symmetric, skew-symmetric and hermitian
+/// matrices are expanded to store both triangles. Elemental matrices and
+/// right-hand sides are not supported, and right-hand sides are skipped. Use
+/// `to_dok` to get the result as a DOKMatrix.
+///
+/// # Arguments
+///
+/// * `reader` - Source of the file contents.
+pub fn read_harwell_boeing<T, R>(reader: R) -> Result<CscMatrix<T>>
+    where T: MatrixMarketElem,
+          R: Read
+{
+    let lines = BufReader::new(reader).lines().collect::<::std::io::Result<Vec<String>>>()?;
+    if lines.len() < 4 {
+        return Err(Error::Parse {
+            line: None,
+            message: "expected at least 4 header lines".into(),
+        });
+    }
+
+    let cards = header_ints(2, &lines[1], 4, 5)?;
+    let (ptrcrd, indcrd, valcrd) =
+        (header_count(2, cards[1])?, header_count(2, cards[2])?, header_count(2, cards[3])?);
+    let rhscrd = cards.get(4).cloned().unwrap_or(0);
+
+    let mxtype = columns(&lines[2], 0, 3).to_uppercase();
+    let sizes = header_ints(3, lines[2].get(3..).unwrap_or(""), 3, 4)?;
+    let (nrows, ncols, nnz) = (sizes[0], sizes[1], header_count(3, sizes[2])?);
+    let nptr = header_count(3, ncols)?
+        .checked_add(1)
+        .ok_or_else(|| parse_error(3, format!("count {} is too large", ncols)))?;
+    let mut kind = mxtype.chars();
+    let field = match kind.next() {
+        Some('R') => Field::Real,
+        Some('C') => Field::Complex,
+        Some('I') => Field::Integer,
+        Some('P') | Some('Q') => Field::Pattern,
+        _ => return Err(parse_error(3, format!("unknown value type in {:?}", mxtype))),
+    };
+    let symmetry = match kind.next() {
+        Some('U') | Some('R') => Symmetry::General,
+        Some('S') => Symmetry::Symmetric,
+        Some('Z') => Symmetry::SkewSymmetric,
+        Some('H') => Symmetry::Hermitian,
+        _ => return Err(parse_error(3, format!("unknown symmetry in {:?}", mxtype))),
+    };
+    if kind.next() != Some('A') {
+        return Err(parse_error(3, format!("only assembled matrices are supported, found {:?}",
+                                          mxtype)));
+    }
+    if symmetry != Symmetry::General && nrows != ncols {
+        return Err(parse_error(3,
+                               format!("{:?} matrix must be square, found shape ({}, {})",
+                                       symmetry,
+                                       nrows,
+                                       ncols)));
+    }
+
+    let ptrfmt = FortranFormat::parse(columns(&lines[3], 0, 16)).map_err(|e| at_line(4, e))?;
+    let indfmt = FortranFormat::parse(columns(&lines[3], 16, 32)).map_err(|e| at_line(4, e))?;
+    let valfmt = if field == Field::Pattern {
+        None
+    } else {
+        Some(FortranFormat::parse(columns(&lines[3], 32, 52)).map_err(|e| at_line(4, e))?)
+    };
+
+    // Sections of the file, each starting at a 0-based line index.
+    let ptr_start: usize = if rhscrd > 0 { 5 } else { 4 };
+    let end = [ptrcrd, indcrd, valcrd]
+        .iter()
+        .try_fold(ptr_start, |end, &n| end.checked_add(n))
+        .ok_or_else(|| parse_error(2, "line counts are too large".into()))?;
+    let (ind_start, val_start) = (ptr_start + ptrcrd, ptr_start + ptrcrd + indcrd);
+    if lines.len() < end {
+        return Err(Error::Parse {
+            line: None,
+            message: format!("expected {} lines, found {}", end, lines.len()),
+        });
+    }
+    let section = |start: usize, len: usize| lines[start..start + len].iter().map(String::as_str);
+    let (ptr_line, ptr_records) = (ptr_start + 1, section(ptr_start, ptrcrd));
+    let (ind_line, ind_records) = (ind_start + 1, section(ind_start, indcrd));
+    let (val_line, val_records) = (val_start + 1, section(val_start, valcrd));
+
+    let indptr = read_ints(&ptrfmt, ptr_line, ptr_records, nptr, "column pointers")?;
+    let rows = read_ints(&indfmt, ind_line, ind_records, nnz, "row indices")?;
+    if let Some(row) = rows.iter().find(|&&row| row < 1 || row > nrows) {
+        return Err(parse_error(ind_line, format!("row index {} out of range 1..={}", row, nrows)));
+    }
+    if indptr[0] != 1 || indptr.windows(2).any(|w| w[0] > w[1]) ||
+       indptr[nptr - 1] - 1 != nnz as u64 {
+        return Err(parse_error(ptr_line,
+                               format!("column pointers must increase from 1 to {}",
+                                       nnz as u128 + 1)));
+    }
+
+    let per_value = match field {
+        Field::Pattern => 0,
+        Field::Complex => 2,
+        Field::Real | Field::Integer => 1,
+    };
+    let tokens = match valfmt {
+        Some(ref valfmt) => {
+            valfmt.read_fields(val_records).map_err(|e| at_line(val_line, e))?
+                .into_iter()
+                .map(|t| if field == Field::Integer { t.to_string() } else { fortran_real(t) })
+                .collect()
+        }
+        None => vec![],
+    };
+    if nnz.checked_mul(per_value) != Some(tokens.len()) {
+        return Err(parse_error(val_line,
+                               format!("expected {} values, found {}",
+                                       nnz as u128 * per_value as u128,
+                                       tokens.len())));
+    }
+    let values = (0..nnz)
+        .map(|k| {
+            let value: Vec<&str> = tokens[k * per_value..(k + 1) * per_value]
+                .iter()
+                .map(String::as_str)
+                .collect();
+            T::parse(field, &value).map_err(|e| parse_error(val_line, e))
+        })
+        .collect::<Result<Vec<T>>>()?;
+
+    let mut entries = Vec::with_capacity(nnz);
+    for j in 0..nptr - 1 {
+        for k in (indptr[j] - 1) as usize..(indptr[j + 1] - 1) as usize {
+            let (i, j, v) = (rows[k] - 1, j as u64, &values[k]);
+            let mirror = symmetry.mirror((i, j), v).map_err(|message| {
+                    Error::Parse {
+                        line: None,
+                        message,
+                    }
+                })?;
+            if let Some(mirror) = mirror {
+                entries.push((i, j, mirror));
+            }
+            entries.push((j, i, v.clone()));
+        }
+    }
+    let (indptr, indices, data) = compress(ncols, entries.into_iter());
+    for j in 0..nptr - 1 {
+        if let Some(w) = indices[indptr[j]..indptr[j + 1]].windows(2).find(|w| w[0] == w[1]) {
+            return Err(Error::Parse {
+                line: None,
+                message: format!("duplicate entry ({}, {})", w[0] + 1, j + 1),
+            });
+        }
+    }
+    CscMatrix::try_new(nrows, ncols, indptr, indices, data)
+}
+
+/// Write a matrix in Harwell-Boeing format.
+///
+/// The matrix is written in full, with type `RUA`, `CUA` or `PUA`, or `R` in
+/// place of `U` if it is not square. Harwell-Boeing files cannot hold
+/// integer values; use `write_rutherford_boeing` for those. Use
+/// `CscMatrix::from_dok` to write a DOKMatrix.
+///
+/// # Arguments
+///
+/// * `writer` - Destination of the file contents.
+/// * `m` - Matrix to write.
+/// * `title` - Description of the matrix, truncated to 72 characters.
+/// * `key` - Identifier of the matrix, truncated to 8 characters.
+pub fn write_harwell_boeing<T, W>(writer: W, m: &CscMatrix<T>, title: &str, key: &str) -> Result<()>
+    where T: MatrixMarketElem,
+          W: Write
+{
+    write(writer, m, title, key, Variant::HarwellBoeing)
+}
+
+/// Write a matrix in Rutherford-Boeing format.
+///
+/// See `write_harwell_boeing`. Integer matrices are written with type `IUA`.
+pub fn write_rutherford_boeing<T, W>(writer: W,
+                                     m: &CscMatrix<T>,
+                                     title: &str,
+                                     key: &str)
+                                     -> Result<()>
+    where T: MatrixMarketElem,
+          W: Write
+{
+    write(writer, m, title, key, Variant::RutherfordBoeing)
+}
+
+/// Records holding `tokens` right-aligned in fields of `width`, along with
+/// the Fortran format describing them.
+fn records(tokens: &[String], width: usize, letter: &str, precision: &str) -> (String, Vec<String>) {
+    let per_record = RECORD_LEN / width;
+    let format = format!("({}{}{}{})", per_record, letter, width, precision);
+    let records = tokens.chunks(per_record)
+        .map(|chunk| chunk.iter().map(|t| format!("{:>width$}", t, width = width)).collect())
+        .collect();
+    (format, records)
+}
+
+/// Records holding integers, in fields just wide enough for the largest.
+fn int_records(tokens: &[String]) -> (String, Vec<String>) {
+    let width = tokens.iter().map(String::len).max().unwrap_or(1) + 1;
+    records(tokens, width, "I", "")
+}
+
+fn write<T, W>(mut writer: W,
+               m: &CscMatrix<T>,
+               title: &str,
+               key: &str,
+               variant: Variant)
+               -> Result<()>
+    where T: MatrixMarketElem,
+          W: Write
+{
+    let value_type = match T::FIELD {
+        Field::Real => 'R',
+        Field::Complex => 'C',
+        Field::Pattern => 'P',
+        Field::Integer if variant == Variant::RutherfordBoeing => 'I',
+        Field::Integer => {
+            return Err(Error::InvalidStructure("Harwell-Boeing files cannot hold integer values"
+                .into()))
+        }
+    };
+    let mxtype = format!("{}{}A", value_type, if m.nrows == m.ncols { 'U' } else { 'R' });
+
+    let ptrs: Vec<String> = m.indptr().iter().map(|p| (p + 1).to_string()).collect();
+    let rows: Vec<String> = m.indices().iter().map(|i| (i + 1).to_string()).collect();
+    let values: Vec<String> = m.data()
+        .iter()
+        .flat_map(|v| {
+            let value = v.format();
+            value.split_whitespace().map(str::to_string).collect::<Vec<_>>()
+        })
+        .collect();
+    let (ptrfmt, ptr_records) = int_records(&ptrs);
+    let (indfmt, ind_records) = int_records(&rows);
+    let (valfmt, val_records) = match T::FIELD {
+        Field::Pattern => (String::new(), vec![]),
+        Field::Integer => int_records(&values),
+        Field::Real | Field::Complex => records(&values, REAL_WIDTH, "E", ".16"),
+    };
+    let (ptrcrd, indcrd, valcrd) = (ptr_records.len(), ind_records.len(), val_records.len());
+
+    let title: String = title.chars().take(72).collect();
+    let key: String = key.chars().take(8).collect();
+    let mut header = vec![format!("{:<72}{}", title, key)];
+    let totcrd = ptrcrd + indcrd + valcrd;
+    header.push(match variant {
+        Variant::HarwellBoeing => {
+            format!("{:>14}{:>14}{:>14}{:>14}{:>14}", totcrd, ptrcrd, indcrd, valcrd, 0)
+        }
+        Variant::RutherfordBoeing => {
+            format!("{:>14}{:>14}{:>14}{:>14}", totcrd, ptrcrd, indcrd, valcrd)
+        }
+    });
+    header.push(format!("{:<14}{:>14}{:>14}{:>14}{:>14}",
+                        mxtype,
+                        m.nrows,
+                        m.ncols,
+                        m.nnz(),
+                        0));
+    header.push(format!("{:<16}{:<16}{}", ptrfmt, indfmt, valfmt));
+
+    for record in header.iter().chain(&ptr_records).chain(&ind_records).chain(&val_records) {
+        writeln!(writer, "{}", record.trim_end())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use complex::Complex;
+    use error::{Error, Result};
+    use sparse::csc::CscMatrix;
+    use sparse::dok::DOKMatrix;
+    use sparse::io::mm::MatrixMarketElem;
+    use super::{read_harwell_boeing, write_harwell_boeing, write_rutherford_boeing,
+                FortranFormat};
+
+    /// Build a file from its header fields and data records.
+    fn hb_file(cards: &[usize], mxtype: &str, sizes: (u64, u64, usize), formats: &[&str],
+               data: &[&str])
+               -> String {
+        let mut lines = vec![format!("{:<72}{:<8}", "Test matrix", "TEST")];
+        lines.push(cards.iter().map(|c| format!("{:>14}", c)).collect());
+        lines.push(format!("{:<14}{:>14}{:>14}{:>14}{:>14}", mxtype, sizes.0, sizes.1, sizes.2, 0));
+        lines.push(formats.iter()
+            .zip(&[16, 16, 20])
+            .map(|(f, &w)| format!("{:<width$}", f, width = w))
+            .collect());
+        lines.extend(data.iter().map(|d| d.to_string()));
+        lines.join("\n") + "\n"
+    }
+
+    fn read<T: MatrixMarketElem>(text: &str) -> Result<DOKMatrix<T>> {
+        read_harwell_boeing::<T, _>(text.as_bytes()).map(|m| m.to_dok())
+    }
+
+    fn fields(format: &str, records: &[&'static str]) -> Vec<&'static str> {
+        FortranFormat::parse(format).unwrap().read_fields(records.iter().cloned()).unwrap()
+    }
+
+    #[test]
+    fn test_fortran_format() {
+        assert_eq!(fields("(4I5)", &["    1   22  333 4444    5"]),
+                   vec!["1", "22", "333", "4444"]);
+        assert_eq!(fields("(1P,2E10.3)", &[" 1.000E+00-2.500E-01", "3.0"]),
+                   vec!["1.000E+00", "-2.500E-01", "3.0"]);
+        assert_eq!(fields("(1PE10.3E2)", &["1.000E+00"]), vec!["1.000E+00"]);
+        assert_eq!(fields("(2(1X,F3.1))", &[" 1.5 2.5", " 3.5"]), vec!["1.5", "2.5", "3.5"]);
+        // Later records revert to the last top-level group.
+        assert_eq!(fields("(3X,2(I2))", &["abc 1 2", " 3 4"]), vec!["1", "2", "3", "4"]);
+        assert_eq!(fields(" ( 3 es12.4 ) ", &["  1.0000E+00  2.0000E+00"]),
+                   vec!["1.0000E+00", "2.0000E+00"]);
+
+        for bad in &["13I6", "(3A8)", "(I)", "(2X)", "(4I5", "(4I5)x"] {
+            assert!(FortranFormat::parse(bad).is_err(), "{}", bad);
+        }
+
+        // Repeat counts are bounded before the format is expanded.
+        assert_eq!(FortranFormat::parse("(999999999999I5)").unwrap_err(),
+                   Error::Parse {
+                       line: None,
+                       message: "invalid Fortran format \"(999999999999I5)\": more than 80 edits \
+                                 per record"
+                           .into(),
+                   });
+        for bad in &["(1000(1000(1000I1)))", "(99999999999999999999999I5)", "(41I2,40(I2))"] {
+            assert!(FortranFormat::parse(bad).is_err(), "{}", bad);
+        }
+        assert_eq!(fields("(80I1)", &["12"]), vec!["1", "2"]);
+    }
+
+    #[test]
+    fn test_read_unsymmetric() {
+        let text = hb_file(&[4, 1, 1, 2, 0],
+                           "RUA",
+                           (3, 3, 4),
+                           &["(4I5)", "(4I5)", "(2D20.12)"],
+                           &["    1    3    4    5",
+                             "    1    3    2    1",
+                             "                 1.0              -2.5D0",
+                             "                 3.0               4.0-3"]);
+        let m: DOKMatrix<f64> = read(&text).unwrap();
+        assert_eq!((m.nrows, m.ncols, m.nnz()), (3, 3, 4));
+        assert_eq!(m[(0, 0)], 1.0);
+        assert_eq!(m[(2, 0)], -2.5);
+        assert_eq!(m[(1, 1)], 3.0);
+        assert_eq!(m[(0, 2)], 0.004);
+    }
+
+    #[test]
+    fn test_read_symmetric_and_pattern() {
+        let text = hb_file(&[3, 1, 1, 1],
+                           "RSA",
+                           (2, 2, 2),
+                           &["(3I3)", "(2I3)", "(2E12.4)"],
+                           &["  1  3  3", "  1  2", "  2.0000E+00  5.0000E-01"]);
+        let m: DOKMatrix<f64> = read(&text).unwrap();
+        assert_eq!((m[(0, 0)], m[(1, 0)], m[(0, 1)], m[(1, 1)]), (2.0, 0.5, 0.5, 0.0));
+
+        let text = hb_file(&[2, 1, 1, 0],
+                           "PSA",
+                           (2, 2, 1),
+                           &["(3I3)", "(1I3)", ""],
+                           &["  1  2  2", "  2"]);
+        let m: DOKMatrix<bool> = read(&text).unwrap();
+        assert_eq!((m[(1, 0)], m[(0, 1)], m[(0, 0)]), (true, true, false));
+
+        let text = hb_file(&[3, 1, 1, 1],
+                           "CZA",
+                           (2, 2, 1),
+                           &["(3I3)", "(1I3)", "(2F5.1)"],
+                           &["  1  2  2", "  2", "  1.0  2.0"]);
+        let m: DOKMatrix<Complex<f64>> = read(&text).unwrap();
+        assert_eq!(m[(1, 0)], Complex::new(1.0, 2.0));
+        assert_eq!(m[(0, 1)], Complex::new(-1.0, -2.0));
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let formats = ["(4I5)", "(4I5)", "(2E20.12)"];
+        let data = ["    1    2    3", "    1    3", "                 1.0                 2.0"];
+        let parse_error = |line, message: &str| {
+            Error::Parse {
+                line,
+                message: message.into(),
+            }
+        };
+
+        assert_eq!(read::<f64>(&hb_file(&[3, 1, 1, 1], "RUE", (2, 2, 2), &formats, &data))
+                       .unwrap_err(),
+                   parse_error(Some(3), "only assembled matrices are supported, found \"RUE\""));
+        assert_eq!(read::<f64>(&hb_file(&[3, 1, 1, 1], "RUA", (2, 2, 2), &formats, &data))
+                       .unwrap_err(),
+                   parse_error(Some(6), "row index 3 out of range 1..=2"));
+        assert_eq!(read::<f64>(&hb_file(&[3, 1, 1, 1],
+                                        "RUA",
+                                        (2, 2, 2),
+                                        &["(4A5)", formats[1], formats[2]],
+                                        &data))
+                       .unwrap_err(),
+                   parse_error(Some(4), "invalid Fortran format \"(4A5)\": unsupported edit \
+                                         descriptor 'A'"));
+        assert_eq!(read::<f64>(&hb_file(&[4, 1, 1, 2], "RUA", (2, 2, 2), &formats, &data))
+                       .unwrap_err(),
+                   parse_error(None, "expected 8 lines, found 7"));
+        assert_eq!(read::<i32>(&hb_file(&[3, 1, 1, 1],
+                                        "RUA",
+                                        (2, 2, 2),
+                                        &formats,
+                                        &["    1    2    3", "    1    2", "                 1.0                 2.0"]))
+                       .unwrap_err(),
+                   parse_error(Some(7), "cannot read a real matrix into an integer one"));
+
+        // Counts that overflow when computing the length of a section.
+        let huge = hb_file(&[3, 1, 1, 1], "RUA", (2, 7777, 2), &formats, &data)
+            .replace("7777", &format!(" {}", u64::MAX));
+        assert_eq!(read::<f64>(&huge).unwrap_err(),
+                   parse_error(Some(3), &format!("count {} is too large", u64::MAX)));
+        let huge = hb_file(&[3, 7777, 7777, 1], "RUA", (2, 2, 2), &formats, &data)
+            .replace("7777", &format!(" {}", usize::MAX));
+        assert_eq!(read::<f64>(&huge).unwrap_err(),
+                   parse_error(Some(2), "line counts are too large"));
+
+        let skew = hb_file(&[3, 1, 1, 1],
+                           "RZA",
+                           (2, 2, 1),
+                           &["(3I3)", "(1I3)", "(1F5.1)"],
+                           &["  1  1  2", "  2", "  1.0"]);
+        assert_eq!(read::<f64>(&skew).unwrap_err(),
+                   parse_error(None, "nonzero diagonal entry (2, 2) in a skew-symmetric matrix"));
+    }
+
+    #[test]
+    fn test_write() {
+        let mut dok = DOKMatrix::<f64>::zeros(2, 3);
+        dok.set(1, 0, 2.5);
+        dok.set(0, 2, -0.1);
+        let mut out = vec![];
+        write_harwell_boeing(&mut out, &CscMatrix::from_dok(&dok), "A small matrix", "SMALL")
+            .unwrap();
+        let expected = [format!("{:<72}SMALL", "A small matrix"),
+                        format!("{:>14}{:>14}{:>14}{:>14}{:>14}", 3, 1, 1, 1, 0),
+                        format!("RRA{:>25}{:>14}{:>14}{:>14}", 2, 3, 2, 0),
+                        "(40I2)          (40I2)          (3E25.16)".to_string(),
+                        " 1 2 2 3".to_string(),
+                        " 2 1".to_string(),
+                        format!("{:>25}{:>25}", "2.5e0", "-1e-1")];
+        assert_eq!(String::from_utf8(out).unwrap(), expected.join("\n") + "\n");
+
+        let m = CscMatrix::from_dok(&DOKMatrix::<i32>::identity(2));
+        assert_eq!(write_harwell_boeing(vec![], &m, "", "").unwrap_err(),
+                   Error::InvalidStructure("Harwell-Boeing files cannot hold integer values"
+                       .into()));
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let mut dok = DOKMatrix::<f64>::zeros(30, 4);
+        for i in 0..30 {
+            dok.set(i, i % 4, (i as f64).sqrt() - 3.0);
+        }
+        dok.set(5, 3, 1e-300);
+        let m = CscMatrix::from_dok(&dok);
+        let mut out = vec![];
+        write_harwell_boeing(&mut out, &m, "roundtrip", "RT").unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.lines().all(|l| l.len() <= 80));
+        assert_eq!(read::<f64>(&text).unwrap(), dok);
+
+        let mut ints = DOKMatrix::<i64>::zeros(3, 3);
+        ints.set(2, 1, -1234567);
+        ints.set(0, 0, 8);
+        let mut out = vec![];
+        write_rutherford_boeing(&mut out, &CscMatrix::from_dok(&ints), "ints", "INTS").unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.lines().nth(2).unwrap().starts_with("IUA"));
+        assert_eq!(read::<i64>(&text).unwrap(), ints);
+
+        let mut complex = DOKMatrix::<Complex<f64>>::zeros(2, 2);
+        complex.set(0, 1, Complex::new(0.5, -1.5));
+        let mut out = vec![];
+        write_rutherford_boeing(&mut out, &CscMatrix::from_dok(&complex), "c", "C").unwrap();
+        assert_eq!(read::<Complex<f64>>(::std::str::from_utf8(&out).unwrap()).unwrap(),
+                   complex);
+
+        let mut pattern = DOKMatrix::<bool>::zeros(3, 2);
+        pattern.set(2, 1, true);
+        let mut out = vec![];
+        write_harwell_boeing(&mut out, &CscMatrix::from_dok(&pattern), "p", "P").unwrap();
+        assert_eq!(read::<bool>(::std::str::from_utf8(&out).unwrap()).unwrap(), pattern);
+    }
+}
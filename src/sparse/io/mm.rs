@@ -0,0 +1,759 @@
+//! The Matrix Market exchange format.
+//!
+//! A Matrix Market file starts with a header line of the form
+//! `%%MatrixMarket matrix <format> <field> <symmetry>`, followed by comment
+//! lines starting with `%`, a size line, and one line per element. The
+//! `coordinate` format lists `row col value` triplets with 1-based indices;
+//! the `array` format lists every element in column-major order. Symmetric,
+//! skew-symmetric and hermitian files store only the lower triangle.
+//!
+//! See <https://math.nist.gov/MatrixMarket/formats.html>.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::result;
+
+use complex::Complex;
+use error::{Error, Result};
+use sparse::dok::{DOKMatrix, MatrixElem, Order};
+
+/// Layout of the data lines of a Matrix Market file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// One `row col value` line per stored element.
+    Coordinate,
+    /// Every element, in column-major order.
+    Array,
+}
+
+/// Type of the values in a Matrix Market file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    Real,
+    Integer,
+    Complex,
+    /// Coordinates only, with no values.
+    Pattern,
+}
+
+/// Symmetry of the matrix stored in a Matrix Market file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Symmetry {
+    General,
+    /// `a[(i, j)] == a[(j, i)]`
+    Symmetric,
+    /// `a[(i, j)] == -a[(j, i)]`
+    SkewSymmetric,
+    /// `a[(i, j)] == conj(a[(j, i)])`
+    Hermitian,
+}
+
+impl Symmetry {
+    /// The value implied at `(j, i)` by an element read at `(i, j)` of a
+    /// file with this symmetry, or `None` if no other element is implied.
+    ///
+    /// Returns an error for diagonal elements the symmetry forbids: nonzero
+    /// ones in a skew-symmetric matrix, and ones that are not real in a
+    /// hermitian matrix. Coordinates in the message are 1-based.
+    pub(crate) fn mirror<T>(self, (i, j): (u64, u64), value: &T) -> result::Result<Option<T>, String>
+        where T: MatrixMarketElem
+    {
+        match self {
+            Symmetry::SkewSymmetric if i == j && !value.is_zero() => {
+                Err(format!("nonzero diagonal entry ({}, {}) in a skew-symmetric matrix",
+                            i + 1,
+                            j + 1))
+            }
+            Symmetry::Hermitian if i == j && value.conjugated() != *value => {
+                Err(format!("diagonal entry ({}, {}) in a hermitian matrix is not real",
+                            i + 1,
+                            j + 1))
+            }
+            Symmetry::General => Ok(None),
+            _ if i == j => Ok(None),
+            Symmetry::Symmetric => Ok(Some(value.clone())),
+            Symmetry::SkewSymmetric => Ok(Some(value.negated())),
+            Symmetry::Hermitian => Ok(Some(value.conjugated())),
+        }
+    }
+}
+
+/// Element types that can be read from and written to Matrix Market files.
+pub trait MatrixMarketElem: MatrixElem + PartialEq {
+    /// Field written to the header of files holding this type.
+    const FIELD: Field;
+
+    /// Parse a value from the tokens following the coordinates of a data
+    /// line of a file with the given field.
+    fn parse(field: Field, tokens: &[&str]) -> result::Result<Self, String>;
+
+    /// Format this value as the tokens of a data line.
+    fn format(&self) -> String;
+
+    /// The value stored at `(j, i)` of a skew-symmetric matrix with this
+    /// value at `(i, j)`.
+    fn negated(&self) -> Self;
+
+    /// The value stored at `(j, i)` of a hermitian matrix with this value at
+    /// `(i, j)`.
+    fn conjugated(&self) -> Self {
+        self.clone()
+    }
+}
+
+/// Parse a real number, accepting Fortran's `D` exponent marker.
+fn parse_real(token: &str) -> result::Result<f64, String> {
+    token.replace(['D', 'd'], "e")
+        .parse()
+        .map_err(|_| format!("invalid real value {:?}", token))
+}
+
+/// Return an error unless `tokens` holds exactly `n` values.
+fn expect_values(tokens: &[&str], n: usize) -> result::Result<(), String> {
+    if tokens.len() != n {
+        return Err(format!("expected {} value(s), found {}", n, tokens.len()));
+    }
+    Ok(())
+}
+
+macro_rules! impl_mm_float {
+    ($($t:ty)*) => {
+        $(
+            impl MatrixMarketElem for $t {
+                const FIELD: Field = Field::Real;
+
+                fn parse(field: Field, tokens: &[&str]) -> result::Result<$t, String> {
+                    match field {
+                        Field::Pattern => {
+                            expect_values(tokens, 0)?;
+                            Ok(1.0)
+                        }
+                        Field::Real | Field::Integer => {
+                            expect_values(tokens, 1)?;
+                            Ok(parse_real(tokens[0])? as $t)
+                        }
+                        Field::Complex => Err("cannot read a complex matrix into a real one".into()),
+                    }
+                }
+
+                fn format(&self) -> String {
+                    format!("{:e}", self)
+                }
+
+                fn negated(&self) -> $t {
+                    -*self
+                }
+            }
+        )*
+    }
+}
+
+impl_mm_float!(f32 f64);
+
+macro_rules! impl_mm_int {
+    ($($t:ty)*) => {
+        $(
+            impl MatrixMarketElem for $t {
+                const FIELD: Field = Field::Integer;
+
+                fn parse(field: Field, tokens: &[&str]) -> result::Result<$t, String> {
+                    match field {
+                        Field::Pattern => {
+                            expect_values(tokens, 0)?;
+                            Ok(1)
+                        }
+                        Field::Integer => {
+                            expect_values(tokens, 1)?;
+                            tokens[0]
+                                .parse()
+                                .map_err(|_| format!("invalid integer value {:?}", tokens[0]))
+                        }
+                        Field::Real | Field::Complex => {
+                            Err(format!("cannot read a {:?} matrix into an integer one", field)
+                                .to_lowercase())
+                        }
+                    }
+                }
+
+                fn format(&self) -> String {
+                    self.to_string()
+                }
+
+                fn negated(&self) -> $t {
+                    -*self
+                }
+            }
+        )*
+    }
+}
+
+impl_mm_int!(i8 i16 i32 i64 i128 isize);
+
+macro_rules! impl_mm_complex {
+    ($($t:ty)*) => {
+        $(
+            impl MatrixMarketElem for Complex<$t> {
+                const FIELD: Field = Field::Complex;
+
+                fn parse(field: Field, tokens: &[&str]) -> result::Result<Complex<$t>, String> {
+                    match field {
+                        Field::Complex => {
+                            expect_values(tokens, 2)?;
+                            Ok(Complex::new(parse_real(tokens[0])? as $t,
+                                            parse_real(tokens[1])? as $t))
+                        }
+                        _ => Ok(Complex::new(<$t as MatrixMarketElem>::parse(field, tokens)?, 0.0)),
+                    }
+                }
+
+                fn format(&self) -> String {
+                    format!("{:e} {:e}", self.re, self.im)
+                }
+
+                fn negated(&self) -> Complex<$t> {
+                    -*self
+                }
+
+                fn conjugated(&self) -> Complex<$t> {
+                    self.conj()
+                }
+            }
+        )*
+    }
+}
+
+impl_mm_complex!(f32 f64);
+
+impl MatrixMarketElem for bool {
+    const FIELD: Field = Field::Pattern;
+
+    fn parse(field: Field, tokens: &[&str]) -> result::Result<bool, String> {
+        match field {
+            Field::Pattern => {
+                expect_values(tokens, 0)?;
+                Ok(true)
+            }
+            _ => Err("only pattern matrices can be read into a boolean matrix".into()),
+        }
+    }
+
+    fn format(&self) -> String {
+        String::new()
+    }
+
+    fn negated(&self) -> bool {
+        *self
+    }
+}
+
+/// Build a parse error for the given 1-based line.
+fn parse_error(line: usize, message: String) -> Error {
+    Error::Parse {
+        line: Some(line),
+        message,
+    }
+}
+
+/// Parse the `%%MatrixMarket` header line.
+fn parse_header(line: &str) -> result::Result<(Format, Field, Symmetry), String> {
+    let words: Vec<String> = line.split_whitespace().map(|w| w.to_lowercase()).collect();
+    if words.len() != 5 || words[0] != "%%matrixmarket" {
+        return Err("expected header of the form \
+                    '%%MatrixMarket matrix <format> <field> <symmetry>'"
+            .into());
+    }
+    if words[1] != "matrix" {
+        return Err(format!("unsupported object {:?}", words[1]));
+    }
+    let format = match words[2].as_str() {
+        "coordinate" => Format::Coordinate,
+        "array" => Format::Array,
+        other => return Err(format!("unknown format {:?}", other)),
+    };
+    let field = match words[3].as_str() {
+        "real" | "double" => Field::Real,
+        "integer" => Field::Integer,
+        "complex" => Field::Complex,
+        "pattern" => Field::Pattern,
+        other => return Err(format!("unknown field {:?}", other)),
+    };
+    let symmetry = match words[4].as_str() {
+        "general" => Symmetry::General,
+        "symmetric" => Symmetry::Symmetric,
+        "skew-symmetric" => Symmetry::SkewSymmetric,
+        "hermitian" => Symmetry::Hermitian,
+        other => return Err(format!("unknown symmetry {:?}", other)),
+    };
+    check_header(format, field, symmetry)?;
+    Ok((format, field, symmetry))
+}
+
+/// Return an error if the header describes a combination the format does not
+/// allow.
+fn check_header(format: Format, field: Field, symmetry: Symmetry) -> result::Result<(), String> {
+    if format == Format::Array && field == Field::Pattern {
+        return Err("array format cannot have the pattern field".into());
+    }
+    if symmetry == Symmetry::Hermitian && field != Field::Complex {
+        return Err("hermitian symmetry requires the complex field".into());
+    }
+    if symmetry == Symmetry::SkewSymmetric && field == Field::Pattern {
+        return Err("skew-symmetric symmetry cannot have the pattern field".into());
+    }
+    Ok(())
+}
+
+/// Parse a 1-based index no larger than `max`, returning it 0-based.
+fn parse_index(token: &str, max: u64) -> result::Result<u64, String> {
+    match token.parse::<u64>() {
+        Ok(index) if index >= 1 && index <= max => Ok(index - 1),
+        Ok(index) => Err(format!("index {} out of range 1..={}", index, max)),
+        Err(_) => Err(format!("invalid index {:?}", token)),
+    }
+}
+
+/// Read a matrix in Matrix Market format.
+///
+/// Symmetric, skew-symmetric and hermitian matrices are expanded to store
+/// both triangles. Zeros listed in a coordinate file are kept as explicit
+/// zeros, while zeros in an array file are not stored.
+/// Any problem with the contents is reported as `Error::Parse` with the
+/// number of the offending line.
+///
+/// # Arguments
+///
+/// * `reader` - Source of the file contents.
+pub fn read_matrix_market<T, R>(reader: R) -> Result<DOKMatrix<T>>
+    where T: MatrixMarketElem,
+          R: Read
+{
+    // Data lines paired with their 1-based line numbers, skipping comments
+    // and blank lines.
+    let mut lines = BufReader::new(reader).lines().enumerate().filter_map(|(n, line)| {
+        match line {
+            Ok(ref l) if l.trim().is_empty() || (n > 0 && l.starts_with('%')) => None,
+            line => Some((n + 1, line)),
+        }
+    });
+
+    let (format, field, symmetry) = match lines.next() {
+        Some((n, line)) => parse_header(&line?).map_err(|e| parse_error(n, e))?,
+        None => {
+            return Err(Error::Parse {
+                line: None,
+                message: "empty input".into(),
+            })
+        }
+    };
+
+    let (size_line, size) = match lines.next() {
+        Some((n, line)) => (n, line?),
+        None => {
+            return Err(Error::Parse {
+                line: None,
+                message: "missing size line".into(),
+            })
+        }
+    };
+    let sizes: Vec<u64> = size.split_whitespace()
+        .map(|t| t.parse().map_err(|_| parse_error(size_line, format!("invalid size {:?}", t))))
+        .collect::<Result<_>>()?;
+    let expected_sizes = if format == Format::Coordinate { 3 } else { 2 };
+    if sizes.len() != expected_sizes {
+        return Err(parse_error(size_line,
+                               format!("expected {} sizes, found {}", expected_sizes, sizes.len())));
+    }
+    let (nrows, ncols) = (sizes[0], sizes[1]);
+    if symmetry != Symmetry::General && nrows != ncols {
+        return Err(parse_error(size_line,
+                               format!("{:?} matrix must be square, found shape ({}, {})",
+                                       symmetry,
+                                       nrows,
+                                       ncols)));
+    }
+
+    // Coordinates of the elements of an array file, in file order.
+    let mut array_coords = (0..ncols).flat_map(|j| {
+        let start = match symmetry {
+            Symmetry::General => 0,
+            Symmetry::Symmetric | Symmetry::Hermitian => j,
+            Symmetry::SkewSymmetric => j + 1,
+        };
+        (start..nrows).map(move |i| (i, j))
+    });
+    let expected = match format {
+        Format::Coordinate => sizes[2],
+        Format::Array => {
+            // Counts are computed in 128 bits so that a huge size line
+            // cannot overflow them.
+            let n = u128::from(nrows);
+            let triangle = match symmetry {
+                Symmetry::General => n * u128::from(ncols),
+                Symmetry::Symmetric | Symmetry::Hermitian => n * (n + 1) / 2,
+                Symmetry::SkewSymmetric => n * n.saturating_sub(1) / 2,
+            };
+            if triangle > u128::from(u64::MAX) {
+                return Err(parse_error(size_line,
+                                       format!("array of shape ({}, {}) has too many entries",
+                                               nrows,
+                                               ncols)));
+            }
+            triangle as u64
+        }
+    };
+
+    let mut elems = HashMap::<(u64, u64), T>::new();
+    let mut count = 0;
+    for (n, line) in lines {
+        let line = line?;
+        if count == expected {
+            return Err(parse_error(n, format!("expected only {} entries", expected)));
+        }
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let (coords, values) = match format {
+            Format::Coordinate => {
+                if tokens.len() < 2 {
+                    return Err(parse_error(n, "expected row and column indices".into()));
+                }
+                let i = parse_index(tokens[0], nrows).map_err(|e| parse_error(n, e))?;
+                let j = parse_index(tokens[1], ncols).map_err(|e| parse_error(n, e))?;
+                ((i, j), &tokens[2..])
+            }
+            Format::Array => (array_coords.next().unwrap_or((0, 0)), &tokens[..]),
+        };
+        let value = T::parse(field, values).map_err(|e| parse_error(n, e))?;
+        count += 1;
+        if format == Format::Array && value.is_zero() {
+            continue;
+        }
+        insert(&mut elems, coords, value, symmetry).map_err(|e| parse_error(n, e))?;
+    }
+    if count != expected {
+        return Err(Error::Parse {
+            line: None,
+            message: format!("expected {} entries, found {}", expected, count),
+        });
+    }
+    DOKMatrix::try_new(nrows, ncols, elems)
+}
+
+/// Store an element read from a file, along with its mirror image if the
+/// file is not general.
+fn insert<T>(elems: &mut HashMap<(u64, u64), T>,
+             (i, j): (u64, u64),
+             value: T,
+             symmetry: Symmetry)
+             -> result::Result<(), String>
+    where T: MatrixMarketElem
+{
+    if elems.contains_key(&(i, j)) {
+        return Err(format!("duplicate entry ({}, {})", i + 1, j + 1));
+    }
+    if symmetry != Symmetry::General && i < j {
+        return Err(format!("entry ({}, {}) lies above the diagonal of a {:?} matrix",
+                           i + 1,
+                           j + 1,
+                           symmetry));
+    }
+    if let Some(mirror) = symmetry.mirror((i, j), &value)? {
+        elems.insert((j, i), mirror);
+    }
+    elems.insert((i, j), value);
+    Ok(())
+}
+
+/// Write a matrix in Matrix Market coordinate format with general symmetry.
+///
+/// Elements are written in column-major order.
+///
+/// # Arguments
+///
+/// * `writer` - Destination of the file contents.
+/// * `m` - Matrix to write.
+pub fn write_matrix_market<T, W>(writer: W, m: &DOKMatrix<T>) -> Result<()>
+    where T: MatrixMarketElem,
+          W: Write
+{
+    write_matrix_market_as(writer, m, Format::Coordinate, Symmetry::General)
+}
+
+/// Write a matrix in Matrix Market format with the given layout and
+/// symmetry.
+///
+/// Only the lower triangle of a matrix with non-general symmetry is written.
+/// Returns an error if `m` does not have the requested symmetry, or if
+/// `format` and the element type form a combination the format does not
+/// allow.
+///
+/// # Arguments
+///
+/// * `writer` - Destination of the file contents.
+/// * `m` - Matrix to write.
+/// * `format` - Layout of the data lines.
+/// * `symmetry` - Symmetry to declare in the header.
+pub fn write_matrix_market_as<T, W>(mut writer: W,
+                                    m: &DOKMatrix<T>,
+                                    format: Format,
+                                    symmetry: Symmetry)
+                                    -> Result<()>
+    where T: MatrixMarketElem,
+          W: Write
+{
+    check_header(format, T::FIELD, symmetry).map_err(Error::InvalidStructure)?;
+    check_symmetry(m, symmetry)?;
+
+    let keep = |i: u64, j: u64| match symmetry {
+        Symmetry::General => true,
+        Symmetry::Symmetric | Symmetry::Hermitian => i >= j,
+        Symmetry::SkewSymmetric => i > j,
+    };
+    let name = |s: &str| s.to_lowercase().replace("skewsymmetric", "skew-symmetric");
+    writeln!(writer,
+             "%%MatrixMarket matrix {} {} {}",
+             name(&format!("{:?}", format)),
+             name(&format!("{:?}", T::FIELD)),
+             name(&format!("{:?}", symmetry)))?;
+
+    match format {
+        Format::Coordinate => {
+            let elems: Vec<_> = m.iter_sorted(Order::ColMajor)
+                .filter(|&((i, j), _)| keep(i, j))
+                .collect();
+            writeln!(writer, "{} {} {}", m.nrows, m.ncols, elems.len())?;
+            for ((i, j), v) in elems {
+                let value = v.format();
+                if value.is_empty() {
+                    writeln!(writer, "{} {}", i + 1, j + 1)?;
+                } else {
+                    writeln!(writer, "{} {} {}", i + 1, j + 1, value)?;
+                }
+            }
+        }
+        Format::Array => {
+            writeln!(writer, "{} {}", m.nrows, m.ncols)?;
+            for j in 0..m.ncols {
+                for i in (0..m.nrows).filter(|&i| keep(i, j)) {
+                    writeln!(writer, "{}", m[(i, j)].format())?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Return an error unless `m` has the given symmetry.
+fn check_symmetry<T>(m: &DOKMatrix<T>, symmetry: Symmetry) -> Result<()>
+    where T: MatrixMarketElem
+{
+    if symmetry == Symmetry::General {
+        return Ok(());
+    }
+    if m.nrows != m.ncols {
+        return Err(Error::InvalidStructure(format!("{:?} matrix must be square, found shape \
+                                                    ({}, {})",
+                                                   symmetry,
+                                                   m.nrows,
+                                                   m.ncols)));
+    }
+    for ((i, j), v) in m.iter_sorted(Order::RowMajor) {
+        let expected = match symmetry {
+            Symmetry::General => unreachable!(),
+            Symmetry::Symmetric => v.clone(),
+            Symmetry::SkewSymmetric => v.negated(),
+            Symmetry::Hermitian => v.conjugated(),
+        };
+        if m[(j, i)] != expected {
+            return Err(Error::InvalidStructure(format!("Matrix is not {:?}: elements ({}, {}) \
+                                                        and ({}, {}) do not match",
+                                                       symmetry,
+                                                       i,
+                                                       j,
+                                                       j,
+                                                       i)));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use complex::Complex;
+    use error::Error;
+    use sparse::dok::DOKMatrix;
+    use super::{read_matrix_market, write_matrix_market, write_matrix_market_as, Format,
+                Symmetry};
+
+    fn read<T: super::MatrixMarketElem>(text: &str) -> ::error::Result<DOKMatrix<T>> {
+        read_matrix_market(text.as_bytes())
+    }
+
+    fn write<T: super::MatrixMarketElem>(m: &DOKMatrix<T>, format: Format, symmetry: Symmetry)
+                                         -> String {
+        let mut out = vec![];
+        write_matrix_market_as(&mut out, m, format, symmetry).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn parse_error(line: Option<usize>, message: &str) -> Error {
+        Error::Parse {
+            line,
+            message: message.into(),
+        }
+    }
+
+    #[test]
+    fn test_read_coordinate() {
+        let text = "%%MatrixMarket matrix coordinate real general\n\
+                    % A comment\n\
+                    %\n\
+                    3 4 3\n\
+                    1 1 1.5\n\
+                    3 2 -2e3\n\
+                    \n\
+                    2 4 1.0D-1\n";
+        let m: DOKMatrix<f64> = read(text).unwrap();
+        assert_eq!((m.nrows, m.ncols, m.nnz()), (3, 4, 3));
+        assert_eq!(m[(0, 0)], 1.5);
+        assert_eq!(m[(2, 1)], -2000.0);
+        assert_eq!(m[(1, 3)], 0.1);
+    }
+
+    #[test]
+    fn test_read_array() {
+        let text = "%%MatrixMarket matrix array integer general\n\
+                    2 3\n1\n0\n2\n3\n0\n-4\n";
+        let m: DOKMatrix<i64> = read(text).unwrap();
+        assert_eq!(m.nnz(), 4);
+        assert_eq!(m[(0, 0)], 1);
+        assert_eq!(m[(0, 1)], 2);
+        assert_eq!(m[(1, 1)], 3);
+        assert_eq!(m[(1, 2)], -4);
+
+        let text = "%%MatrixMarket matrix array real symmetric\n2 2\n1\n2\n3\n";
+        let m: DOKMatrix<f64> = read(text).unwrap();
+        assert_eq!((m[(0, 0)], m[(1, 0)], m[(0, 1)], m[(1, 1)]), (1.0, 2.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn test_read_symmetries() {
+        let text = "%%MatrixMarket matrix coordinate integer symmetric\n3 3 2\n2 1 5\n3 3 1\n";
+        let m: DOKMatrix<i32> = read(text).unwrap();
+        assert_eq!(m.nnz(), 3);
+        assert_eq!((m[(1, 0)], m[(0, 1)], m[(2, 2)]), (5, 5, 1));
+
+        let text = "%%MatrixMarket matrix coordinate real skew-symmetric\n2 2 1\n2 1 5\n";
+        let m: DOKMatrix<f64> = read(text).unwrap();
+        assert_eq!((m[(1, 0)], m[(0, 1)]), (5.0, -5.0));
+
+        let text = "%%MatrixMarket matrix coordinate complex hermitian\n2 2 2\n1 1 2 0\n2 1 1 3\n";
+        let m: DOKMatrix<Complex<f64>> = read(text).unwrap();
+        assert_eq!(m[(0, 0)], Complex::new(2.0, 0.0));
+        assert_eq!(m[(1, 0)], Complex::new(1.0, 3.0));
+        assert_eq!(m[(0, 1)], Complex::new(1.0, -3.0));
+    }
+
+    #[test]
+    fn test_read_pattern() {
+        let text = "%%MatrixMarket matrix coordinate pattern general\n2 2 2\n1 2\n2 1\n";
+        let m: DOKMatrix<bool> = read(text).unwrap();
+        assert_eq!((m[(0, 1)], m[(1, 0)], m[(0, 0)]), (true, true, false));
+        let m: DOKMatrix<f64> = read(text).unwrap();
+        assert_eq!(m[(0, 1)], 1.0);
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let header = "%%MatrixMarket matrix coordinate real general\n";
+        assert_eq!(read::<f64>("%%MatrixMarket matrix coordinate real banded\n").unwrap_err(),
+                   parse_error(Some(1), "unknown symmetry \"banded\""));
+        assert_eq!(read::<f64>(&format!("{}2 2 1\n1 3 1.0\n", header)).unwrap_err(),
+                   parse_error(Some(3), "index 3 out of range 1..=2"));
+        assert_eq!(read::<f64>(&format!("{}% note\n2 2 2\n1 1 1.0\n2 2 x\n", header)).unwrap_err(),
+                   parse_error(Some(5), "invalid real value \"x\""));
+        assert_eq!(read::<f64>(&format!("{}2 2 2\n1 1 1.0\n", header)).unwrap_err(),
+                   parse_error(None, "expected 2 entries, found 1"));
+        assert_eq!(read::<f64>(&format!("{}2 2 1\n1 1 1.0\n2 2 1.0\n", header)).unwrap_err(),
+                   parse_error(Some(4), "expected only 1 entries"));
+        assert_eq!(read::<f64>(&format!("{}2 2 2\n1 1 1.0\n1 1 2.0\n", header)).unwrap_err(),
+                   parse_error(Some(4), "duplicate entry (1, 1)"));
+        assert_eq!(read::<i32>(&format!("{}1 1 1\n1 1 1.0\n", header)).unwrap_err(),
+                   parse_error(Some(3), "cannot read a real matrix into an integer one"));
+
+        let symmetric = "%%MatrixMarket matrix coordinate real symmetric\n2 2 1\n1 2 1.0\n";
+        assert_eq!(read::<f64>(symmetric).unwrap_err(),
+                   parse_error(Some(3), "entry (1, 2) lies above the diagonal of a Symmetric matrix"));
+        assert_eq!(read::<f64>("%%MatrixMarket matrix array pattern general\n").unwrap_err(),
+                   parse_error(Some(1), "array format cannot have the pattern field"));
+
+        let huge = "%%MatrixMarket matrix array real general\n4294967296 4294967296\n";
+        assert_eq!(read::<f64>(huge).unwrap_err(),
+                   parse_error(Some(2),
+                               "array of shape (4294967296, 4294967296) has too many entries"));
+        let hermitian = "%%MatrixMarket matrix coordinate complex hermitian\n2 2 1\n2 2 1 1\n";
+        assert_eq!(read::<Complex<f64>>(hermitian).unwrap_err(),
+                   parse_error(Some(3), "diagonal entry (2, 2) in a hermitian matrix is not real"));
+        let skew = "%%MatrixMarket matrix coordinate real skew-symmetric\n2 2 2\n1 1 0\n2 2 1\n";
+        assert_eq!(read::<f64>(skew).unwrap_err(),
+                   parse_error(Some(4), "nonzero diagonal entry (2, 2) in a skew-symmetric matrix"));
+    }
+
+    #[test]
+    fn test_write_coordinate() {
+        let mut m = DOKMatrix::<f64>::zeros(2, 3);
+        m.set(1, 0, 2.5);
+        m.set(0, 2, -1.0);
+        let mut out = vec![];
+        write_matrix_market(&mut out, &m).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(),
+                   "%%MatrixMarket matrix coordinate real general\n\
+                    2 3 2\n\
+                    2 1 2.5e0\n\
+                    1 3 -1e0\n");
+    }
+
+    #[test]
+    fn test_write_symmetric_and_array() {
+        let mut m = DOKMatrix::<i32>::zeros(2, 2);
+        m.set(0, 0, 1);
+        m.set(1, 0, 7);
+        m.set(0, 1, 7);
+        assert_eq!(write(&m, Format::Coordinate, Symmetry::Symmetric),
+                   "%%MatrixMarket matrix coordinate integer symmetric\n2 2 2\n1 1 1\n2 1 7\n");
+        assert_eq!(write(&m, Format::Array, Symmetry::General),
+                   "%%MatrixMarket matrix array integer general\n2 2\n1\n7\n7\n0\n");
+
+        let mut out = vec![];
+        let err = write_matrix_market_as(&mut out, &m, Format::Coordinate, Symmetry::SkewSymmetric)
+            .unwrap_err();
+        assert_eq!(err,
+                   Error::InvalidStructure("Matrix is not SkewSymmetric: elements (0, 0) and \
+                                            (0, 0) do not match"
+                       .into()));
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let mut m = DOKMatrix::<Complex<f64>>::zeros(3, 3);
+        m.set(0, 0, Complex::new(1.0, 0.0));
+        m.set(2, 1, Complex::new(0.1, -3.0));
+        m.set(1, 2, Complex::new(0.1, 3.0));
+        for &format in &[Format::Coordinate, Format::Array] {
+            for &symmetry in &[Symmetry::General, Symmetry::Hermitian] {
+                let text = write(&m, format, symmetry);
+                assert_eq!(read::<Complex<f64>>(&text).unwrap(), m);
+            }
+        }
+
+        let mut p = DOKMatrix::<bool>::zeros(2, 3);
+        p.set(1, 2, true);
+        let text = write(&p, Format::Coordinate, Symmetry::General);
+        assert_eq!(text, "%%MatrixMarket matrix coordinate pattern general\n2 3 1\n2 3\n");
+        assert_eq!(read::<bool>(&text).unwrap(), p);
+
+        // Values survive the text round trip exactly.
+        let mut f = DOKMatrix::<f64>::zeros(1, 2);
+        f.set(0, 0, 0.1 + 0.2);
+        f.set(0, 1, 1e-300);
+        assert_eq!(read::<f64>(&write(&f, Format::Array, Symmetry::General)).unwrap(), f);
+    }
+}
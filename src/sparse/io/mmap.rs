@@ -0,0 +1,510 @@
+//! Read-only CSR matrices backed by memory-mapped files.
+//!
+//! A `MmapCsrMatrix` maps a file written by `CsrMatrix::write_to` instead of
+//! reading it, so the operating system pages the matrix in as rows are
+//! touched and evicts it under memory pressure. Matrices larger than memory
+//! can be used this way, and every process mapping the same file shares one
+//! copy of it in the page cache.
+//!
+//! The sections of the binary format start at multiples of 8 bytes, so on
+//! little-endian platforms the indices and values are used in place,
+//! without decoding.
+
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{self, Read};
+use std::mem;
+use std::ops::{Add, Index, Mul};
+use std::os::raw::{c_int, c_void};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::ptr;
+use std::slice;
+
+use complex::Complex;
+use error::{Error, Result};
+use sparse::compressed::check_compressed;
+use sparse::csr::CsrMatrix;
+use sparse::io::binary::{invalid, BinaryElem, Header, Layout, HEADER_LEN};
+use sparse::io::crc::crc32;
+use sparse::semiring::{PlusTimes, Semiring};
+
+const PROT_READ: c_int = 1;
+const MAP_SHARED: c_int = 1;
+
+extern "C" {
+    // The offset is an `off_t`, which is pointer-sized on the platforms we
+    // support. We only ever pass zero.
+    fn mmap(addr: *mut c_void,
+            len: usize,
+            prot: c_int,
+            flags: c_int,
+            fd: c_int,
+            offset: isize)
+            -> *mut c_void;
+    fn munmap(addr: *mut c_void, len: usize) -> c_int;
+}
+
+/// A shared, read-only mapping of the start of a file.
+struct Mmap {
+    ptr: *mut c_void,
+    len: usize,
+}
+
+// The mapping is never written through, so it can be read from any thread.
+unsafe impl Send for Mmap {}
+unsafe impl Sync for Mmap {}
+
+impl Mmap {
+    /// Map the first `len` bytes of `file`, where `len` is nonzero.
+    fn map(file: &File, len: usize) -> io::Result<Mmap> {
+        let ptr = unsafe {
+            mmap(ptr::null_mut(), len, PROT_READ, MAP_SHARED, file.as_raw_fd(), 0)
+        };
+        if ptr as isize == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Mmap { ptr, len })
+    }
+
+    fn bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        unsafe {
+            munmap(self.ptr, self.len);
+        }
+    }
+}
+
+/// Element types whose values can be used in place in a mapped file.
+///
+/// # Safety
+///
+/// On little-endian platforms, any bytes written by `BinaryElem::write_le`
+/// and accepted by `is_valid` must be a valid value of the type, which must
+/// have no padding and an alignment of at most 8 bytes.
+pub unsafe trait MmapElem: BinaryElem {
+    /// Check that the values of a file, in its byte order, are valid values
+    /// of this type.
+    fn is_valid(_bytes: &[u8]) -> bool {
+        true
+    }
+}
+
+unsafe impl MmapElem for i8 {}
+unsafe impl MmapElem for i16 {}
+unsafe impl MmapElem for i32 {}
+unsafe impl MmapElem for i64 {}
+unsafe impl MmapElem for u8 {}
+unsafe impl MmapElem for u16 {}
+unsafe impl MmapElem for u32 {}
+unsafe impl MmapElem for u64 {}
+unsafe impl MmapElem for f32 {}
+unsafe impl MmapElem for f64 {}
+unsafe impl MmapElem for Complex<f32> {}
+unsafe impl MmapElem for Complex<f64> {}
+
+unsafe impl MmapElem for bool {
+    fn is_valid(bytes: &[u8]) -> bool {
+        bytes.iter().all(|&b| b <= 1)
+    }
+}
+
+/// A read-only Compressed Sparse Row Matrix stored in a memory-mapped file.
+pub struct MmapCsrMatrix<T>
+    where T: MmapElem
+{
+    pub nrows: u64,
+    pub ncols: u64,
+    map: Mmap,
+    nnz: usize,
+    // Offsets of the indices and values sections in the mapping.
+    indices_offset: usize,
+    data_offset: usize,
+    // Absent elements are returned from `Index` as a reference to this value.
+    zero: T,
+}
+
+impl<T> MmapCsrMatrix<T>
+    where T: MmapElem
+{
+    /// Map a file written by `CsrMatrix::write_to`.
+    ///
+    /// The header and the `indptr` and `indices` sections are checked, so
+    /// that the other methods cannot panic on a malformed file; the values
+    /// are only read if `T` is `bool`. Returns an error if the header is
+    /// malformed, describes a matrix of another layout or element type, or
+    /// does not match the length of the file, or if the arrays do not
+    /// describe a valid matrix. The checksum is not checked; see `verify`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the file to map.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated, by this or any other
+    /// process, while the matrix is alive. Slices returned by this matrix
+    /// borrow the file's pages directly: truncating the file makes reads
+    /// fault, and modifying it can change values, including into invalid
+    /// `bool`s, behind a shared reference.
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        if cfg!(target_endian = "big") {
+            return Err(invalid("memory-mapped matrices require a little-endian platform".into()));
+        }
+        let mut file = File::open(path)?;
+        let mut header = [0; HEADER_LEN];
+        file.read_exact(&mut header)?;
+        let header = Header::parse(&header, Layout::Csr, T::ELEM_TYPE)?;
+
+        // Sizes are computed in 128 bits so that a corrupt header cannot
+        // overflow them.
+        let len = file.metadata()?.len();
+        let (indptr_len, nnz) = (u128::from(header.nrows) + 1, u128::from(header.nnz));
+        let expected = HEADER_LEN as u128 + 8 * (indptr_len + nnz) +
+                       nnz * T::ELEM_TYPE.size() as u128 + 4;
+        if u128::from(len) != expected {
+            return Err(invalid(format!("expected a file of {} bytes, found {}", expected, len)));
+        }
+        let len = usize::try_from(len)
+            .map_err(|_| invalid(format!("file of {} bytes is too large to map", len)))?;
+
+        let indices_offset = HEADER_LEN + 8 * indptr_len as usize;
+        let data_offset = indices_offset + 8 * nnz as usize;
+        let map = Mmap::map(&file, len)?;
+        let values = &map.bytes()[data_offset..len - 4];
+        if !T::is_valid(values) {
+            return Err(invalid(format!("file holds invalid values of type {:?}", T::ELEM_TYPE)));
+        }
+        let m = MmapCsrMatrix {
+            nrows: header.nrows,
+            ncols: header.ncols,
+            map,
+            nnz: nnz as usize,
+            indices_offset,
+            data_offset,
+            zero: T::zero(),
+        };
+        let indptr: Vec<usize> = m.indptr()
+            .iter()
+            .map(|&p| usize::try_from(p).unwrap_or(usize::MAX))
+            .collect();
+        check_compressed("CSR", "row", m.nrows, m.ncols, &indptr, m.indices(), m.nnz)?;
+        Ok(m)
+    }
+
+    /// Check the checksum of the file, reading the whole file.
+    ///
+    /// The structure of the matrix is checked by `open`; this catches
+    /// corrupt values that a valid structure cannot.
+    pub fn verify(&self) -> Result<()> {
+        let (body, checksum) = self.map.bytes().split_at(self.map.len - 4);
+        if crc32(body) != u32::from_le_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]) {
+            return Err(invalid("checksum mismatch: the file is corrupt".into()));
+        }
+        Ok(())
+    }
+
+    /// View `len` elements of type `U` starting at `offset` in the mapping.
+    fn section<U>(&self, offset: usize, len: usize) -> &[U] {
+        let bytes = &self.map.bytes()[offset..offset + len * mem::size_of::<U>()];
+        // The mapping is page-aligned and every section starts at a multiple
+        // of 8 bytes.
+        debug_assert_eq!(bytes.as_ptr() as usize % mem::align_of::<U>(), 0);
+        unsafe { slice::from_raw_parts(bytes.as_ptr() as *const U, len) }
+    }
+
+    /// Get the column indices and values stored in row `row`.
+    pub fn row(&self, row: u64) -> (&[u64], &[T]) {
+        if row >= self.nrows {
+            panic!("Out of bounds row {row} for sparse matrix of shape \
+                    ({nrows}, {ncols})",
+                   row = row,
+                   nrows = self.nrows,
+                   ncols = self.ncols)
+        }
+        let indptr = self.indptr();
+        let (start, end) = (indptr[row as usize] as usize, indptr[row as usize + 1] as usize);
+        (&self.indices()[start..end], &self.data()[start..end])
+    }
+
+    /// Get the element at coordinate (row, col), or `None` if (row, col)
+    /// lies outside of the matrix.
+    pub fn get(&self, row: u64, col: u64) -> Option<&T> {
+        if row >= self.nrows || col >= self.ncols {
+            return None;
+        }
+        let (cols, values) = self.row(row);
+        match cols.binary_search(&col) {
+            Ok(pos) => Some(&values[pos]),
+            Err(_) => Some(&self.zero),
+        }
+    }
+
+    /// Number of explicitly stored elements.
+    pub fn nnz(&self) -> usize {
+        self.nnz
+    }
+
+    /// Row offsets into `indices` and `data`.
+    pub fn indptr(&self) -> &[u64] {
+        self.section(HEADER_LEN, self.nrows as usize + 1)
+    }
+
+    /// Column index of each stored element.
+    pub fn indices(&self) -> &[u64] {
+        self.section(self.indices_offset, self.nnz)
+    }
+
+    /// Value of each stored element.
+    pub fn data(&self) -> &[T] {
+        self.section(self.data_offset, self.nnz)
+    }
+
+    /// Copy this matrix into memory.
+    pub fn to_csr(&self) -> Result<CsrMatrix<T>> {
+        let indptr = self.indptr()
+            .iter()
+            .map(|&p| usize::try_from(p).unwrap_or(usize::MAX))
+            .collect();
+        CsrMatrix::try_new(self.nrows,
+                           self.ncols,
+                           indptr,
+                           self.indices().to_vec(),
+                           self.data().to_vec())
+    }
+
+    /// Compute the matrix-vector product `self * x` over `semiring`.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Dense vector of length `self.ncols`.
+    /// * `semiring` - Operations used to combine elements.
+    pub fn mxv<S>(&self, x: &[T], semiring: S) -> Vec<T>
+        where S: Semiring<T>
+    {
+        self.try_mxv(x, semiring).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Compute the matrix-vector product `self * x` over `semiring`,
+    /// returning an error if `x` does not have length `self.ncols`.
+    pub fn try_mxv<S>(&self, x: &[T], semiring: S) -> Result<Vec<T>>
+        where S: Semiring<T>
+    {
+        if x.len() as u64 != self.ncols {
+            return Err(Error::ShapeMismatch {
+                op: "matvec",
+                left: (self.nrows, self.ncols),
+                right: (x.len() as u64, 1),
+            });
+        }
+        Ok((0..self.nrows)
+            .map(|row| {
+                let (cols, values) = self.row(row);
+                cols.iter().zip(values).fold(semiring.zero(), |acc, (&col, v)| {
+                    semiring.add(acc, semiring.mul(v.clone(), x[col as usize].clone()))
+                })
+            })
+            .collect())
+    }
+}
+
+impl<T> MmapCsrMatrix<T>
+    where T: MmapElem + Add<Output = T> + Mul<Output = T>
+{
+    /// Compute the matrix-vector product `self * x`.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Dense vector of length `self.ncols`.
+    pub fn matvec(&self, x: &[T]) -> Vec<T> {
+        self.mxv(x, PlusTimes)
+    }
+
+    /// Compute the matrix-vector product `self * x`, returning an error if
+    /// `x` does not have length `self.ncols`.
+    pub fn try_matvec(&self, x: &[T]) -> Result<Vec<T>> {
+        self.try_mxv(x, PlusTimes)
+    }
+}
+
+impl<T> Index<(u64, u64)> for MmapCsrMatrix<T>
+    where T: MmapElem
+{
+    type Output = T;
+
+    /// Get the element at coordinate (row, col).
+    fn index(&self, (row, col): (u64, u64)) -> &T {
+        self.get(row, col).unwrap_or_else(|| {
+            panic!("{}",
+                   Error::OutOfBounds {
+                       index: (row, col),
+                       shape: (self.nrows, self.ncols),
+                   })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+    use std::process;
+
+    use complex::Complex;
+    use error::{Error, Result};
+    use sparse::csr::CsrMatrix;
+    use sparse::dok::DOKMatrix;
+    use sparse::io::binary::BinaryElem;
+    use sparse::io::crc::crc32;
+    use sparse::semiring::MaxPlus;
+    use util::itertools::cartesian_product;
+    use super::{MmapCsrMatrix, MmapElem};
+
+    fn example() -> CsrMatrix<f64> {
+        // [[1, 0, 2, 0],
+        //  [0, 0, 0, 0],
+        //  [0, 5, 0, 6]]
+        CsrMatrix::new(3,
+                       4,
+                       vec![0, 2, 2, 4],
+                       vec![0, 2, 1, 3],
+                       vec![1.0, 2.0, 5.0, 6.0])
+    }
+
+    /// Write `contents` to a file that is removed when the guard is dropped.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, contents: &[u8]) -> TempFile {
+            let path = env::temp_dir().join(format!("revolver-{}-{}", process::id(), name));
+            File::create(&path).unwrap().write_all(contents).unwrap();
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn open<T: MmapElem>(path: &Path) -> Result<MmapCsrMatrix<T>> {
+        // Test files are never modified while they are mapped.
+        unsafe { MmapCsrMatrix::open(path) }
+    }
+
+    fn written<T: BinaryElem>(m: &CsrMatrix<T>) -> Vec<u8> {
+        let mut out = vec![];
+        m.write_to(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_open() {
+        let csr = example();
+        let file = TempFile::new("open", &written(&csr));
+        let m = open::<f64>(&file.0).unwrap();
+        m.verify().unwrap();
+        assert_eq!((m.nrows, m.ncols, m.nnz()), (3, 4, 4));
+        assert_eq!(m.indptr(), &[0, 2, 2, 4]);
+        assert_eq!(m.row(1), (&[][..], &[][..]));
+        assert_eq!(m.row(2), (&[1, 3][..], &[5.0, 6.0][..]));
+        for (i, j) in cartesian_product(0..3, 0..4) {
+            assert_eq!(m[(i, j)], csr[(i, j)]);
+        }
+        assert_eq!(m.get(3, 0), None);
+        assert_eq!(m.matvec(&[1.0, 2.0, 3.0, 4.0]), vec![7.0, 0.0, 34.0]);
+        assert_eq!(m.mxv(&[1.0, 2.0, 3.0, 4.0], MaxPlus), csr.mxv(&[1.0, 2.0, 3.0, 4.0], MaxPlus));
+        assert!(m.try_matvec(&[1.0]).is_err());
+
+        let copy = m.to_csr().unwrap();
+        assert_eq!((copy.indptr(), copy.indices(), copy.data()),
+                   (csr.indptr(), csr.indices(), csr.data()));
+
+        // Several mappings of one file can be open at once.
+        let other = open::<f64>(&file.0).unwrap();
+        assert_eq!(other.data(), m.data());
+    }
+
+    #[test]
+    fn test_open_types() {
+        let mut complex = DOKMatrix::zeros(2, 3);
+        complex.set(1, 2, Complex::new(1.5, -2.0));
+        let file = TempFile::new("complex", &written(&CsrMatrix::from_dok(&complex)));
+        let m = open::<Complex<f64>>(&file.0).unwrap();
+        assert_eq!(m[(1, 2)], Complex::new(1.5, -2.0));
+
+        let mut bytes = written(&CsrMatrix::from_dok(&DOKMatrix::<bool>::identity(3)));
+        let file = TempFile::new("bool", &bytes);
+        assert!(open::<bool>(&file.0).unwrap()[(2, 2)]);
+        let last = bytes.len() - 5;
+        bytes[last] = 2;
+        let file = TempFile::new("bool-invalid", &bytes);
+        assert_eq!(open::<bool>(&file.0).err().unwrap(),
+                   Error::Parse {
+                       line: None,
+                       message: "file holds invalid values of type Bool".into(),
+                   });
+    }
+
+    #[test]
+    fn test_open_errors() {
+        let invalid = |message: &str| {
+            Error::Parse {
+                line: None,
+                message: message.into(),
+            }
+        };
+        let bytes = written(&example());
+        let file = TempFile::new("errors", &bytes);
+        assert_eq!(open::<f32>(&file.0).err().unwrap(),
+                   invalid("expected elements of type F32, found F64"));
+
+        let mut dok = vec![];
+        example().to_dok().write_to(&mut dok).unwrap();
+        let file = TempFile::new("errors-dok", &dok);
+        assert_eq!(open::<f64>(&file.0).err().unwrap(),
+                   invalid("expected Csr layout, found Triplets"));
+
+        let file = TempFile::new("errors-truncated", &bytes[..bytes.len() - 1]);
+        assert_eq!(open::<f64>(&file.0).err().unwrap(),
+                   invalid(&format!("expected a file of {} bytes, found {}",
+                                    bytes.len(),
+                                    bytes.len() - 1)));
+
+        // A file with a valid checksum but an indptr past the end of the
+        // data, or a column out of bounds.
+        for &(offset, value) in &[(48, 9u64), (80, 4)] {
+            let mut bad = bytes.clone();
+            bad[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+            let len = bad.len();
+            let crc = crc32(&bad[..len - 4]);
+            bad[len - 4..].copy_from_slice(&crc.to_le_bytes());
+            let file = TempFile::new("errors-structure", &bad);
+            match open::<f64>(&file.0).err().unwrap() {
+                Error::InvalidStructure(_) => {}
+                e => panic!("unexpected error {:?}", e),
+            }
+        }
+
+        // Corrupt values are only caught by `verify`.
+        let mut corrupt = bytes.clone();
+        corrupt[bytes.len() - 5] ^= 0x10;
+        let file = TempFile::new("errors-corrupt", &corrupt);
+        let m = open::<f64>(&file.0).unwrap();
+        assert_eq!(m.verify().err().unwrap(),
+                   invalid("checksum mismatch: the file is corrupt"));
+
+        match open::<f64>(Path::new("/nonexistent/revolver-matrix")).err().unwrap() {
+            Error::Io { kind, .. } => assert_eq!(kind, ::std::io::ErrorKind::NotFound),
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+}
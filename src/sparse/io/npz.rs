@@ -0,0 +1,583 @@
+//! SciPy's `.npz` sparse matrix files.
+//!
+//! `scipy.sparse.save_npz` writes a ZIP archive of `.npy` arrays. CSR and
+//! CSC matrices are stored as the arrays `indices`, `indptr`, `format`,
+//! `shape` and `data`, and COO matrices as `row`, `col`, `format`, `shape`
+//! and `data`. `format` holds the string `csr`, `csc` or `coo`.
+//!
+//! Files are read whether they were saved compressed or not, and are
+//! written uncompressed.
+
+mod inflate;
+mod npy;
+mod zip;
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+use std::ops::Add;
+
+use complex::Complex;
+use error::{Error, Result};
+use sparse::coo::CooMatrix;
+use sparse::csc::CscMatrix;
+use sparse::csr::CsrMatrix;
+use sparse::dok::{DOKMatrix, MatrixElem};
+
+use self::npy::{read_array, write_array, Array};
+use self::zip::{read_archive, ZipWriter};
+
+/// Element types that can be read from and written to `.npy` arrays.
+pub trait NpyElem: MatrixElem {
+    /// NumPy type descriptor of arrays written from this type, such as
+    /// `<f8`.
+    const DESCR: &'static str;
+
+    /// Convert an element of an array, returning `None` if it cannot be
+    /// represented exactly by this type.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - NumPy kind of the array: `b`, `i`, `u`, `f` or `c`.
+    /// * `bytes` - The element, in little-endian byte order.
+    fn from_npy(kind: char, bytes: &[u8]) -> Option<Self>;
+
+    /// Append the little-endian bytes of this value to `out`.
+    fn to_npy(&self, out: &mut Vec<u8>);
+}
+
+/// Read an element of a boolean or integer array.
+fn npy_integer(kind: char, bytes: &[u8]) -> Option<i128> {
+    if bytes.len() > 8 {
+        return None;
+    }
+    let negative = kind == 'i' && bytes.last().is_some_and(|&b| b & 0x80 != 0);
+    let mut wide = if negative { [0xff; 16] } else { [0; 16] };
+    wide[..bytes.len()].copy_from_slice(bytes);
+    match kind {
+        'b' | 'i' | 'u' => Some(i128::from_le_bytes(wide)),
+        _ => None,
+    }
+}
+
+/// Read an element of a floating point array.
+fn npy_float(kind: char, bytes: &[u8]) -> Option<f64> {
+    match (kind, bytes.len()) {
+        ('f', 4) => Some(f64::from(f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))),
+        ('f', 8) => {
+            let mut b = [0; 8];
+            b.copy_from_slice(bytes);
+            Some(f64::from_le_bytes(b))
+        }
+        _ => None,
+    }
+}
+
+macro_rules! impl_npy_int {
+    ($($t:ident $descr:expr),*) => {
+        $(
+            impl NpyElem for $t {
+                const DESCR: &'static str = $descr;
+
+                fn from_npy(kind: char, bytes: &[u8]) -> Option<$t> {
+                    npy_integer(kind, bytes).and_then(|v| $t::try_from(v).ok())
+                }
+
+                fn to_npy(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+            }
+        )*
+    }
+}
+
+impl_npy_int!(i8 "|i1", i16 "<i2", i32 "<i4", i64 "<i8", u8 "|u1", u16 "<u2", u32 "<u4",
+              u64 "<u8");
+
+impl NpyElem for f64 {
+    const DESCR: &'static str = "<f8";
+
+    fn from_npy(kind: char, bytes: &[u8]) -> Option<f64> {
+        npy_float(kind, bytes).or_else(|| npy_integer(kind, bytes).map(|v| v as f64))
+    }
+
+    fn to_npy(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl NpyElem for f32 {
+    const DESCR: &'static str = "<f4";
+
+    fn from_npy(kind: char, bytes: &[u8]) -> Option<f32> {
+        match (kind, bytes.len()) {
+            ('f', 4) => npy_float(kind, bytes).map(|v| v as f32),
+            ('f', _) => None,
+            _ => npy_integer(kind, bytes).map(|v| v as f32),
+        }
+    }
+
+    fn to_npy(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+macro_rules! impl_npy_complex {
+    ($($t:ident $descr:expr),*) => {
+        $(
+            impl NpyElem for Complex<$t> {
+                const DESCR: &'static str = $descr;
+
+                fn from_npy(kind: char, bytes: &[u8]) -> Option<Complex<$t>> {
+                    if kind != 'c' {
+                        return $t::from_npy(kind, bytes).map(|re| Complex::new(re, 0.0));
+                    }
+                    let (re, im) = bytes.split_at(bytes.len() / 2);
+                    Some(Complex::new($t::from_npy('f', re)?, $t::from_npy('f', im)?))
+                }
+
+                fn to_npy(&self, out: &mut Vec<u8>) {
+                    self.re.to_npy(out);
+                    self.im.to_npy(out);
+                }
+            }
+        )*
+    }
+}
+
+impl_npy_complex!(f32 "<c8", f64 "<c16");
+
+impl NpyElem for bool {
+    const DESCR: &'static str = "|b1";
+
+    fn from_npy(kind: char, bytes: &[u8]) -> Option<bool> {
+        match kind {
+            'b' => Some(bytes[0] != 0),
+            _ => None,
+        }
+    }
+
+    fn to_npy(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+}
+
+/// A sparse matrix read from a `.npz` file, in the format it was saved in.
+#[derive(Clone)]
+pub enum NpzMatrix<T>
+    where T: MatrixElem
+{
+    Csr(CsrMatrix<T>),
+    Csc(CscMatrix<T>),
+    Coo(CooMatrix<T>),
+}
+
+impl<T> NpzMatrix<T>
+    where T: MatrixElem + Add<Output = T>
+{
+    /// Convert this matrix into a DOKMatrix holding the same elements.
+    pub fn to_dok(&self) -> DOKMatrix<T> {
+        match *self {
+            NpzMatrix::Csr(ref m) => m.to_dok(),
+            NpzMatrix::Csc(ref m) => m.to_dok(),
+            NpzMatrix::Coo(ref m) => m.to_dok(),
+        }
+    }
+}
+
+/// Build an error for a malformed file.
+fn invalid(message: String) -> Error {
+    Error::Parse {
+        line: None,
+        message,
+    }
+}
+
+/// Convert every element of an array.
+fn elements<T: NpyElem>(name: &str, array: &Array) -> Result<Vec<T>> {
+    array.elements()
+        .map(|bytes| T::from_npy(array.dtype.kind, bytes))
+        .collect::<Option<Vec<T>>>()
+        .ok_or_else(|| {
+            invalid(format!("cannot convert array {:?} of dtype {:?} to the matrix element type",
+                            name,
+                            array.dtype.descr))
+        })
+}
+
+/// Read a `.npz` file written by `scipy.sparse.save_npz`.
+///
+/// CSR and CSC matrices whose indices are unsorted or hold duplicates are
+/// brought into canonical form by summing duplicates and dropping zeros. Use
+/// `to_dok` to get the result as a DOKMatrix.
+///
+/// # Arguments
+///
+/// * `reader` - Source of the file contents.
+pub fn read_npz<T, R>(mut reader: R) -> Result<NpzMatrix<T>>
+    where T: NpyElem + Add<Output = T>,
+          R: Read
+{
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes)?;
+    let mut arrays = HashMap::new();
+    for (name, contents) in read_archive(&bytes).map_err(invalid)? {
+        let array = read_array(&contents).map_err(|e| invalid(format!("{}: {}", name, e)))?;
+        arrays.insert(name.trim_end_matches(".npy").to_string(), array);
+    }
+    let array = |name: &str| {
+        arrays.get(name).ok_or_else(|| invalid(format!("missing array {:?}", name)))
+    };
+
+    let format = array("format")?.text().map_err(|e| invalid(format!("format: {}", e)))?;
+    let shape: Vec<u64> = elements("shape", array("shape")?)?;
+    if shape.len() != 2 {
+        return Err(invalid(format!("expected a shape of length 2, found {:?}", shape)));
+    }
+    let (nrows, ncols) = (shape[0], shape[1]);
+    let data: Vec<T> = elements("data", array("data")?)?;
+
+    match format.as_str() {
+        "csr" | "csc" => {
+            let indices: Vec<u64> = elements("indices", array("indices")?)?;
+            let indptr = elements::<u64>("indptr", array("indptr")?)?
+                .into_iter()
+                .map(|p| {
+                    usize::try_from(p)
+                        .map_err(|_| invalid(format!("indptr entry {} does not fit in memory", p)))
+                })
+                .collect::<Result<Vec<usize>>>()?;
+            let nmajor = if format == "csr" { nrows } else { ncols };
+            let (indptr, indices, data) = canonicalize(nmajor, indptr, indices, data);
+            if format == "csr" {
+                CsrMatrix::try_new(nrows, ncols, indptr, indices, data).map(NpzMatrix::Csr)
+            } else {
+                CscMatrix::try_new(nrows, ncols, indptr, indices, data).map(NpzMatrix::Csc)
+            }
+        }
+        "coo" => {
+            let rows: Vec<u64> = elements("row", array("row")?)?;
+            let cols: Vec<u64> = elements("col", array("col")?)?;
+            if rows.len() != data.len() || cols.len() != data.len() {
+                return Err(invalid("row, col and data must have the same length".into()));
+            }
+            let mut m = CooMatrix::with_capacity(nrows, ncols, data.len());
+            for ((i, j), v) in rows.into_iter().zip(cols).zip(data) {
+                m.try_push(i, j, v)?;
+            }
+            Ok(NpzMatrix::Coo(m))
+        }
+        other => Err(invalid(format!("unsupported sparse format {:?}", other))),
+    }
+}
+
+/// Sort the indices of each major slice of a compressed matrix and sum
+/// duplicates, dropping elements that sum to zero.
+///
+/// The arrays are returned unchanged if they are already in canonical form,
+/// or if they are malformed, leaving the error to the matrix constructor.
+///
+/// # Arguments
+///
+/// * `nmajor` - Length of the major axis.
+/// * `indptr` - Offsets of the start of each major slice.
+/// * `indices` - Minor index of each stored element.
+/// * `data` - Value of each stored element.
+fn canonicalize<T>(nmajor: u64,
+                   indptr: Vec<usize>,
+                   indices: Vec<u64>,
+                   data: Vec<T>)
+                   -> (Vec<usize>, Vec<u64>, Vec<T>)
+    where T: MatrixElem + Add<Output = T>
+{
+    let well_formed = indptr.len() as u128 == u128::from(nmajor) + 1 && indptr[0] == 0 &&
+                      indptr.windows(2).all(|w| w[0] <= w[1]) &&
+                      indptr[nmajor as usize] == data.len() &&
+                      indices.len() == data.len();
+    let sorted = || {
+        indptr.windows(2).all(|w| indices[w[0]..w[1]].windows(2).all(|p| p[0] < p[1]))
+    };
+    if !well_formed || sorted() {
+        return (indptr, indices, data);
+    }
+
+    let mut new_indptr = vec![0];
+    let mut new_indices = Vec::with_capacity(indices.len());
+    let mut new_data = Vec::with_capacity(data.len());
+    for w in indptr.windows(2) {
+        let mut slice: Vec<(u64, T)> = (w[0]..w[1]).map(|k| (indices[k], data[k].clone())).collect();
+        slice.sort_by_key(|&(minor, _)| minor);
+        let mut merged: Vec<(u64, T)> = Vec::with_capacity(slice.len());
+        for (minor, v) in slice {
+            match merged.last_mut() {
+                Some(last) if last.0 == minor => last.1 = last.1.clone() + v,
+                _ => merged.push((minor, v)),
+            }
+        }
+        for (minor, v) in merged.into_iter().filter(|(_, v)| !v.is_zero()) {
+            new_indices.push(minor);
+            new_data.push(v);
+        }
+        new_indptr.push(new_indices.len());
+    }
+    (new_indptr, new_indices, new_data)
+}
+
+/// Write the arrays of a `.npz` file.
+fn write_arrays<W>(writer: W, arrays: &[(&str, &str, Vec<u64>, Vec<u8>)]) -> Result<()>
+    where W: Write
+{
+    let mut zip = ZipWriter::new(writer);
+    for &(name, descr, ref shape, ref data) in arrays {
+        zip.add(&format!("{}.npy", name), &write_array(descr, shape, data))?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+/// The descriptor and bytes of an index array, as 32-bit integers if every
+/// value fits and 64-bit integers otherwise, as SciPy does.
+fn index_array<I>(values: I, max: u64) -> (&'static str, Vec<u8>)
+    where I: Iterator<Item = u64>
+{
+    if max <= i32::MAX as u64 {
+        ("<i4", values.flat_map(|v| (v as i32).to_le_bytes().to_vec()).collect())
+    } else {
+        ("<i8", values.flat_map(|v| (v as i64).to_le_bytes().to_vec()).collect())
+    }
+}
+
+/// The bytes of an array of values.
+fn value_array<T: NpyElem>(values: &[T]) -> Vec<u8> {
+    let mut out = vec![];
+    for v in values {
+        v.to_npy(&mut out);
+    }
+    out
+}
+
+/// Write a compressed matrix in the layout shared by CSR and CSC.
+fn write_compressed<T, W>(writer: W,
+                          format: &str,
+                          shape: (u64, u64),
+                          indptr: &[usize],
+                          indices: &[u64],
+                          data: &[T])
+                          -> Result<()>
+    where T: NpyElem,
+          W: Write
+{
+    let max = shape.0.max(shape.1).max(data.len() as u64);
+    let (index_descr, index_bytes) = index_array(indices.iter().cloned(), max);
+    let (ptr_descr, ptr_bytes) = index_array(indptr.iter().map(|&p| p as u64), max);
+    write_arrays(writer,
+                 &[("indices", index_descr, vec![indices.len() as u64], index_bytes),
+                   ("indptr", ptr_descr, vec![indptr.len() as u64], ptr_bytes),
+                   ("format", "|S3", vec![], format.as_bytes().to_vec()),
+                   ("shape", "<i8", vec![2], value_array(&[shape.0 as i64, shape.1 as i64])),
+                   ("data", T::DESCR, vec![data.len() as u64], value_array(data))])
+}
+
+/// Write a CSR matrix in the format of `scipy.sparse.save_npz`.
+///
+/// # Arguments
+///
+/// * `writer` - Destination of the file contents.
+/// * `m` - Matrix to write.
+pub fn write_npz_csr<T, W>(writer: W, m: &CsrMatrix<T>) -> Result<()>
+    where T: NpyElem,
+          W: Write
+{
+    write_compressed(writer, "csr", (m.nrows, m.ncols), m.indptr(), m.indices(), m.data())
+}
+
+/// Write a CSC matrix in the format of `scipy.sparse.save_npz`.
+///
+/// # Arguments
+///
+/// * `writer` - Destination of the file contents.
+/// * `m` - Matrix to write.
+pub fn write_npz_csc<T, W>(writer: W, m: &CscMatrix<T>) -> Result<()>
+    where T: NpyElem,
+          W: Write
+{
+    write_compressed(writer, "csc", (m.nrows, m.ncols), m.indptr(), m.indices(), m.data())
+}
+
+/// Write a COO matrix in the format of `scipy.sparse.save_npz`.
+///
+/// Duplicate triplets are written as-is.
+///
+/// # Arguments
+///
+/// * `writer` - Destination of the file contents.
+/// * `m` - Matrix to write.
+pub fn write_npz_coo<T, W>(writer: W, m: &CooMatrix<T>) -> Result<()>
+    where T: NpyElem,
+          W: Write
+{
+    let max = m.nrows.max(m.ncols);
+    let (row_descr, row_bytes) = index_array(m.rows().iter().cloned(), max);
+    let (col_descr, col_bytes) = index_array(m.cols().iter().cloned(), max);
+    let len = vec![m.nnz() as u64];
+    write_arrays(writer,
+                 &[("row", row_descr, len.clone(), row_bytes),
+                   ("col", col_descr, len.clone(), col_bytes),
+                   ("format", "|S3", vec![], b"coo".to_vec()),
+                   ("shape", "<i8", vec![2], value_array(&[m.nrows as i64, m.ncols as i64])),
+                   ("data", T::DESCR, len, value_array(m.values()))])
+}
+
+#[cfg(test)]
+mod tests {
+    use complex::Complex;
+    use error::Error;
+    use sparse::coo::CooMatrix;
+    use sparse::csc::CscMatrix;
+    use sparse::csr::CsrMatrix;
+    use sparse::dok::DOKMatrix;
+    use super::{read_npz, value_array, write_arrays, write_npz_coo, write_npz_csc, write_npz_csr,
+                NpzMatrix};
+    use super::npy::read_array;
+    use super::zip::read_archive;
+
+    fn example() -> DOKMatrix<f64> {
+        let mut m = DOKMatrix::zeros(3, 4);
+        m.set(0, 1, 1.5);
+        m.set(2, 0, -2.0);
+        m.set(2, 3, 1e-300);
+        m
+    }
+
+    #[test]
+    fn test_csr_layout() {
+        let mut out = vec![];
+        write_npz_csr(&mut out, &CsrMatrix::from_dok(&example())).unwrap();
+        let entries = read_archive(&out).unwrap();
+        let names: Vec<&str> = entries.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names,
+                   vec!["indices.npy", "indptr.npy", "format.npy", "shape.npy", "data.npy"]);
+
+        let indptr = read_array(&entries[1].1).unwrap();
+        assert_eq!(indptr.dtype.descr, "<i4");
+        assert_eq!(indptr.shape, vec![4]);
+        assert_eq!(indptr.data, value_array(&[0i32, 1, 1, 3]));
+        assert_eq!(read_array(&entries[2].1).unwrap().text().unwrap(), "csr");
+        assert_eq!(read_array(&entries[3].1).unwrap().data, value_array(&[3i64, 4]));
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let dok = example();
+
+        let mut out = vec![];
+        write_npz_csr(&mut out, &CsrMatrix::from_dok(&dok)).unwrap();
+        match read_npz::<f64, _>(&out[..]).unwrap() {
+            NpzMatrix::Csr(m) => assert_eq!(m.to_dok(), dok),
+            _ => panic!("expected a CSR matrix"),
+        }
+
+        let mut out = vec![];
+        write_npz_csc(&mut out, &CscMatrix::from_dok(&dok)).unwrap();
+        match read_npz::<f64, _>(&out[..]).unwrap() {
+            NpzMatrix::Csc(m) => assert_eq!(m.to_dok(), dok),
+            _ => panic!("expected a CSC matrix"),
+        }
+
+        let mut coo = CooMatrix::new(2, 2);
+        coo.push(1, 0, Complex::new(1.0, -1.0));
+        coo.push(1, 0, Complex::new(0.5, 0.0));
+        let mut out = vec![];
+        write_npz_coo(&mut out, &coo).unwrap();
+        let read = read_npz::<Complex<f64>, _>(&out[..]).unwrap();
+        match read {
+            NpzMatrix::Coo(ref m) => assert_eq!(m.nnz(), 2),
+            _ => panic!("expected a COO matrix"),
+        }
+        assert_eq!(read.to_dok()[(1, 0)], Complex::new(1.5, -1.0));
+    }
+
+    #[test]
+    fn test_conversions() {
+        // Integer data can be read into floats, but not the other way around.
+        let mut ints = DOKMatrix::<i16>::zeros(2, 2);
+        ints.set(0, 0, -3);
+        let mut out = vec![];
+        write_npz_csr(&mut out, &CsrMatrix::from_dok(&ints)).unwrap();
+        assert_eq!(read_npz::<f32, _>(&out[..]).unwrap().to_dok()[(0, 0)], -3.0);
+        assert_eq!(read_npz::<i64, _>(&out[..]).unwrap().to_dok()[(0, 0)], -3);
+        assert!(read_npz::<u8, _>(&out[..]).is_err());
+
+        let mut out = vec![];
+        write_npz_csr(&mut out, &CsrMatrix::from_dok(&example())).unwrap();
+        assert_eq!(read_npz::<i32, _>(&out[..]).err().unwrap(),
+                   Error::Parse {
+                       line: None,
+                       message: "cannot convert array \"data\" of dtype \"<f8\" to the matrix \
+                                 element type"
+                           .into(),
+                   });
+    }
+
+    #[test]
+    fn test_non_canonical() {
+        // Row 0 holds its columns out of order and column 2 twice; row 1
+        // holds two elements that cancel.
+        let mut out = vec![];
+        write_arrays(&mut out,
+                     &[("indices", "<i8", vec![5], value_array(&[2i64, 0, 2, 1, 1])),
+                       ("indptr", "<i8", vec![3], value_array(&[0i64, 3, 5])),
+                       ("format", "|S3", vec![], b"csr".to_vec()),
+                       ("shape", "<i8", vec![2], value_array(&[2i64, 3])),
+                       ("data", "<f8", vec![5], value_array(&[1.0, 2.0, 3.0, 4.0, -4.0]))])
+            .unwrap();
+        match read_npz::<f64, _>(&out[..]).unwrap() {
+            NpzMatrix::Csr(m) => {
+                assert_eq!(m.indptr(), &[0, 2, 2]);
+                assert_eq!(m.indices(), &[0, 2]);
+                assert_eq!(m.data(), &[2.0, 4.0]);
+            }
+            _ => panic!("expected a CSR matrix"),
+        }
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let mut out = vec![];
+        write_arrays(&mut out,
+                     &[("format", "|S3", vec![], b"dia".to_vec()),
+                       ("shape", "<i8", vec![2], value_array(&[2i64, 3])),
+                       ("data", "<f8", vec![0], vec![])])
+            .unwrap();
+        assert_eq!(read_npz::<f64, _>(&out[..]).err().unwrap(),
+                   Error::Parse {
+                       line: None,
+                       message: "unsupported sparse format \"dia\"".into(),
+                   });
+
+        let mut out = vec![];
+        write_arrays(&mut out,
+                     &[("indices", "<i8", vec![1], value_array(&[3i64])),
+                       ("indptr", "<i8", vec![3], value_array(&[0i64, 1, 1])),
+                       ("format", "|S3", vec![], b"csr".to_vec()),
+                       ("shape", "<i8", vec![2], value_array(&[2i64, 3])),
+                       ("data", "<f8", vec![1], value_array(&[1.0]))])
+            .unwrap();
+        assert!(read_npz::<f64, _>(&out[..]).is_err());
+        assert!(read_npz::<f64, _>(&b"not a zip file"[..]).is_err());
+
+        // An interior indptr entry past the end of the data.
+        let mut out = vec![];
+        write_arrays(&mut out,
+                     &[("indices", "<i8", vec![1], value_array(&[0i64])),
+                       ("indptr", "<i8", vec![3], value_array(&[0i64, 5, 1])),
+                       ("format", "|S3", vec![], b"csr".to_vec()),
+                       ("shape", "<i8", vec![2], value_array(&[2i64, 3])),
+                       ("data", "<f8", vec![1], value_array(&[1.0]))])
+            .unwrap();
+        match read_npz::<f64, _>(&out[..]).err().unwrap() {
+            Error::InvalidStructure(_) => {}
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+}
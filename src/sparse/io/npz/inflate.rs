@@ -0,0 +1,280 @@
+//! Decompression of raw DEFLATE streams, as described in RFC 1951.
+//!
+//! This follows the structure of zlib's `puff` reference decoder: it favors
+//! simplicity over speed, decoding Huffman codes one bit at a time.
+
+use std::result;
+
+type Result<T> = result::Result<T, String>;
+
+/// Largest number of bits in a Huffman code.
+const MAX_BITS: usize = 15;
+
+/// Base lengths and extra bits of length symbols 257 to 285.
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43,
+                                51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4,
+                                4, 4, 5, 5, 5, 5, 0];
+
+/// Base distances and extra bits of distance symbols 0 to 29.
+const DIST_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385,
+                              513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385,
+                              24577];
+const DIST_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9,
+                              10, 10, 11, 11, 12, 12, 13, 13];
+
+/// Order in which the code length code lengths of a dynamic block are
+/// stored.
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2,
+                                        14, 1, 15];
+
+/// Reads a byte slice as a stream of bits, least significant bit first.
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buf: u32,
+    nbits: u32,
+}
+
+impl<'a> Bits<'a> {
+    /// Read an `n`-bit value, for `n` of at most 16.
+    fn bits(&mut self, n: u32) -> Result<u32> {
+        while self.nbits < n {
+            let byte = *self.data.get(self.pos).ok_or("unexpected end of compressed data")?;
+            self.buf |= u32::from(byte) << self.nbits;
+            self.nbits += 8;
+            self.pos += 1;
+        }
+        let value = self.buf & ((1 << n) - 1);
+        self.buf >>= n;
+        self.nbits -= n;
+        Ok(value)
+    }
+
+    /// Discard the bits left in the current byte.
+    fn align(&mut self) {
+        self.buf = 0;
+        self.nbits = 0;
+    }
+}
+
+/// A canonical Huffman code, stored as the number of codes of each length
+/// and the symbols ordered by code.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    /// Build the code in which symbol `i` has a code of `lengths[i]` bits,
+    /// or no code if that is zero.
+    fn new(lengths: &[u8]) -> Result<Huffman> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = (left << 1) - i32::from(count);
+            if left < 0 {
+                return Err("over-subscribed Huffman code".into());
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for len in 1..=MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; offsets[MAX_BITS + 1] as usize];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        Ok(Huffman { counts, symbols })
+    }
+
+    /// Decode one symbol.
+    fn decode(&self, bits: &mut Bits) -> Result<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= bits.bits(1)? as i32;
+            let count = i32::from(count);
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid Huffman code".into())
+    }
+}
+
+/// Decompress a raw DEFLATE stream, with no zlib or gzip wrapper.
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>> {
+    let mut bits = Bits {
+        data,
+        pos: 0,
+        buf: 0,
+        nbits: 0,
+    };
+    let mut out = vec![];
+    loop {
+        let last = bits.bits(1)? == 1;
+        match bits.bits(2)? {
+            0 => stored(&mut bits, &mut out)?,
+            1 => {
+                let (lengths, distances) = fixed_codes()?;
+                codes(&mut bits, &mut out, &lengths, &distances)?
+            }
+            2 => {
+                let (lengths, distances) = dynamic_codes(&mut bits)?;
+                codes(&mut bits, &mut out, &lengths, &distances)?
+            }
+            _ => return Err("invalid block type".into()),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+/// Copy an uncompressed block.
+fn stored(bits: &mut Bits, out: &mut Vec<u8>) -> Result<()> {
+    bits.align();
+    let header = bits.data.get(bits.pos..bits.pos + 4).ok_or("unexpected end of compressed data")?;
+    let len = u16::from_le_bytes([header[0], header[1]]);
+    let nlen = u16::from_le_bytes([header[2], header[3]]);
+    if len != !nlen {
+        return Err("stored block length does not match its complement".into());
+    }
+    let start = bits.pos + 4;
+    let block = bits.data
+        .get(start..start + len as usize)
+        .ok_or("unexpected end of compressed data")?;
+    out.extend_from_slice(block);
+    bits.pos = start + len as usize;
+    Ok(())
+}
+
+/// The codes used by blocks compressed with fixed Huffman codes.
+fn fixed_codes() -> Result<(Huffman, Huffman)> {
+    let mut lengths = [0u8; 288];
+    for (symbol, len) in lengths.iter_mut().enumerate() {
+        *len = match symbol {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        };
+    }
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+/// Read the codes at the start of a block compressed with dynamic Huffman
+/// codes.
+fn dynamic_codes(bits: &mut Bits) -> Result<(Huffman, Huffman)> {
+    let nlen = bits.bits(5)? as usize + 257;
+    let ndist = bits.bits(5)? as usize + 1;
+    let ncode = bits.bits(4)? as usize + 4;
+    if nlen > 286 || ndist > 30 {
+        return Err("too many length or distance codes".into());
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for &symbol in &CODE_LENGTH_ORDER[..ncode] {
+        code_lengths[symbol] = bits.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths)?;
+
+    let mut lengths = Vec::with_capacity(nlen + ndist);
+    while lengths.len() < nlen + ndist {
+        let symbol = code_lengths.decode(bits)?;
+        let (len, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths.last().ok_or("repeated length with no first length")?;
+                (previous, 3 + bits.bits(2)?)
+            }
+            17 => (0, 3 + bits.bits(3)?),
+            _ => (0, 11 + bits.bits(7)?),
+        };
+        if lengths.len() + repeat as usize > nlen + ndist {
+            return Err("too many code lengths".into());
+        }
+        lengths.extend((0..repeat).map(|_| len));
+    }
+    if lengths[256] == 0 {
+        return Err("missing end-of-block code".into());
+    }
+    Ok((Huffman::new(&lengths[..nlen])?, Huffman::new(&lengths[nlen..])?))
+}
+
+/// Decode the literals and back-references of a compressed block.
+fn codes(bits: &mut Bits, out: &mut Vec<u8>, lengths: &Huffman, distances: &Huffman) -> Result<()> {
+    loop {
+        let symbol = lengths.decode(bits)? as usize;
+        if symbol < 256 {
+            out.push(symbol as u8);
+            continue;
+        }
+        if symbol == 256 {
+            return Ok(());
+        }
+        let symbol = symbol - 257;
+        if symbol >= LENGTH_BASE.len() {
+            return Err("invalid length symbol".into());
+        }
+        let len = LENGTH_BASE[symbol] as usize + bits.bits(u32::from(LENGTH_EXTRA[symbol]))? as usize;
+        let symbol = distances.decode(bits)? as usize;
+        if symbol >= DIST_BASE.len() {
+            return Err("invalid distance symbol".into());
+        }
+        let dist = DIST_BASE[symbol] as usize + bits.bits(u32::from(DIST_EXTRA[symbol]))? as usize;
+        if dist > out.len() {
+            return Err("distance too far back".into());
+        }
+        // Copy byte by byte, since the source may overlap the bytes written.
+        let start = out.len() - dist;
+        for k in 0..len {
+            let byte = out[start + k];
+            out.push(byte);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::inflate;
+
+    #[test]
+    fn test_stored() {
+        assert_eq!(inflate(&[1, 7, 0, 248, 255, 115, 116, 111, 114, 101, 100, 33]).unwrap(),
+                   b"stored!");
+        assert!(inflate(&[1, 7, 0, 248, 254, 115]).is_err());
+    }
+
+    #[test]
+    fn test_fixed() {
+        assert_eq!(inflate(&[203, 72, 205, 201, 201, 87, 200, 64, 39, 1]).unwrap(),
+                   &b"hello hello hello hello"[..]);
+    }
+
+    #[test]
+    fn test_dynamic() {
+        let compressed = [29, 204, 59, 10, 128, 48, 20, 68, 209, 62, 107, 185, 72, 94, 254, 89,
+                          142, 136, 93, 32, 32, 168, 219, 119, 34, 83, 156, 234, 206, 53, 95, 142,
+                          57, 120, 246, 113, 159, 206, 163, 109, 217, 25, 17, 147, 129, 66, 144,
+                          145, 64, 148, 137, 76, 146, 25, 35, 203, 66, 162, 200, 170, 174, 202,
+                          166, 174, 201, 174, 174, 175, 31, 175, 208, 254, 71, 5, 216, 250, 252,
+                          0];
+        let expected: String = (0..12)
+            .map(|i| format!("{},{},{}.5\n", i, i * 3 % 7, i))
+            .collect();
+        assert_eq!(inflate(&compressed).unwrap(),
+                   format!("row,col,value\n{}", expected).into_bytes());
+        assert!(inflate(&compressed[..40]).is_err());
+    }
+}
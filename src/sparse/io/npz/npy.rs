@@ -0,0 +1,322 @@
+//! The NumPy `.npy` array format.
+//!
+//! A `.npy` file holds the magic string `\x93NUMPY`, a format version, and
+//! a header giving the type, order and shape of the array as a Python dict
+//! literal such as `{'descr': '<f8', 'fortran_order': False, 'shape': (3,), }`,
+//! followed by the raw elements.
+
+use std::result;
+
+type Result<T> = result::Result<T, String>;
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+
+/// Alignment of the start of the data written by NumPy.
+const ALIGN: usize = 64;
+
+/// Element type of an array, parsed from a descriptor such as `<f8`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Dtype {
+    pub descr: String,
+    /// NumPy's kind character, such as `f` for floating point.
+    pub kind: char,
+    /// Size of each element in bytes.
+    pub size: usize,
+    pub big_endian: bool,
+}
+
+impl Dtype {
+    fn parse(descr: &str) -> Result<Dtype> {
+        let unsupported = || format!("unsupported dtype {:?}", descr);
+        let mut chars = descr.chars();
+        let big_endian = match chars.next() {
+            Some('>') => true,
+            Some('<') | Some('|') | Some('=') => false,
+            _ => return Err(unsupported()),
+        };
+        let kind = chars.next().ok_or_else(unsupported)?;
+        let size: usize = chars.as_str().parse().map_err(|_| unsupported())?;
+        // Unicode strings are stored as UTF-32 code units.
+        let size = if kind == 'U' { size * 4 } else { size };
+        if !"biufcSU".contains(kind) || size == 0 {
+            return Err(unsupported());
+        }
+        Ok(Dtype {
+            descr: descr.to_string(),
+            kind,
+            size,
+            big_endian,
+        })
+    }
+}
+
+/// A one-dimensional or scalar array read from a `.npy` file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Array {
+    pub dtype: Dtype,
+    pub shape: Vec<u64>,
+    /// Elements in little-endian byte order.
+    pub data: Vec<u8>,
+}
+
+impl Array {
+    /// Number of elements in the array.
+    pub fn len(&self) -> usize {
+        self.data.len() / self.dtype.size
+    }
+
+    /// Iterate over the little-endian bytes of each element.
+    pub fn elements(&self) -> ::std::slice::Chunks<'_, u8> {
+        self.data.chunks(self.dtype.size)
+    }
+
+    /// Read a scalar or one-element array of bytes or Unicode as a string.
+    pub fn text(&self) -> Result<String> {
+        if self.len() != 1 {
+            return Err("expected a single string".into());
+        }
+        let text = match self.dtype.kind {
+            'S' => self.data.iter().map(|&b| b as char).collect::<String>(),
+            'U' => {
+                self.data
+                    .chunks(4)
+                    .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+                    .map(|c| ::std::char::from_u32(c).ok_or_else(|| "invalid Unicode string".to_string()))
+                    .collect::<Result<String>>()?
+            }
+            _ => return Err(format!("expected a string, found dtype {:?}", self.dtype.descr)),
+        };
+        Ok(text.trim_end_matches('\0').to_string())
+    }
+}
+
+/// A Python literal in a `.npy` header.
+#[derive(Clone, Debug, PartialEq)]
+enum Literal {
+    Str(String),
+    Bool(bool),
+    Int(u64),
+    Tuple(Vec<Literal>),
+    Dict(Vec<(Literal, Literal)>),
+}
+
+/// Parser for the subset of Python literals used in `.npy` headers.
+struct LiteralParser<'a> {
+    text: &'a str,
+}
+
+impl<'a> LiteralParser<'a> {
+    fn skip_whitespace(&mut self) {
+        self.text = self.text.trim_start();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.text.starts_with(token) {
+            self.text = &self.text[token.len()..];
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Parse the items of a sequence up to and including `close`, allowing a
+    /// trailing comma.
+    fn items<F, T>(&mut self, close: &str, mut item: F) -> Result<Vec<T>>
+        where F: FnMut(&mut Self) -> Result<T>
+    {
+        let mut items = vec![];
+        loop {
+            if self.eat(close) {
+                return Ok(items);
+            }
+            items.push(item(self)?);
+            if !self.eat(",") {
+                return if self.eat(close) {
+                    Ok(items)
+                } else {
+                    Err(format!("expected ',' or {:?} in header", close))
+                };
+            }
+        }
+    }
+
+    fn literal(&mut self) -> Result<Literal> {
+        self.skip_whitespace();
+        if self.eat("{") {
+            return self.items("}", |p| {
+                    let key = p.literal()?;
+                    if !p.eat(":") {
+                        return Err("expected ':' in header".into());
+                    }
+                    Ok((key, p.literal()?))
+                })
+                .map(Literal::Dict);
+        }
+        if self.eat("(") {
+            return self.items(")", |p| p.literal()).map(Literal::Tuple);
+        }
+        if self.eat("True") {
+            return Ok(Literal::Bool(true));
+        }
+        if self.eat("False") {
+            return Ok(Literal::Bool(false));
+        }
+        if let Some(quote) = self.text.chars().next().filter(|&c| c == '\'' || c == '"') {
+            let end = self.text[1..]
+                .find(quote)
+                .ok_or("unterminated string in header")?;
+            let s = self.text[1..end + 1].to_string();
+            self.text = &self.text[end + 2..];
+            return Ok(Literal::Str(s));
+        }
+        let digits = self.text.find(|c: char| !c.is_ascii_digit()).unwrap_or(self.text.len());
+        // Python 2 writes shapes with a long suffix, as in `(3L,)`.
+        let value = self.text[..digits]
+            .parse()
+            .map_err(|_| format!("unexpected {:?} in header", self.text))?;
+        self.text = &self.text[digits..];
+        self.eat("L");
+        Ok(Literal::Int(value))
+    }
+}
+
+/// Parse the contents of a `.npy` file.
+pub fn read_array(bytes: &[u8]) -> Result<Array> {
+    if !bytes.starts_with(MAGIC) || bytes.len() < 10 {
+        return Err("not a .npy file".into());
+    }
+    let (header_start, header_len) = match bytes[6] {
+        1 => (10, u16::from_le_bytes([bytes[8], bytes[9]]) as usize),
+        2 | 3 if bytes.len() >= 12 => {
+            (12, u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize)
+        }
+        version => return Err(format!("unsupported .npy version {}", version)),
+    };
+    let header = bytes.get(header_start..header_start + header_len)
+        .and_then(|h| ::std::str::from_utf8(h).ok())
+        .ok_or("invalid .npy header")?;
+
+    let mut parser = LiteralParser { text: header };
+    let fields = match parser.literal()? {
+        Literal::Dict(fields) => fields,
+        _ => return Err("expected a dict in .npy header".into()),
+    };
+    let field = |name: &str| {
+        fields.iter()
+            .find(|(key, _)| *key == Literal::Str(name.into()))
+            .map(|(_, value)| value)
+            .ok_or_else(|| format!("missing {:?} in .npy header", name))
+    };
+    let dtype = match *field("descr")? {
+        Literal::Str(ref descr) => Dtype::parse(descr)?,
+        _ => return Err("structured dtypes are not supported".into()),
+    };
+    let shape = match *field("shape")? {
+        Literal::Tuple(ref dims) => {
+            dims.iter()
+                .map(|d| match *d {
+                    Literal::Int(d) => Ok(d),
+                    _ => Err("invalid shape in .npy header".to_string()),
+                })
+                .collect::<Result<Vec<u64>>>()?
+        }
+        _ => return Err("invalid shape in .npy header".into()),
+    };
+    if shape.len() > 1 {
+        return Err(format!("expected a one-dimensional array, found shape {:?}", shape));
+    }
+
+    let len = shape.iter().product::<u64>();
+    let data_len = len.checked_mul(dtype.size as u64)
+        .ok_or_else(|| format!("array of shape {:?} is too large", shape))?;
+    let data_start = header_start + header_len;
+    let mut data = bytes.get(data_start..)
+        .filter(|d| d.len() as u64 == data_len)
+        .ok_or_else(|| format!("expected {} elements of dtype {:?}", len, dtype.descr))?
+        .to_vec();
+    if dtype.big_endian {
+        let unit = if dtype.kind == 'c' { dtype.size / 2 } else { dtype.size };
+        for element in data.chunks_mut(unit) {
+            element.reverse();
+        }
+    }
+    Ok(Array { dtype, shape, data })
+}
+
+/// Build the contents of a `.npy` file.
+///
+/// # Arguments
+///
+/// * `descr` - NumPy type descriptor of the elements.
+/// * `shape` - Shape of the array, with no dimensions for a scalar.
+/// * `data` - Raw elements, in the byte order given by `descr`.
+pub fn write_array(descr: &str, shape: &[u64], data: &[u8]) -> Vec<u8> {
+    let shape = match shape.len() {
+        1 => format!("({},)", shape[0]),
+        _ => format!("({})", shape.iter().map(u64::to_string).collect::<Vec<_>>().join(", ")),
+    };
+    let mut header = format!("{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+                             descr,
+                             shape);
+    // Pad with spaces and a newline so that the data starts aligned.
+    let unpadded = MAGIC.len() + 4 + header.len() + 1;
+    header.extend((0..(ALIGN - unpadded % ALIGN) % ALIGN).map(|_| ' '));
+    header.push('\n');
+
+    let mut out = MAGIC.to_vec();
+    out.extend_from_slice(&[1, 0]);
+    out.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    out.extend_from_slice(header.as_bytes());
+    out.extend_from_slice(data);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_array, write_array};
+
+    #[test]
+    fn test_roundtrip() {
+        let data: Vec<u8> = [1.5f64, -2.0].iter().flat_map(|v| v.to_le_bytes().to_vec()).collect();
+        let bytes = write_array("<f8", &[2], &data);
+        assert_eq!(&bytes[..10], b"\x93NUMPY\x01\x00\x76\x00");
+        assert_eq!(&bytes[10..67],
+                   &b"{'descr': '<f8', 'fortran_order': False, 'shape': (2,), }"[..]);
+        assert_eq!(bytes.len() - data.len(), 128);
+        assert_eq!(bytes[127], b'\n');
+
+        let array = read_array(&bytes).unwrap();
+        assert_eq!((array.dtype.kind, array.dtype.size), ('f', 8));
+        assert_eq!(array.shape, vec![2]);
+        assert_eq!(array.data, data);
+    }
+
+    #[test]
+    fn test_read() {
+        // Big-endian data, a Python 2 header and a version 2 file.
+        let mut bytes = b"\x93NUMPY\x02\x00".to_vec();
+        let header = b"{\"descr\": \">i4\", \"shape\": (2L,), \"fortran_order\": True}\n";
+        bytes.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(header);
+        bytes.extend_from_slice(&[0, 0, 1, 2, 255, 255, 255, 254]);
+        let array = read_array(&bytes).unwrap();
+        assert_eq!(array.dtype.descr, ">i4");
+        assert_eq!(array.elements().collect::<Vec<_>>(),
+                   vec![&[2, 1, 0, 0][..], &[254, 255, 255, 255][..]]);
+
+        let scalar = read_array(&write_array("|S3", &[], b"csr")).unwrap();
+        assert_eq!(scalar.shape, Vec::<u64>::new());
+        assert_eq!(scalar.text().unwrap(), "csr");
+        let unicode = read_array(&write_array("<U4", &[], b"c\0\0\0o\0\0\0o\0\0\0\0\0\0\0"))
+            .unwrap();
+        assert_eq!(unicode.text().unwrap(), "coo");
+
+        assert!(read_array(b"\x93NUMPY\x01\x00\x05\x00{'a':").is_err());
+        assert!(read_array(&write_array("<f8", &[3], &[0; 16])).is_err());
+        assert!(read_array(&write_array("<f8", &[2, 2], &[0; 32])).is_err());
+        assert!(read_array(&write_array("<V8", &[1], &[0; 8])).is_err());
+        assert_eq!(read_array(&write_array("<f8", &[1 << 62], &[])).unwrap_err(),
+                   "array of shape [4611686018427387904] is too large");
+    }
+}
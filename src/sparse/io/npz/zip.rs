@@ -0,0 +1,307 @@
+//! Just enough of the ZIP archive format to hold NumPy `.npz` files.
+//!
+//! Archives are read from memory, with stored or deflated entries and ZIP64
+//! extensions. Archives are written with stored entries only.
+
+use std::convert::TryFrom;
+use std::io::{self, Write};
+use std::result;
+
+use sparse::io::crc::crc32;
+
+use super::inflate::inflate;
+
+type Result<T> = result::Result<T, String>;
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIR: u32 = 0x0605_4b50;
+const ZIP64_END_OF_CENTRAL_DIR: u32 = 0x0606_4b50;
+const ZIP64_LOCATOR: u32 = 0x0706_4b50;
+const ZIP64_EXTRA: u16 = 0x0001;
+
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+/// The DOS date of 1980-01-01, the earliest a ZIP entry can have.
+const DOS_EPOCH_DATE: u16 = (1 << 5) | 1;
+
+/// Little-endian reads from a byte slice, with bounds checks.
+struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn at(data: &'a [u8], pos: usize) -> Cursor<'a> {
+        Cursor { data, pos }
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8]> {
+        let bytes = self.pos
+            .checked_add(n)
+            .and_then(|end| self.data.get(self.pos..end))
+            .ok_or("truncated ZIP archive")?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        let (low, high) = (self.u32()?, self.u32()?);
+        Ok(u64::from(low) | (u64::from(high) << 32))
+    }
+
+    fn skip(&mut self, n: usize) -> Result<()> {
+        self.bytes(n).map(|_| ())
+    }
+}
+
+/// Find the offset of the end of central directory record, which is
+/// followed by a comment of at most 65535 bytes.
+fn find_end_of_central_dir(data: &[u8]) -> Result<usize> {
+    let signature = END_OF_CENTRAL_DIR.to_le_bytes();
+    let earliest = data.len().saturating_sub(22 + 0xffff);
+    (earliest..data.len().saturating_sub(21))
+        .rev()
+        .find(|&pos| data[pos..pos + 4] == signature)
+        .ok_or_else(|| "not a ZIP archive".to_string())
+}
+
+/// Read the name and decompressed contents of every entry of a ZIP archive.
+pub fn read_archive(data: &[u8]) -> Result<Vec<(String, Vec<u8>)>> {
+    let end = find_end_of_central_dir(data)?;
+    let mut cursor = Cursor::at(data, end + 10);
+    let mut count = u64::from(cursor.u16()?);
+    cursor.skip(4)?;
+    let mut offset = u64::from(cursor.u32()?);
+
+    if count == 0xffff || offset == 0xffff_ffff {
+        let mut locator = Cursor::at(data, end.checked_sub(20).ok_or("truncated ZIP archive")?);
+        if locator.u32()? != ZIP64_LOCATOR {
+            return Err("missing ZIP64 end of central directory locator".into());
+        }
+        locator.skip(4)?;
+        let mut record = Cursor::at(data, locator.u64()? as usize);
+        if record.u32()? != ZIP64_END_OF_CENTRAL_DIR {
+            return Err("invalid ZIP64 end of central directory record".into());
+        }
+        record.skip(28)?;
+        count = record.u64()?;
+        record.skip(8)?;
+        offset = record.u64()?;
+    }
+
+    let mut cursor = Cursor::at(data, offset as usize);
+    let mut entries = vec![];
+    for _ in 0..count {
+        if cursor.u32()? != CENTRAL_HEADER {
+            return Err("invalid central directory entry".into());
+        }
+        cursor.skip(4)?;
+        let flags = cursor.u16()?;
+        let method = cursor.u16()?;
+        cursor.skip(4)?;
+        let crc = cursor.u32()?;
+        let mut compressed_size = u64::from(cursor.u32()?);
+        let mut size = u64::from(cursor.u32()?);
+        let name_len = cursor.u16()? as usize;
+        let extra_len = cursor.u16()? as usize;
+        let comment_len = cursor.u16()? as usize;
+        cursor.skip(8)?;
+        let mut local_offset = u64::from(cursor.u32()?);
+        let name = String::from_utf8_lossy(cursor.bytes(name_len)?).into_owned();
+
+        // The ZIP64 extra field holds, in order, whichever of the sizes and
+        // offset did not fit in 32 bits.
+        let mut extra = Cursor::at(cursor.bytes(extra_len)?, 0);
+        while extra.pos + 4 <= extra.data.len() {
+            let (id, len) = (extra.u16()?, extra.u16()? as usize);
+            if id != ZIP64_EXTRA {
+                extra.skip(len)?;
+                continue;
+            }
+            for field in &mut [&mut size, &mut compressed_size, &mut local_offset] {
+                if **field == 0xffff_ffff {
+                    **field = extra.u64()?;
+                }
+            }
+            break;
+        }
+        cursor.skip(comment_len)?;
+
+        if flags & 1 != 0 {
+            return Err(format!("entry {:?} is encrypted", name));
+        }
+        let mut local = Cursor::at(data, local_offset as usize);
+        if local.u32()? != LOCAL_HEADER {
+            return Err(format!("invalid local header for entry {:?}", name));
+        }
+        local.skip(22)?;
+        let skip = local.u16()? as usize + local.u16()? as usize;
+        local.skip(skip)?;
+        let raw = local.bytes(compressed_size as usize)?;
+
+        let contents = match method {
+            STORED => raw.to_vec(),
+            DEFLATED => inflate(raw).map_err(|e| format!("entry {:?}: {}", name, e))?,
+            _ => {
+                return Err(format!("entry {:?} uses unsupported compression method {}",
+                                   name,
+                                   method))
+            }
+        };
+        if contents.len() as u64 != size || crc32(&contents) != crc {
+            return Err(format!("entry {:?} is corrupt", name));
+        }
+        entries.push((name, contents));
+    }
+    Ok(entries)
+}
+
+/// Writes a ZIP archive of stored entries.
+pub struct ZipWriter<W: Write> {
+    writer: W,
+    offset: u64,
+    // Central directory records of the entries written so far.
+    central_dir: Vec<u8>,
+    count: u16,
+}
+
+impl<W: Write> ZipWriter<W> {
+    pub fn new(writer: W) -> ZipWriter<W> {
+        ZipWriter {
+            writer,
+            offset: 0,
+            central_dir: vec![],
+            count: 0,
+        }
+    }
+
+    /// Append an entry to the archive.
+    pub fn add(&mut self, name: &str, contents: &[u8]) -> io::Result<()> {
+        let too_large = || io::Error::other("ZIP entries and archives must be smaller than 4 GiB");
+        let size = u32::try_from(contents.len()).map_err(|_| too_large())?;
+        let offset = u32::try_from(self.offset).map_err(|_| too_large())?;
+        if self.count == u16::MAX {
+            return Err(io::Error::other("ZIP archives can hold at most 65535 entries"));
+        }
+        let crc = crc32(contents);
+
+        // Fields shared by the local header and the central directory record:
+        // version needed, flags, method, time, date, crc and sizes.
+        let mut common = vec![];
+        common.extend_from_slice(&20u16.to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes());
+        common.extend_from_slice(&STORED.to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes());
+        common.extend_from_slice(&DOS_EPOCH_DATE.to_le_bytes());
+        common.extend_from_slice(&crc.to_le_bytes());
+        common.extend_from_slice(&size.to_le_bytes());
+        common.extend_from_slice(&size.to_le_bytes());
+        common.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes());
+
+        let mut local = LOCAL_HEADER.to_le_bytes().to_vec();
+        local.extend_from_slice(&common);
+        local.extend_from_slice(name.as_bytes());
+        self.writer.write_all(&local)?;
+        self.writer.write_all(contents)?;
+
+        self.central_dir.extend_from_slice(&CENTRAL_HEADER.to_le_bytes());
+        self.central_dir.extend_from_slice(&20u16.to_le_bytes());
+        self.central_dir.extend_from_slice(&common);
+        // Comment length, disk number, internal and external attributes.
+        self.central_dir.extend_from_slice(&[0; 10]);
+        self.central_dir.extend_from_slice(&offset.to_le_bytes());
+        self.central_dir.extend_from_slice(name.as_bytes());
+
+        self.offset += (local.len() + contents.len()) as u64;
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Write the central directory, completing the archive.
+    pub fn finish(mut self) -> io::Result<W> {
+        let offset = u32::try_from(self.offset)
+            .map_err(|_| io::Error::other("ZIP archives must be smaller than 4 GiB"))?;
+        self.writer.write_all(&self.central_dir)?;
+        let mut end = END_OF_CENTRAL_DIR.to_le_bytes().to_vec();
+        end.extend_from_slice(&[0; 4]);
+        end.extend_from_slice(&self.count.to_le_bytes());
+        end.extend_from_slice(&self.count.to_le_bytes());
+        end.extend_from_slice(&(self.central_dir.len() as u32).to_le_bytes());
+        end.extend_from_slice(&offset.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes());
+        self.writer.write_all(&end)?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sparse::io::crc::crc32;
+
+    use super::{read_archive, ZipWriter};
+
+    #[test]
+    fn test_roundtrip() {
+        let mut writer = ZipWriter::new(vec![]);
+        writer.add("a.npy", b"first").unwrap();
+        writer.add("empty", b"").unwrap();
+        writer.add("b.npy", &[7; 1000]).unwrap();
+        let archive = writer.finish().unwrap();
+
+        let entries = read_archive(&archive).unwrap();
+        assert_eq!(entries,
+                   vec![("a.npy".to_string(), b"first".to_vec()),
+                        ("empty".to_string(), vec![]),
+                        ("b.npy".to_string(), vec![7; 1000])]);
+    }
+
+    #[test]
+    fn test_deflated() {
+        // A single deflated entry "x" holding "hello hello hello hello".
+        let mut archive = vec![0x50, 0x4b, 3, 4, 20, 0, 0, 0, 8, 0, 0, 0, 0x21, 0];
+        let crc = crc32(b"hello hello hello hello");
+        let compressed = [203, 72, 205, 201, 201, 87, 200, 64, 39, 1];
+        let common: Vec<u8> = crc.to_le_bytes()
+            .iter()
+            .chain(&10u32.to_le_bytes())
+            .chain(&23u32.to_le_bytes())
+            .chain(&[1, 0, 0, 0])
+            .cloned()
+            .collect();
+        archive.extend_from_slice(&common);
+        archive.push(b'x');
+        archive.extend_from_slice(&compressed);
+        let central = archive.len() as u32;
+        archive.extend_from_slice(&[0x50, 0x4b, 1, 2, 20, 0, 20, 0, 0, 0, 8, 0, 0, 0, 0x21, 0]);
+        archive.extend_from_slice(&common);
+        archive.extend_from_slice(&[0; 14]);
+        archive.push(b'x');
+        let central_len = archive.len() as u32 - central;
+        archive.extend_from_slice(&[0x50, 0x4b, 5, 6, 0, 0, 0, 0, 1, 0, 1, 0]);
+        archive.extend_from_slice(&central_len.to_le_bytes());
+        archive.extend_from_slice(&central.to_le_bytes());
+        archive.extend_from_slice(&[0, 0]);
+
+        assert_eq!(read_archive(&archive).unwrap(),
+                   vec![("x".to_string(), b"hello hello hello hello".to_vec())]);
+
+        // Corrupting the data is caught by the checksum.
+        let mut corrupt = archive.clone();
+        corrupt[31] ^= 1;
+        assert!(read_archive(&corrupt).is_err());
+        assert!(read_archive(b"not a zip file at all").is_err());
+    }
+}
@@ -0,0 +1,700 @@
+use std::collections::HashMap;
+use std::ops::{Add, Mul};
+
+use error::{Error, Result};
+use sparse::dok::{DOKMatrix, MatrixElem};
+use sparse::semiring::{self, Semiring};
+
+type Coords = (u64, u64);
+
+/// Read-only view of the coordinates stored in a matrix, independent of its
+/// element type.
+trait Pattern {
+    fn shape(&self) -> (u64, u64);
+    fn contains(&self, coords: Coords) -> bool;
+    fn coords(&self) -> Vec<Coords>;
+}
+
+impl<M> Pattern for DOKMatrix<M>
+    where M: MatrixElem
+{
+    fn shape(&self) -> (u64, u64) {
+        (self.nrows, self.ncols)
+    }
+
+    fn contains(&self, coords: Coords) -> bool {
+        self.elems.contains_key(&coords)
+    }
+
+    fn coords(&self) -> Vec<Coords> {
+        self.elems.keys().cloned().collect()
+    }
+}
+
+/// Restricts the output elements a masked operation may write.
+///
+/// A structural mask allows exactly the coordinates stored in its matrix; a
+/// complemented mask allows every coordinate that is not stored. The values
+/// of the mask matrix are ignored, so a matrix of any element type can be
+/// used as a mask.
+#[derive(Clone, Copy)]
+pub struct Mask<'a> {
+    pattern: &'a dyn Pattern,
+    complement: bool,
+}
+
+impl<'a> Mask<'a> {
+    /// Create a mask allowing the coordinates stored in `matrix`.
+    pub fn structural<M>(matrix: &'a DOKMatrix<M>) -> Self
+        where M: MatrixElem
+    {
+        Mask {
+            pattern: matrix,
+            complement: false,
+        }
+    }
+
+    /// Create a mask allowing the coordinates not stored in `matrix`.
+    pub fn complement<M>(matrix: &'a DOKMatrix<M>) -> Self
+        where M: MatrixElem
+    {
+        Mask {
+            pattern: matrix,
+            complement: true,
+        }
+    }
+
+    /// Whether the element at `coords` may be written.
+    pub fn allows(&self, coords: Coords) -> bool {
+        self.pattern.contains(coords) != self.complement
+    }
+
+    /// Shape of the matrix this mask was created from.
+    pub fn shape(&self) -> (u64, u64) {
+        self.pattern.shape()
+    }
+
+    /// Whether this mask is a complemented mask.
+    pub fn is_complement(&self) -> bool {
+        self.complement
+    }
+}
+
+/// Whether an optional mask allows the element at `coords`.
+fn allowed(mask: &Option<Mask>, coords: Coords) -> bool {
+    mask.as_ref().is_none_or(|m| m.allows(coords))
+}
+
+/// How a masked operation combines its result with the existing contents of
+/// the output.
+///
+/// Either argument may be absent: `old` when the output has no stored element
+/// at a coordinate, and `new` when the operation produced nothing there.
+/// Returning `None` leaves the output element absent.
+pub trait Accumulator<T> {
+    fn accumulate(&self, old: Option<T>, new: Option<T>) -> Option<T>;
+}
+
+/// Overwrite the output with the result, clearing output elements the
+/// operation did not produce.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Replace;
+
+impl<T> Accumulator<T> for Replace {
+    fn accumulate(&self, _old: Option<T>, new: Option<T>) -> Option<T> {
+        new
+    }
+}
+
+/// Combine `old` and `new` with `op` where both are present, and keep
+/// whichever is present otherwise.
+fn union<T, F>(old: Option<T>, new: Option<T>, op: F) -> Option<T>
+    where F: FnOnce(T, T) -> T
+{
+    match (old, new) {
+        (Some(old), Some(new)) => Some(op(old, new)),
+        (old, None) => old,
+        (None, new) => new,
+    }
+}
+
+/// Add the result into the output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Plus;
+
+impl<T> Accumulator<T> for Plus
+    where T: Add<Output = T>
+{
+    fn accumulate(&self, old: Option<T>, new: Option<T>) -> Option<T> {
+        union(old, new, |a, b| a + b)
+    }
+}
+
+/// Multiply the result into the output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Times;
+
+impl<T> Accumulator<T> for Times
+    where T: Mul<Output = T>
+{
+    fn accumulate(&self, old: Option<T>, new: Option<T>) -> Option<T> {
+        union(old, new, |a, b| a * b)
+    }
+}
+
+/// Keep the smaller of the output and the result.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Min;
+
+impl<T> Accumulator<T> for Min
+    where T: PartialOrd
+{
+    fn accumulate(&self, old: Option<T>, new: Option<T>) -> Option<T> {
+        union(old, new, semiring::min)
+    }
+}
+
+/// Keep the larger of the output and the result.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Max;
+
+impl<T> Accumulator<T> for Max
+    where T: PartialOrd
+{
+    fn accumulate(&self, old: Option<T>, new: Option<T>) -> Option<T> {
+        union(old, new, semiring::max)
+    }
+}
+
+/// Any binary function can be used as an accumulator, in which case it is
+/// applied where both the output and the result are present.
+impl<T, F> Accumulator<T> for F
+    where F: Fn(T, T) -> T
+{
+    fn accumulate(&self, old: Option<T>, new: Option<T>) -> Option<T> {
+        union(old, new, self)
+    }
+}
+
+/// Return an error unless `left` and `right` are equal shapes.
+fn check_shape(op: &'static str, left: (u64, u64), right: (u64, u64)) -> Result<()> {
+    if left != right {
+        return Err(Error::ShapeMismatch { op, left, right });
+    }
+    Ok(())
+}
+
+/// Group the stored elements of `m` by row or by column, sorting each group
+/// by its other coordinate.
+fn group_by<T>(m: &DOKMatrix<T>, by_row: bool) -> HashMap<u64, Vec<(u64, &T)>>
+    where T: MatrixElem
+{
+    let mut groups = HashMap::<u64, Vec<(u64, &T)>>::new();
+    for (&(i, j), v) in &m.elems {
+        let (outer, inner) = if by_row { (i, j) } else { (j, i) };
+        groups.entry(outer).or_default().push((inner, v));
+    }
+    for group in groups.values_mut() {
+        group.sort_unstable_by_key(|&(k, _)| k);
+    }
+    groups
+}
+
+impl<T> DOKMatrix<T>
+    where T: MatrixElem
+{
+    /// Compute `self<mask> = accum(self, a * b)` over `semiring`.
+    ///
+    /// Only output elements allowed by `mask` are written; the rest of
+    /// `self` is left unchanged. Panics if the shapes of the operands, the
+    /// mask and `self` are incompatible.
+    ///
+    /// # Arguments
+    ///
+    /// * `mask` - Output elements that may be written, or `None` to allow
+    ///   every element.
+    /// * `accum` - How to combine the product with the current contents of
+    ///   `self`.
+    /// * `a` - Left-hand operand, of shape `(self.nrows, k)`.
+    /// * `b` - Right-hand operand, of shape `(k, self.ncols)`.
+    /// * `semiring` - Operations used to combine elements of the operands.
+    pub fn mxm_masked<A, S>(&mut self,
+                            mask: Option<Mask>,
+                            accum: A,
+                            a: &DOKMatrix<T>,
+                            b: &DOKMatrix<T>,
+                            semiring: S)
+        where A: Accumulator<T>,
+              S: Semiring<T>
+    {
+        self.try_mxm_masked(mask, accum, a, b, semiring).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Compute `self<mask> = accum(self, a * b)` over `semiring`, returning
+    /// an error if the shapes of the operands, the mask and `self` are
+    /// incompatible.
+    ///
+    /// With a structural mask, each allowed output element is computed as a
+    /// sparse dot product of a row of `a` and a column of `b`, so the cost
+    /// depends on the mask rather than on the size of the full product.
+    /// Otherwise partial products are formed row by row, and those landing
+    /// on disallowed elements are skipped before they are accumulated.
+    pub fn try_mxm_masked<A, S>(&mut self,
+                                mask: Option<Mask>,
+                                accum: A,
+                                a: &DOKMatrix<T>,
+                                b: &DOKMatrix<T>,
+                                semiring: S)
+                                -> Result<()>
+        where A: Accumulator<T>,
+              S: Semiring<T>
+    {
+        if a.ncols != b.nrows {
+            return Err(Error::ShapeMismatch {
+                op: "multiply",
+                left: (a.nrows, a.ncols),
+                right: (b.nrows, b.ncols),
+            });
+        }
+        check_shape("assign", (self.nrows, self.ncols), (a.nrows, b.ncols))?;
+        if let Some(ref m) = mask {
+            check_shape("mask", (self.nrows, self.ncols), m.shape())?;
+        }
+
+        let mut result = HashMap::<Coords, T>::new();
+        match mask {
+            Some(ref m) if !m.is_complement() => {
+                let a_rows = group_by(a, true);
+                let b_cols = group_by(b, false);
+                for (i, j) in m.pattern.coords() {
+                    let (row, col) = match (a_rows.get(&i), b_cols.get(&j)) {
+                        (Some(row), Some(col)) => (row, col),
+                        _ => continue,
+                    };
+                    if let Some(v) = sparse_dot(row, col, &semiring) {
+                        result.insert((i, j), v);
+                    }
+                }
+            }
+            _ => {
+                let b_rows = group_by(b, true);
+                for (&(i, k), left) in &a.elems {
+                    let row = match b_rows.get(&k) {
+                        Some(row) => row,
+                        None => continue,
+                    };
+                    for &(j, right) in row {
+                        if !allowed(&mask, (i, j)) {
+                            continue;
+                        }
+                        let product = semiring.mul(left.clone(), right.clone());
+                        let acc = result.entry((i, j)).or_insert_with(|| semiring.zero());
+                        *acc = semiring.add(acc.clone(), product);
+                    }
+                }
+            }
+        }
+        result.retain(|_, v| !semiring.is_zero(v));
+        self.assign_masked(mask, accum, result, |v| semiring.is_zero(v));
+        Ok(())
+    }
+
+    /// Compute `self<mask> = accum(self, a .+ b)`, where `.+` combines
+    /// elements stored in both `a` and `b` with `op` and passes through
+    /// elements stored in only one of them.
+    ///
+    /// Panics if `a`, `b`, the mask and `self` do not all have the same
+    /// shape.
+    pub fn ewise_add_masked<A, F>(&mut self,
+                                  mask: Option<Mask>,
+                                  accum: A,
+                                  a: &DOKMatrix<T>,
+                                  b: &DOKMatrix<T>,
+                                  op: F)
+        where A: Accumulator<T>,
+              F: Fn(T, T) -> T
+    {
+        self.try_ewise_add_masked(mask, accum, a, b, op).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Compute `self<mask> = accum(self, a .+ b)`, returning an error if
+    /// `a`, `b`, the mask and `self` do not all have the same shape.
+    pub fn try_ewise_add_masked<A, F>(&mut self,
+                                      mask: Option<Mask>,
+                                      accum: A,
+                                      a: &DOKMatrix<T>,
+                                      b: &DOKMatrix<T>,
+                                      op: F)
+                                      -> Result<()>
+        where A: Accumulator<T>,
+              F: Fn(T, T) -> T
+    {
+        self.check_ewise_shapes(&mask, a, b)?;
+        let mut result = HashMap::<Coords, T>::new();
+        for (&coords, v) in &a.elems {
+            if allowed(&mask, coords) {
+                result.insert(coords, v.clone());
+            }
+        }
+        for (&coords, v) in &b.elems {
+            if allowed(&mask, coords) {
+                let combined = union(result.remove(&coords), Some(v.clone()), &op);
+                result.extend(combined.map(|v| (coords, v)));
+            }
+        }
+        self.assign_masked(mask, accum, result, T::is_zero);
+        Ok(())
+    }
+
+    /// Compute `self<mask> = accum(self, a .* b)`, where `.*` combines
+    /// elements stored in both `a` and `b` with `op` and drops elements
+    /// stored in only one of them.
+    ///
+    /// Panics if `a`, `b`, the mask and `self` do not all have the same
+    /// shape.
+    pub fn ewise_mult_masked<A, F>(&mut self,
+                                   mask: Option<Mask>,
+                                   accum: A,
+                                   a: &DOKMatrix<T>,
+                                   b: &DOKMatrix<T>,
+                                   op: F)
+        where A: Accumulator<T>,
+              F: Fn(T, T) -> T
+    {
+        self.try_ewise_mult_masked(mask, accum, a, b, op).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Compute `self<mask> = accum(self, a .* b)`, returning an error if
+    /// `a`, `b`, the mask and `self` do not all have the same shape.
+    pub fn try_ewise_mult_masked<A, F>(&mut self,
+                                       mask: Option<Mask>,
+                                       accum: A,
+                                       a: &DOKMatrix<T>,
+                                       b: &DOKMatrix<T>,
+                                       op: F)
+                                       -> Result<()>
+        where A: Accumulator<T>,
+              F: Fn(T, T) -> T
+    {
+        self.check_ewise_shapes(&mask, a, b)?;
+        // Probe the larger operand with the elements of the smaller one.
+        let (small, large, swapped) = if a.nnz() <= b.nnz() {
+            (a, b, false)
+        } else {
+            (b, a, true)
+        };
+        let mut result = HashMap::<Coords, T>::new();
+        for (&coords, v) in &small.elems {
+            if !allowed(&mask, coords) {
+                continue;
+            }
+            if let Some(w) = large.elems.get(&coords) {
+                let (left, right) = if swapped { (w, v) } else { (v, w) };
+                result.insert(coords, op(left.clone(), right.clone()));
+            }
+        }
+        self.assign_masked(mask, accum, result, T::is_zero);
+        Ok(())
+    }
+
+    /// Compute `w<mask> = accum(w, self * x)` over `semiring`.
+    ///
+    /// The mask is a column vector: output element `w[i]` is written only if
+    /// the mask allows coordinate `(i, 0)`. Elements of `w` that are cleared
+    /// by the accumulator are set to `semiring.zero()`. Panics if the shapes
+    /// of `w`, `x` and the mask are incompatible with `self`.
+    ///
+    /// # Arguments
+    ///
+    /// * `w` - Dense output vector of length `self.nrows`.
+    /// * `mask` - Mask of shape `(self.nrows, 1)`, or `None` to allow every
+    ///   element.
+    /// * `accum` - How to combine the product with the current contents of
+    ///   `w`.
+    /// * `x` - Dense vector of length `self.ncols`.
+    /// * `semiring` - Operations used to combine elements.
+    pub fn mxv_masked<A, S>(&self, w: &mut [T], mask: Option<Mask>, accum: A, x: &[T], semiring: S)
+        where A: Accumulator<T>,
+              S: Semiring<T>
+    {
+        self.try_mxv_masked(w, mask, accum, x, semiring).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Compute `w<mask> = accum(w, self * x)` over `semiring`, returning an
+    /// error if the shapes of `w`, `x` and the mask are incompatible with
+    /// `self`.
+    pub fn try_mxv_masked<A, S>(&self,
+                                w: &mut [T],
+                                mask: Option<Mask>,
+                                accum: A,
+                                x: &[T],
+                                semiring: S)
+                                -> Result<()>
+        where A: Accumulator<T>,
+              S: Semiring<T>
+    {
+        check_shape("matvec", (self.ncols, 1), (x.len() as u64, 1))?;
+        check_shape("assign", (self.nrows, 1), (w.len() as u64, 1))?;
+        if let Some(ref m) = mask {
+            check_shape("mask", (self.nrows, 1), m.shape())?;
+        }
+
+        let mut result: Vec<Option<T>> = vec![None; w.len()];
+        for (&(i, j), v) in &self.elems {
+            if !allowed(&mask, (i, 0)) {
+                continue;
+            }
+            let product = semiring.mul(v.clone(), x[j as usize].clone());
+            let slot = &mut result[i as usize];
+            *slot = Some(match slot.take() {
+                Some(acc) => semiring.add(acc, product),
+                None => product,
+            });
+        }
+        for (i, (out, new)) in w.iter_mut().zip(result).enumerate() {
+            if allowed(&mask, (i as u64, 0)) {
+                let old = out.clone();
+                *out = accum.accumulate(Some(old), new).unwrap_or_else(|| semiring.zero());
+            }
+        }
+        Ok(())
+    }
+
+    /// Return an error unless `a`, `b` and the mask have the same shape as
+    /// `self`.
+    fn check_ewise_shapes(&self,
+                          mask: &Option<Mask>,
+                          a: &DOKMatrix<T>,
+                          b: &DOKMatrix<T>)
+                          -> Result<()> {
+        check_shape("combine", (a.nrows, a.ncols), (b.nrows, b.ncols))?;
+        check_shape("assign", (self.nrows, self.ncols), (a.nrows, a.ncols))?;
+        if let Some(ref m) = *mask {
+            check_shape("mask", (self.nrows, self.ncols), m.shape())?;
+        }
+        Ok(())
+    }
+
+    /// Write `accum(self, result)` into every element of `self` allowed by
+    /// `mask`, where `result` holds no elements outside of the mask.
+    ///
+    /// Elements that accumulate to a value for which `is_zero` holds are
+    /// removed, so that products over a semiring drop the semiring's zero.
+    fn assign_masked<A, Z>(&mut self,
+                           mask: Option<Mask>,
+                           accum: A,
+                           mut result: HashMap<Coords, T>,
+                           is_zero: Z)
+        where A: Accumulator<T>,
+              Z: Fn(&T) -> bool
+    {
+        // Elements of `self` allowed by the mask but absent from `result`
+        // still see the accumulator, which may clear them.
+        let existing: Vec<Coords> = self.elems
+            .keys()
+            .filter(|&&coords| allowed(&mask, coords) && !result.contains_key(&coords))
+            .cloned()
+            .collect();
+        for coords in existing {
+            let old = self.elems.remove(&coords);
+            if let Some(v) = accum.accumulate(old, None).filter(|v| !is_zero(v)) {
+                self.elems.insert(coords, v);
+            }
+        }
+        for (coords, new) in result.drain() {
+            let old = self.elems.remove(&coords);
+            if let Some(v) = accum.accumulate(old, Some(new)).filter(|v| !is_zero(v)) {
+                self.elems.insert(coords, v);
+            }
+        }
+    }
+}
+
+/// Combine the elements of two sorted sparse vectors that share an index,
+/// returning `None` if there are none.
+fn sparse_dot<T, S>(left: &[(u64, &T)], right: &[(u64, &T)], semiring: &S) -> Option<T>
+    where T: Clone,
+          S: Semiring<T>
+{
+    let (mut p, mut q) = (0, 0);
+    let mut acc: Option<T> = None;
+    while p < left.len() && q < right.len() {
+        let (k, l) = left[p];
+        let (m, r) = right[q];
+        if k < m {
+            p += 1;
+        } else if m < k {
+            q += 1;
+        } else {
+            let product = semiring.mul(l.clone(), r.clone());
+            acc = Some(match acc {
+                Some(acc) => semiring.add(acc, product),
+                None => product,
+            });
+            p += 1;
+            q += 1;
+        }
+    }
+    acc
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use sparse::dok::DOKMatrix;
+    use sparse::semiring::{MinPlus, OrAnd, PlusTimes};
+    use super::{Mask, Max, Plus, Replace};
+
+    fn make_matrix(nrows: u64, ncols: u64, entries: &[((u64, u64), f64)]) -> DOKMatrix<f64> {
+        let elems: HashMap<_, _> = entries.iter().cloned().collect();
+        DOKMatrix::new(nrows, ncols, elems)
+    }
+
+    fn sorted_coords<T: ::sparse::dok::MatrixElem>(m: &DOKMatrix<T>) -> Vec<(u64, u64)> {
+        let mut coords: Vec<_> = m.iter().map(|(coords, _)| coords).collect();
+        coords.sort();
+        coords
+    }
+
+    #[test]
+    fn test_mxm_structural_mask() {
+        let a = make_matrix(2, 2, &[((0, 0), 1.0), ((0, 1), 2.0), ((1, 0), 3.0)]);
+        let full = &a * &a;
+        let mask = make_matrix(2, 2, &[((0, 1), 1.0), ((1, 1), 1.0)]);
+
+        let mut c = DOKMatrix::zeros(2, 2);
+        c.mxm_masked(Some(Mask::structural(&mask)), Replace, &a, &a, PlusTimes);
+        assert_eq!(sorted_coords(&c), vec![(0, 1), (1, 1)]);
+        assert_eq!(c[(0, 1)], full[(0, 1)]);
+        assert_eq!(c[(1, 1)], full[(1, 1)]);
+    }
+
+    #[test]
+    fn test_mxm_complement_mask_and_accum() {
+        let a = make_matrix(2, 2, &[((0, 0), 1.0), ((0, 1), 2.0), ((1, 0), 3.0)]);
+        let full = &a * &a;
+        let mask = make_matrix(2, 2, &[((0, 0), 1.0)]);
+
+        let mut c = make_matrix(2, 2, &[((0, 0), 10.0), ((1, 0), 10.0)]);
+        c.mxm_masked(Some(Mask::complement(&mask)), Plus, &a, &a, PlusTimes);
+        // (0, 0) is masked out, so it keeps its old value.
+        assert_eq!(c[(0, 0)], 10.0);
+        assert_eq!(c[(1, 0)], 10.0 + full[(1, 0)]);
+        assert_eq!(c[(0, 1)], full[(0, 1)]);
+        assert_eq!(c[(1, 1)], full[(1, 1)]);
+    }
+
+    #[test]
+    fn test_replace_clears_allowed_elements() {
+        let a = make_matrix(2, 2, &[((0, 0), 1.0)]);
+        let mut c = make_matrix(2, 2, &[((0, 1), 5.0), ((1, 1), 7.0)]);
+        let mask = make_matrix(2, 2, &[((0, 0), 1.0), ((0, 1), 1.0)]);
+        c.mxm_masked(Some(Mask::structural(&mask)), Replace, &a, &a, PlusTimes);
+        assert_eq!(sorted_coords(&c), vec![(0, 0), (1, 1)]);
+        assert_eq!(c[(0, 0)], 1.0);
+        assert_eq!(c[(1, 1)], 7.0);
+    }
+
+    #[test]
+    fn test_mxm_masked_semiring_zero() {
+        // Over MinPlus a path of length 0.0 is stored, and the semiring's
+        // zero, an infinite distance, is not.
+        let a = make_matrix(2, 2, &[((0, 1), 0.0), ((1, 1), 0.0)]);
+        let full = a.mxm(&a, MinPlus);
+        assert_eq!(sorted_coords(&full), vec![(0, 1), (1, 1)]);
+
+        let mut c = DOKMatrix::zeros(2, 2);
+        c.mxm_masked(None, Replace, &a, &a, MinPlus);
+        assert_eq!(sorted_coords(&c), sorted_coords(&full));
+        assert_eq!(c[(0, 1)], 0.0);
+
+        let mut c = make_matrix(2, 2, &[((1, 0), 3.0)]);
+        let mask = make_matrix(2, 2, &[((0, 1), 1.0), ((1, 1), 1.0)]);
+        c.mxm_masked(Some(Mask::structural(&mask)), Replace, &a, &a, MinPlus);
+        assert_eq!(sorted_coords(&c), vec![(0, 1), (1, 0), (1, 1)]);
+    }
+
+    #[test]
+    fn test_triangle_count() {
+        // Undirected graph on 4 nodes containing the triangles {0, 1, 2} and
+        // {0, 2, 3}. `l` holds the strictly lower triangle of its adjacency
+        // matrix; masking L * L^T with L counts each triangle once.
+        let edges = [(1, 0), (2, 0), (2, 1), (3, 0), (3, 2)];
+        let mut l = DOKMatrix::<u64>::zeros(4, 4);
+        for &(i, j) in &edges {
+            l.set(i, j, 1);
+        }
+        let mut c = DOKMatrix::zeros(4, 4);
+        c.mxm_masked(Some(Mask::structural(&l)), Replace, &l, &l.transposed(), PlusTimes);
+        let triangles: u64 = c.iter().map(|(_, v)| *v).sum();
+        assert_eq!(triangles, 2);
+    }
+
+    #[test]
+    fn test_mxv_masked_bfs() {
+        // Breadth-first search from node 0 along edges 0->1, 0->2, 1->3 and
+        // 2->3, skipping nodes that have already been visited.
+        let mut at = DOKMatrix::<bool>::zeros(4, 4);
+        for &(src, dst) in &[(0, 1), (0, 2), (1, 3), (2, 3)] {
+            at.set(dst, src, true);
+        }
+        let mut visited = DOKMatrix::<bool>::zeros(4, 1);
+        visited.set(0, 0, true);
+        let mut frontier = vec![true, false, false, false];
+        let mut levels = vec![];
+        while frontier.iter().any(|&v| v) {
+            let mut next = vec![false; 4];
+            at.mxv_masked(&mut next, Some(Mask::complement(&visited)), Replace, &frontier, OrAnd);
+            for (i, &v) in next.iter().enumerate() {
+                if v {
+                    visited.set(i as u64, 0, true);
+                }
+            }
+            levels.push(next.clone());
+            frontier = next;
+        }
+        assert_eq!(levels,
+                   vec![vec![false, true, true, false],
+                        vec![false, false, false, true],
+                        vec![false; 4]]);
+    }
+
+    #[test]
+    fn test_ewise_masked() {
+        let a = make_matrix(2, 2, &[((0, 0), 1.0), ((0, 1), 2.0)]);
+        let b = make_matrix(2, 2, &[((0, 1), 3.0), ((1, 1), 4.0)]);
+        let mask = make_matrix(2, 2, &[((0, 0), 1.0), ((0, 1), 1.0)]);
+
+        let mut sum = DOKMatrix::zeros(2, 2);
+        sum.ewise_add_masked(Some(Mask::structural(&mask)), Replace, &a, &b, |x, y| x + y);
+        assert_eq!(sorted_coords(&sum), vec![(0, 0), (0, 1)]);
+        assert_eq!(sum[(0, 1)], 5.0);
+
+        let mut product = make_matrix(2, 2, &[((0, 1), 9.0)]);
+        product.ewise_mult_masked(None, Max, &a, &b, |x, y| x * y);
+        assert_eq!(sorted_coords(&product), vec![(0, 1)]);
+        assert_eq!(product[(0, 1)], 9.0);
+
+        product.ewise_mult_masked(None, |x: f64, y: f64| x - y, &a, &b, |x, y| x * y);
+        assert_eq!(product[(0, 1)], 3.0);
+
+        // Results that are zero, from the operation or the accumulator, are
+        // not stored.
+        let mut zeros = make_matrix(2, 2, &[((0, 0), 5.0), ((0, 1), 6.0)]);
+        zeros.ewise_mult_masked(None, |x: f64, y: f64| x - y, &a, &b, |x, y| x * y);
+        assert_eq!(sorted_coords(&zeros), vec![(0, 0)]);
+        let mut cancelled = DOKMatrix::zeros(2, 2);
+        cancelled.ewise_add_masked(Some(Mask::structural(&mask)), Replace, &a, &a, |x, y| x - y);
+        assert_eq!(cancelled.nnz(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Shape mismatch: cannot mask")]
+    fn test_mask_shape_mismatch() {
+        let a = make_matrix(2, 2, &[]);
+        let mask = make_matrix(3, 2, &[]);
+        let mut c = DOKMatrix::zeros(2, 2);
+        c.mxm_masked(Some(Mask::structural(&mask)), Replace, &a, &a, PlusTimes);
+    }
+}
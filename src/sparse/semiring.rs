@@ -0,0 +1,267 @@
+use std::ops::{Add, Mul};
+
+use sparse::dok::{One, Zero};
+
+/// The pair of operations used to combine elements in a matrix product.
+///
+/// A product `C = A * B` over a semiring computes
+/// `C[(i, j)] = add(mul(A[(i, 0)], B[(0, j)]), mul(A[(i, 1)], B[(1, j)]), ...)`,
+/// where only stored elements of `A` and `B` take part. `zero` is the
+/// identity of `add` and is the value taken by absent elements of the result,
+/// and `one` is the identity of `mul`.
+///
+/// The usual arithmetic product is `PlusTimes`. Other semirings turn the same
+/// product into graph algorithms: `MinPlus` relaxes shortest paths, `OrAnd`
+/// propagates reachability and `MaxMin` finds bottleneck paths.
+pub trait Semiring<T> {
+    /// The identity of `add`, which is also an annihilator of `mul`.
+    fn zero(&self) -> T;
+
+    /// The identity of `mul`.
+    fn one(&self) -> T;
+
+    /// Combine two partial results.
+    fn add(&self, a: T, b: T) -> T;
+
+    /// Combine a pair of matched elements.
+    fn mul(&self, a: T, b: T) -> T;
+
+    /// Whether `value` is equal to `self.zero()`.
+    ///
+    /// Products drop result elements for which this returns true.
+    fn is_zero(&self, value: &T) -> bool;
+}
+
+/// Types with a smallest and a largest value.
+///
+/// Floating point types use negative and positive infinity.
+pub trait Bounded {
+    fn min_value() -> Self;
+    fn max_value() -> Self;
+}
+
+macro_rules! impl_bounded {
+    ($min:ident, $max:ident; $($t:ident)*) => {
+        $(
+            impl Bounded for $t {
+                fn min_value() -> $t {
+                    $t::$min
+                }
+
+                fn max_value() -> $t {
+                    $t::$max
+                }
+            }
+        )*
+    }
+}
+
+impl_bounded!(MIN, MAX; i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize);
+impl_bounded!(NEG_INFINITY, INFINITY; f32 f64);
+
+/// The smaller of `a` and `b`, preferring `a` if they are unordered.
+pub(crate) fn min<T: PartialOrd>(a: T, b: T) -> T {
+    if b < a { b } else { a }
+}
+
+/// The larger of `a` and `b`, preferring `a` if they are unordered.
+pub(crate) fn max<T: PartialOrd>(a: T, b: T) -> T {
+    if b > a { b } else { a }
+}
+
+/// The arithmetic semiring `(+, *, 0, 1)`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PlusTimes;
+
+impl<T> Semiring<T> for PlusTimes
+    where T: Zero + One + Add<Output = T> + Mul<Output = T>
+{
+    fn zero(&self) -> T {
+        T::zero()
+    }
+
+    fn one(&self) -> T {
+        T::one()
+    }
+
+    fn add(&self, a: T, b: T) -> T {
+        a + b
+    }
+
+    fn mul(&self, a: T, b: T) -> T {
+        a * b
+    }
+
+    fn is_zero(&self, value: &T) -> bool {
+        value.is_zero()
+    }
+}
+
+/// The tropical semiring `(min, +, max_value, 0)`.
+///
+/// A product of edge-weight matrices gives the lengths of the shortest paths
+/// that use one edge from each operand. Absent elements mean "no path".
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MinPlus;
+
+impl<T> Semiring<T> for MinPlus
+    where T: Zero + Bounded + PartialOrd + Add<Output = T>
+{
+    fn zero(&self) -> T {
+        T::max_value()
+    }
+
+    fn one(&self) -> T {
+        T::zero()
+    }
+
+    fn add(&self, a: T, b: T) -> T {
+        min(a, b)
+    }
+
+    fn mul(&self, a: T, b: T) -> T {
+        // Check for the annihilator explicitly so that integer types do not
+        // overflow when adding to `max_value`.
+        if self.is_zero(&a) || self.is_zero(&b) {
+            return self.zero();
+        }
+        a + b
+    }
+
+    fn is_zero(&self, value: &T) -> bool {
+        *value == T::max_value()
+    }
+}
+
+/// The semiring `(max, +, min_value, 0)`.
+///
+/// A product of edge-weight matrices gives the lengths of the longest paths
+/// that use one edge from each operand.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MaxPlus;
+
+impl<T> Semiring<T> for MaxPlus
+    where T: Zero + Bounded + PartialOrd + Add<Output = T>
+{
+    fn zero(&self) -> T {
+        T::min_value()
+    }
+
+    fn one(&self) -> T {
+        T::zero()
+    }
+
+    fn add(&self, a: T, b: T) -> T {
+        max(a, b)
+    }
+
+    fn mul(&self, a: T, b: T) -> T {
+        if self.is_zero(&a) || self.is_zero(&b) {
+            return self.zero();
+        }
+        a + b
+    }
+
+    fn is_zero(&self, value: &T) -> bool {
+        *value == T::min_value()
+    }
+}
+
+/// The boolean semiring `(||, &&, false, true)`.
+///
+/// A product of adjacency matrices tells whether a path exists that uses one
+/// edge from each operand.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OrAnd;
+
+impl Semiring<bool> for OrAnd {
+    fn zero(&self) -> bool {
+        false
+    }
+
+    fn one(&self) -> bool {
+        true
+    }
+
+    fn add(&self, a: bool, b: bool) -> bool {
+        a || b
+    }
+
+    fn mul(&self, a: bool, b: bool) -> bool {
+        a && b
+    }
+
+    fn is_zero(&self, value: &bool) -> bool {
+        !*value
+    }
+}
+
+/// The semiring `(max, min, min_value, max_value)`.
+///
+/// A product of edge-capacity matrices gives the capacities of the widest
+/// paths that use one edge from each operand, where the capacity of a path
+/// is that of its narrowest edge.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MaxMin;
+
+impl<T> Semiring<T> for MaxMin
+    where T: Bounded + PartialOrd
+{
+    fn zero(&self) -> T {
+        T::min_value()
+    }
+
+    fn one(&self) -> T {
+        T::max_value()
+    }
+
+    fn add(&self, a: T, b: T) -> T {
+        max(a, b)
+    }
+
+    fn mul(&self, a: T, b: T) -> T {
+        min(a, b)
+    }
+
+    fn is_zero(&self, value: &T) -> bool {
+        *value == T::min_value()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MaxMin, MaxPlus, MinPlus, OrAnd, PlusTimes, Semiring};
+
+    /// Check the identity and annihilator laws of `s` on `values`.
+    fn check_identities<T, S>(s: &S, values: &[T])
+        where T: Clone + PartialEq + ::std::fmt::Debug,
+              S: Semiring<T>
+    {
+        for v in values {
+            assert_eq!(s.add(s.zero(), v.clone()), *v);
+            assert_eq!(s.mul(s.one(), v.clone()), *v);
+            assert!(s.is_zero(&s.mul(s.zero(), v.clone())));
+        }
+    }
+
+    #[test]
+    fn test_identities() {
+        check_identities(&PlusTimes, &[-2.0, 0.0, 3.5]);
+        check_identities(&MinPlus, &[-2.0, 0.0, 3.5, f64::INFINITY]);
+        check_identities(&MinPlus, &[-2, 0, 7, i32::MAX]);
+        check_identities(&MaxPlus, &[-2, 0, 7, i64::MIN]);
+        check_identities(&OrAnd, &[false, true]);
+        check_identities(&MaxMin, &[0u8, 3, 255]);
+    }
+
+    #[test]
+    fn test_operations() {
+        assert_eq!(MinPlus.add(3, 5), 3);
+        assert_eq!(MinPlus.mul(3, 5), 8);
+        assert_eq!(MaxPlus.add(3, 5), 5);
+        assert_eq!(MaxMin.add(3, 5), 5);
+        assert_eq!(MaxMin.mul(3, 5), 3);
+        assert!(OrAnd.add(true, false));
+        assert!(!OrAnd.mul(true, false));
+    }
+}
@@ -30,6 +30,7 @@ pub mod itertools {
     {
         type Item = (I::Item, J::Item);
 
+        #[allow(clippy::manual_map)]
         fn next(&mut self) -> Option<(I::Item, J::Item)> {
 
             // We need to get the second element first because if `second` is
@@ -75,6 +76,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::never_loop)]
     fn test_cartesian_product_empty() {
         for (_, _) in cartesian_product(0..0, 0..5) {
             panic!("Cartesian product of empty input should not yield.");